@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+use crossterm::event::{Event, KeyEvent};
+
+// key presses closer together than this come from holding the key down
+const REPEAT_INTERVAL: Duration = Duration::from_millis(100);
+
+// how a held movement key speeds up: after so many repeats, move so many rows per key.
+// Without steps there is no acceleration
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccelCurve {
+    steps: Vec<(usize, u16)>,   // ascending repeats
+}
+
+impl Default for AccelCurve {
+    fn default() -> Self {
+        AccelCurve { steps: vec![(10, 2), (30, 4)] }
+    }
+}
+
+impl AccelCurve {
+    // "off", or repeats:rows pairs like "10:2,30:4"
+    pub fn parse(value: &str) -> Result<Self> {
+        if value == "off" {
+            return Ok(AccelCurve { steps: Vec::new() });
+        }
+        let mut steps = Vec::new();
+        for step in value.split(',') {
+            let Some((repeats, rows)) = step.trim().split_once(':') else {
+                return Err(anyhow!("expected off or REPEATS:ROWS,..."));
+            };
+            let (repeats, rows) = (repeats.trim().parse()?, rows.trim().parse()?);
+            if rows == 0 {
+                return Err(anyhow!("must move at least one row"));
+            }
+            steps.push((repeats, rows));
+        }
+        steps.sort();
+
+        Ok(AccelCurve { steps })
+    }
+
+    fn rows(&self, repeats: usize) -> u16 {
+        self.steps.iter()
+            .take_while(|&&(r, _)| r <= repeats)
+            .last()
+            .map_or(1, |&(_, rows)| rows)
+    }
+}
+
+// counts how long the same key has been held
+#[derive(Debug, Clone)]
+pub struct Accel {
+    curve: AccelCurve,
+    last: Option<(KeyEvent, Instant)>,
+    repeats: usize,
+}
+
+impl Accel {
+    pub fn new(curve: AccelCurve) -> Self {
+        Accel { curve, last: None, repeats: 0 }
+    }
+
+    // rows to move for the event, if it is a movement
+    pub fn rows(&mut self, event: Option<&Event>) -> u16 {
+        self.rows_at(event, Instant::now())
+    }
+
+    fn rows_at(&mut self, event: Option<&Event>, now: Instant) -> u16 {
+        let Some(&Event::Key(key)) = event else {
+            return 1;
+        };
+        let held = self.last.is_some_and(|(last, at)| {
+            last == key && now.duration_since(at) < REPEAT_INTERVAL
+        });
+        self.repeats = if held { self.repeats + 1 } else { 0 };
+        self.last = Some((key, now));
+
+        self.curve.rows(self.repeats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    #[test]
+    fn test_accel() {
+        assert!(AccelCurve::parse("10:2,x").is_err());
+        assert!(AccelCurve::parse("10:0").is_err());
+        assert_eq!(AccelCurve::parse("off").unwrap().rows(100), 1);
+        let curve = AccelCurve::parse("20:3, 5:2").unwrap();
+        assert_eq!((curve.rows(4), curve.rows(5), curve.rows(19), curve.rows(20)), (1, 2, 2, 3));
+
+        let mut accel = Accel::new(curve);
+        let j = Event::Key(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE));
+        let k = Event::Key(KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE));
+        let mut now = Instant::now();
+        let mut rows = Vec::new();
+        for _ in 0..7 {
+            now += Duration::from_millis(30);
+            rows.push(accel.rows_at(Some(&j), now));
+        }
+        assert_eq!(rows, vec![1, 1, 1, 1, 1, 2, 2]);
+        // a pause or another key starts over
+        assert_eq!(accel.rows_at(Some(&j), now + Duration::from_secs(1)), 1);
+        assert_eq!(accel.rows_at(Some(&k), now + Duration::from_millis(1030)), 1);
+    }
+}
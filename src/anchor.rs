@@ -0,0 +1,155 @@
+use std::fmt;
+use anyhow::{anyhow, Result};
+
+use crate::lines::*;
+use crate::pattern::PatternSet;
+use crate::cache::SearchType;
+use crate::qhash::fnv1a;
+
+// how far from the old offset the line is looked for, in bytes on each side. Reading all of
+// a big file for a line that is gone would take long
+const RESOLVE_WINDOW: LineId = 64 << 20;
+
+// a reference to a line that survives small edits of the file: where the line was, a hash
+// of its content and a hash of the lines around it. Written as OFFSET.HASH.CONTEXT in hex
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LineAnchor {
+    offset: LineId,
+    hash: u32,
+    context: u16,
+}
+
+// the content of a line without the newline, empty if there is no line
+fn line_bytes(lines: &Lines, line_id: Option<LineId>, patterns: &PatternSet) -> Vec<u8> {
+    let Some((_, mut bytes)) = line_id.and_then(|id| lines.get_raw(id, patterns)) else {
+        return Vec::new();
+    };
+    if bytes.last() == Some(&b'\n') {
+        bytes.pop();
+    }
+
+    bytes
+}
+
+fn line_hash(lines: &Lines, line_id: LineId, patterns: &PatternSet) -> u32 {
+    fnv1a(&line_bytes(lines, Some(line_id), patterns)) as u32
+}
+
+// hash of the lines before and after the line, regardless of what is displayed
+fn context_hash(lines: &Lines, line_id: LineId, patterns: &PatternSet) -> u16 {
+    let prev = lines.prev_line(SearchType::Tag, line_id, patterns, DisplayMode::All, false);
+    let next = lines.next_line(SearchType::Tag, line_id, patterns, DisplayMode::All, false);
+    let mut bytes = line_bytes(lines, prev, patterns);
+    bytes.push(b'\n');
+    bytes.extend(line_bytes(lines, next, patterns));
+
+    fnv1a(&bytes) as u16
+}
+
+impl LineAnchor {
+    pub fn new(lines: &Lines, line_id: LineId, patterns: &PatternSet) -> Self {
+        LineAnchor {
+            offset: line_id,
+            hash: line_hash(lines, line_id, patterns),
+            context: context_hash(lines, line_id, patterns),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid anchor {}, expected OFFSET.HASH.CONTEXT", s);
+        let parts = s.split('.').collect::<Vec<_>>();
+        let [offset, hash, context] = parts[..] else {
+            return Err(invalid());
+        };
+
+        Ok(LineAnchor {
+            offset: LineId::from_str_radix(offset, 16).map_err(|_| invalid())?,
+            hash: u32::from_str_radix(hash, 16).map_err(|_| invalid())?,
+            context: u16::from_str_radix(context, 16).map_err(|_| invalid())?,
+        })
+    }
+
+    // where the line was, if nothing better is found
+    pub fn offset(&self) -> LineId {
+        self.offset
+    }
+
+    // the line nearest to the old offset with the same content and context. Failing that,
+    // the nearest one with the same content. Only lines within RESOLVE_WINDOW are looked at
+    pub fn resolve(&self, lines: &Lines, patterns: &PatternSet) -> Option<LineId> {
+        self.resolve_within(lines, patterns, RESOLVE_WINDOW)
+    }
+
+    fn resolve_within(&self, lines: &Lines, patterns: &PatternSet, window: LineId)
+        -> Option<LineId>
+    {
+        let start = self.offset.min(lines.last_line_id());
+        let within = |id: &LineId| id.abs_diff(start) <= window;
+        let mut forward = lines.next_line(SearchType::Tag, start, patterns, DisplayMode::All,
+            true).filter(within);
+        let mut backward = forward.and_then(|id| lines.prev_line(SearchType::Tag, id, patterns,
+            DisplayMode::All, false)).filter(within);
+        let mut fallback = None;
+        while forward.is_some() || backward.is_some() {
+            for line_id in [forward, backward].into_iter().flatten() {
+                if line_hash(lines, line_id, patterns) != self.hash {
+                    continue;
+                }
+                if context_hash(lines, line_id, patterns) == self.context {
+                    return Some(line_id);
+                }
+                fallback = fallback.or(Some(line_id));
+            }
+            forward = forward.and_then(|id| lines.next_line(SearchType::Tag, id, patterns,
+                DisplayMode::All, false)).filter(within);
+            backward = backward.and_then(|id| lines.prev_line(SearchType::Tag, id, patterns,
+                DisplayMode::All, false)).filter(within);
+        }
+
+        fallback
+    }
+}
+
+impl fmt::Display for LineAnchor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:x}.{:08x}.{:04x}", self.offset, self.hash, self.context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempPath;
+    use crate::MarkStyle;
+
+    #[test]
+    fn test_anchor() {
+        let path = TempPath::file("anchor", "a\nsame\nb\nx\nsame\ny\n");
+        let patterns = PatternSet::new(MarkStyle::new());
+        let lines = Lines::new(path.as_os_str()).unwrap();
+        let anchor = LineAnchor::new(&lines, 11, &patterns);
+        assert_eq!(LineAnchor::parse(&anchor.to_string()).unwrap(), anchor);
+        assert!(LineAnchor::parse("12.34").is_err());
+        assert!(LineAnchor::parse("12.xyz.0").is_err());
+        drop(lines);
+
+        // lines inserted before it: the same content with the same context wins over
+        // the nearer one with different neighbours
+        std::fs::write(&path, "new\nlines\nx\nsame\nb\nx\nsame\ny\n").unwrap();
+        let lines = Lines::new(path.as_os_str()).unwrap();
+        assert_eq!(anchor.resolve(&lines, &patterns), Some(21));
+        // too far away for a small window, the near line with the same content has to do
+        assert_eq!(anchor.resolve_within(&lines, &patterns, 3), Some(12));
+        drop(lines);
+
+        // with the context gone, the nearest line with the same content
+        std::fs::write(&path, "same\n1\n2\nsame\n").unwrap();
+        let lines = Lines::new(path.as_os_str()).unwrap();
+        assert_eq!(anchor.resolve(&lines, &patterns), Some(9));
+        drop(lines);
+
+        std::fs::write(&path, "other\n").unwrap();
+        let lines = Lines::new(path.as_os_str()).unwrap();
+        assert_eq!(anchor.resolve(&lines, &patterns), None);
+    }
+}
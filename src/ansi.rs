@@ -0,0 +1,181 @@
+use std::borrow::Cow;
+use ratatui::style::{Color, Modifier, Style};
+
+const ESC: u8 = 0x1b;
+
+// length of the escape sequence at the start of bytes. CSI sequences end with a final byte,
+// OSC sequences with BEL or ST, all others with the first byte after their intermediate
+// bytes. An unterminated sequence takes the rest of the line, but never the newline, so
+// whole splits are stripped like their lines one by one
+fn escape_len(bytes: &[u8]) -> usize {
+    match bytes.get(1) {
+        Some(b'[') => match bytes.iter().skip(2)
+            .position(|&b| (0x40..=0x7e).contains(&b) || b == b'\n')
+        {
+            Some(p) if bytes[p + 2] == b'\n' => p + 2,
+            Some(p) => p + 3,
+            None => bytes.len(),
+        },
+        Some(b']') => (2..bytes.len()).find_map(|i| match bytes[i] {
+            0x07 => Some(i + 1),
+            b'\n' => Some(i),
+            ESC if bytes.get(i + 1) == Some(&b'\\') => Some(i + 2),
+            _ => None,
+        }).unwrap_or(bytes.len()),
+        _ => {
+            let end = 1 + bytes[1..].iter().take_while(|b| (0x20..=0x2f).contains(*b)).count();
+            // the final byte may start a multibyte char, keep the sequence on char boundaries
+            let final_len = match bytes.get(end) {
+                Some(b'\n') | None => 0,
+                Some(&b) => b.leading_ones().max(1) as usize,
+            };
+            (end + final_len).min(bytes.len())
+        }
+    }
+}
+
+// what is displayed of a line, the escape sequences removed. Lines are passed through
+// unless escapes are interpreted
+pub fn visible(bytes: &[u8], enabled: bool) -> Cow<'_, [u8]> {
+    if !enabled {
+        return Cow::Borrowed(bytes);
+    }
+    strip(bytes)
+}
+
+fn strip(bytes: &[u8]) -> Cow<'_, [u8]> {
+    if !bytes.contains(&ESC) {
+        return Cow::Borrowed(bytes);
+    }
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == ESC {
+            i += escape_len(&bytes[i..]);
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+// the line without escape sequences and the style the SGR sequences give each char of it
+pub fn parse(line: &str) -> (String, Vec<Style>) {
+    let mut text = String::with_capacity(line.len());
+    let mut styles = Vec::with_capacity(line.len());
+    let mut style = Style::default();
+    let mut rest = line;
+    while let Some(pos) = rest.find('\x1b') {
+        for c in rest[..pos].chars() {
+            text.push(c);
+            styles.push(style);
+        }
+        let len = escape_len(&rest.as_bytes()[pos..]).min(rest.len() - pos);
+        let seq = &rest[pos..pos + len];
+        if let Some(params) = seq.strip_prefix("\x1b[").and_then(|s| s.strip_suffix('m')) {
+            style = sgr(style, params);
+        }
+        rest = &rest[pos + len..];
+    }
+    for c in rest.chars() {
+        text.push(c);
+        styles.push(style);
+    }
+
+    (text, styles)
+}
+
+const COLORS: [Color; 16] = [
+    Color::Black, Color::Red, Color::Green, Color::Yellow,
+    Color::Blue, Color::Magenta, Color::Cyan, Color::Gray,
+    Color::DarkGray, Color::LightRed, Color::LightGreen, Color::LightYellow,
+    Color::LightBlue, Color::LightMagenta, Color::LightCyan, Color::White,
+];
+
+// 38;5;N or 38;2;R;G;B, the params after the 38 or 48
+fn extended_color(params: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match params.next()? {
+        5 => Some(Color::Indexed(params.next()?)),
+        2 => Some(Color::Rgb(params.next()?, params.next()?, params.next()?)),
+        _ => None,
+    }
+}
+
+// unlike remove_modifier, this doesn't take the modifier away from styles it's patched over
+fn without(style: Style, modifier: Modifier) -> Style {
+    Style { add_modifier: style.add_modifier - modifier, ..style }
+}
+
+// apply the parameters of an SGR sequence. Unknown ones are ignored
+fn sgr(mut style: Style, params: &str) -> Style {
+    // an empty parameter is a 0, out of range ones are ignored
+    let mut params = params.split(';').map(|p| match p {
+        "" => 0,
+        p => p.parse::<u8>().unwrap_or(u8::MAX),
+    });
+    while let Some(p) = params.next() {
+        style = match p {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            22 => without(style, Modifier::BOLD | Modifier::DIM),
+            23 => without(style, Modifier::ITALIC),
+            24 => without(style, Modifier::UNDERLINED),
+            25 => without(style, Modifier::SLOW_BLINK),
+            27 => without(style, Modifier::REVERSED),
+            29 => without(style, Modifier::CROSSED_OUT),
+            30..=37 => style.fg(COLORS[p as usize - 30]),
+            38 => match extended_color(&mut params) {
+                Some(color) => style.fg(color),
+                None => style,
+            },
+            39 => Style { fg: None, ..style },
+            40..=47 => style.bg(COLORS[p as usize - 40]),
+            48 => match extended_color(&mut params) {
+                Some(color) => style.bg(color),
+                None => style,
+            },
+            49 => Style { bg: None, ..style },
+            90..=97 => style.fg(COLORS[p as usize - 90 + 8]),
+            100..=107 => style.bg(COLORS[p as usize - 100 + 8]),
+            _ => style,
+        };
+    }
+
+    style
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let (text, styles) = parse("a\x1b[1;31mbc\x1b[0m d\x1b[38;5;208;44me\x1b[K\x1b]0;t\x07f");
+        assert_eq!(text, "abc def");
+        let red = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+        let orange = Style::default().fg(Color::Indexed(208)).bg(Color::Blue);
+        assert_eq!(styles, vec![Style::default(), red, red, Style::default(),
+            Style::default(), orange, orange]);
+
+        // unknown and broken sequences are removed, the style is kept
+        let (text, styles) = parse("\x1b[32mx\x1b[99my\x1b(Bz\x1b[");
+        assert_eq!(text, "xyz");
+        assert_eq!(styles, vec![Style::default().fg(Color::Green); 3]);
+        assert_eq!(sgr(red, "22;39"), Style::default());
+    }
+
+    #[test]
+    fn test_strip_split() {
+        // unterminated sequences in the middle of a split end with their line
+        let split = b"a\x1b]0;title\nerror\x1b\nb\x1b[1;3\nc\x1b[31md\n";
+        assert_eq!(&strip(split)[..], b"a\nerror\nb\ncd\n".as_slice());
+    }
+}
@@ -1,51 +1,102 @@
 use anyhow::Result;
 use lru::LruCache;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::num::NonZeroUsize;
+use std::ops::{Deref, Range};
 use std::sync::Arc;
 use std::cell::RefCell;
 use std::fs::File;
-use std::io::{Seek, SeekFrom};
-use std::io::Read;
 use clog::prelude::*;
+use memmap2::Mmap;
 
 use crate::log::LogKeys::CA;
 use crate::search::SplitId;
-use crate::search::FileSearch;
+use crate::search::{self, FileSearch};
 use crate::pattern::*;
 use crate::lines::LineId;
 
-// when changed, also change SearchType::max
+// each search type has its own slot in the background search
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SearchType {
-    Tag = 0,
-    Search = 1,
+    Tag,
+    Search,
+    Hide,   // matches only splits where all lines are hidden, also by negative patterns
 }
 
 impl SearchType {
+    // all search types, in slot order
+    pub const ALL: [SearchType; 3] = [SearchType::Tag, SearchType::Search, SearchType::Hide];
+
     pub fn as_ix(&self) -> usize {
         *self as usize
     }
-    fn max() -> usize {
-        2
+
+    // the patterns searched for in this slot
+    fn re(&self, patterns: &PatternSet) -> LineMatcher {
+        match self {
+            SearchType::Tag => LineMatcher::new(patterns.get_tagged_re())
+                .with_literals(patterns.literals(PatternMode::Tagging))
+                .with_ansi(patterns.ansi()),
+            SearchType::Search => LineMatcher::new(patterns.get_search_re())
+                .with_literals(patterns.literals(PatternMode::Search))
+                .with_ansi(patterns.ansi()),
+            SearchType::Hide => patterns.get_hidden_re(),
+        }
+    }
+
+    // does a split only match if all of its lines match
+    fn all_lines(&self) -> bool {
+        *self == SearchType::Hide
+    }
+
+    // are matching lines counted, for display
+    fn counted(&self) -> bool {
+        *self == SearchType::Search
+    }
+}
+
+// the bytes of a split, read from the file or a part of the mapped file
+#[derive(Debug)]
+pub enum SplitBuf {
+    Read(Vec<u8>),
+    Mapped(Arc<Mmap>, Range<usize>),
+}
+
+impl Deref for SplitBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SplitBuf::Read(buf) => buf,
+            SplitBuf::Mapped(map, range) => &map[range.clone()],
+        }
+    }
+}
+
+impl PartialEq for SplitBuf {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
     }
 }
 
 #[derive(Debug)]
 pub struct Split {
     pub pattern_seq: PatternId,
-    pub buf: Vec<u8>,
+    pub buf: SplitBuf,
     pub line_ends: Vec<usize>,
     pub tagged_lines: Vec<usize>,
     pub search_lines: Vec<usize>,
     pub hidden_lines: Vec<usize>,
+    pub unreadable: bool, // a placeholder line instead of the content
 }
 
 #[derive(Debug)]
 pub struct SplitCacheInner {
     lru: LruCache<SplitId, Arc<Split>>,
     file_search: FileSearch,
-    file: File,
+    filename: OsString,
+    file: Option<File>,
+    map: Option<Arc<Mmap>>,
 }
 
 #[derive(Debug)]
@@ -54,15 +105,62 @@ pub struct SplitCache {
 }
 
 impl SplitCache {
-    pub fn new(filename: &OsStr, nsplits: NonZeroUsize) -> Result<Self> {
+    // nsplits are kept in memory, each of about chunk_size bytes
+    pub fn new(filename: &OsStr, nsplits: NonZeroUsize, chunk_size: LineId) -> Result<Self> {
         let file = File::open(filename)?;
         Ok(SplitCache { inner: RefCell::new(SplitCacheInner {
             lru: LruCache::new(nsplits),
-            file_search: FileSearch::new(filename, SearchType::max())?,
-            file,
+            file_search: FileSearch::new(filename, SearchType::ALL.len(), chunk_size)?,
+            filename: filename.into(),
+            file: Some(file),
+            map: None,
         })})
     }
 
+    // map the file into memory. Splits are then slices of it, for the cache and the
+    // background search alike, instead of copies read from the file
+    pub fn map_file(&self) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        Self::remap(&mut inner)?;
+        // splits read before are read again as slices
+        inner.lru.clear();
+
+        Ok(())
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.inner.borrow().map.is_some()
+    }
+
+    fn remap(inner: &mut SplitCacheInner) -> Result<()> {
+        let file = File::open(&inner.filename)?;
+        // safety: the file must not be truncated while it is mapped, see --mmap
+        let map = Arc::new(unsafe { Mmap::map(&file)? });
+        inner.file_search.set_map(map.clone());
+        inner.map = Some(map);
+
+        Ok(())
+    }
+
+    // the split as a slice of the mapped file, if it is mapped. Maps it again if it has grown
+    fn mapped(inner: &mut SplitCacheInner, start: LineId, end: LineId) -> Option<SplitBuf> {
+        let len = inner.map.as_ref()?.len() as LineId;
+        if len < end {
+            if let Err(e) = Self::remap(inner) {
+                lD1!(CA, "failed to map the file again: {}", e);
+                return None;
+            }
+        }
+        let map = inner.map.as_ref()?;
+        // touching pages beyond the end of the file would crash, read it instead to find out
+        let file_len = std::fs::metadata(&inner.filename).ok()?.len();
+        if (map.len() as LineId) < end || file_len < end {
+            return None;
+        }
+
+        Some(SplitBuf::Mapped(map.clone(), start as usize..end as usize))
+    }
+
     pub fn num_splits(&self) -> usize {
         let inner = self.inner.borrow();
         inner.file_search.num_splits()
@@ -78,21 +176,67 @@ impl SplitCache {
         inner.file_search.get_split(split)
     }
 
+    // check if the file has grown. Returns the first split that changed
+    pub fn grow(&self) -> Result<Option<SplitId>> {
+        let mut inner = self.inner.borrow_mut();
+        let first = inner.file_search.grow()?;
+        if let Some(first) = first {
+            // the last split might have gotten more lines
+            inner.lru.pop(&first);
+        }
+
+        Ok(first)
+    }
+
+    // read the file from scratch, after it was replaced or truncated
+    pub fn reset(&self) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        inner.file_search.reset()?;
+        inner.lru.clear();
+        inner.file = Some(File::open(&inner.filename)?);
+        if inner.map.is_some() {
+            Self::remap(&mut inner)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn filename(&self) -> OsString {
+        self.inner.borrow().filename.clone()
+    }
+
     pub fn get_file_search(&self) -> FileSearch {
         let inner = self.inner.borrow();
         inner.file_search.clone()
     }
 
+    // number of lines before the split, blocks until they are counted
+    pub fn lines_before(&self, split_id: SplitId) -> u64 {
+        let inner = self.inner.borrow();
+        inner.file_search.lines_before(split_id)
+    }
+
+    // like lines_before, but None until they are counted
+    pub fn counted_lines_before(&self, split_id: SplitId) -> Option<u64> {
+        let inner = self.inner.borrow();
+        inner.file_search.counted_lines_before(split_id)
+    }
+
     pub fn set_re(&self, st: SearchType, patterns: &PatternSet) {
         let mut inner = self.inner.borrow_mut();
-        match st {
-            SearchType::Tag => {
-                inner.file_search.set_re(st.as_ix(), &patterns.get_tagged_re());
-            }
-            SearchType::Search => {
-                inner.file_search.set_re(st.as_ix(), &patterns.get_search_re());
-            }
-        }
+        inner.file_search.set_re(st.as_ix(), &st.re(patterns), st.all_lines(), st.counted());
+    }
+
+    // lines counted so far, and if that are all of them
+    pub fn line_count(&self) -> (u64, bool) {
+        let inner = self.inner.borrow();
+        inner.file_search.line_count()
+    }
+
+    // matching lines found so far, and if the search is complete
+    pub fn match_count(&self, st: SearchType) -> Option<(u64, bool)> {
+        let inner = self.inner.borrow();
+        inner.file_search.match_count(st.as_ix())
     }
 
     pub fn has_matches(&self, st: SearchType, split_id: SplitId) -> bool {
@@ -100,14 +244,99 @@ impl SplitCache {
         inner.file_search.split_has_matches(st.as_ix(), split_id)
     }
 
+    // like has_matches, but None instead of waiting for the split to be searched
+    pub fn searched(&self, st: SearchType, split_id: SplitId) -> Option<bool> {
+        let inner = self.inner.borrow();
+        inner.file_search.split_searched(st.as_ix(), split_id)
+    }
+
+    // None if the split is not in the cache, otherwise whether it is up to date with the
+    // patterns. Doesn't count as a use of the split
+    pub fn cached(&self, split_id: SplitId, patterns: &PatternSet) -> Option<bool> {
+        let inner = self.inner.borrow();
+        inner.lru.peek(&split_id).map(|split| split.pattern_seq == patterns.seq)
+    }
+
+    // the last error reading the file, in the foreground or the background
+    pub fn take_read_error(&self) -> Option<String> {
+        let inner = self.inner.borrow();
+        inner.file_search.take_read_error()
+    }
+
     pub fn set_current_split(&self, split_id: SplitId) {
         let mut inner = self.inner.borrow_mut();
         inner.file_search.set_current_split(split_id);
     }
 
+    // read the split and find the line ends. If it can't be read, the split gets a single
+    // placeholder line saying so. It's read again once it drops out of the cache or the
+    // patterns change
+    fn read(inner: &mut SplitCacheInner, split_id: SplitId, pattern_seq: PatternId) -> Split {
+        let Some((start, end)) = inner.file_search.get_split(split_id) else {
+            panic!("split {} not found", split_id);
+        };
+        let (buf, unreadable) = match Self::mapped(inner, start, end) {
+            Some(buf) => (buf, false),
+            None => Self::read_buf(inner, split_id, start, end),
+        };
+
+        let mut line_ends = Vec::new();
+        let mut start = 0;
+        loop {
+            lD10!(CA, "start {} buflen {}", start, buf.len());
+            let Some(line_end) = memchr::memchr(b'\n', &buf[start..]) else {
+                break;
+            };
+            lD10!(CA, "line_end {}", line_end);
+            line_ends.push(start + line_end + 1);
+            start += line_end + 1;
+        }
+        if line_ends.last() != Some(&buf.len()) {
+            // the file was changed in place
+            lD1!(CA, "split {} doesn't end with a newline", split_id);
+            line_ends.push(buf.len());
+        }
+        if !unreadable {
+            inner.file_search.set_line_count(split_id, line_ends.len() as u64);
+        }
+
+        lD5!(CA, "split read done");
+
+        Split {
+            pattern_seq,
+            buf,
+            line_ends,
+            tagged_lines: Vec::new(),
+            search_lines: Vec::new(),
+            hidden_lines: Vec::new(),
+            unreadable,
+        }
+    }
+
+    // the split read from the file, or a placeholder line and true if that failed
+    fn read_buf(inner: &mut SplitCacheInner, split_id: SplitId, start: LineId, end: LineId)
+        -> (SplitBuf, bool)
+    {
+        let buflen = (end - start) as usize;
+        let mut buf = Vec::new();
+        let res = search::read_split(&mut inner.file, &inner.filename, start, buflen, &mut buf,
+            false);
+        match res {
+            Ok(()) => (SplitBuf::Read(buf), false),
+            Err(e) => {
+                lD1!(CA, "failed to read split {}: {}", split_id, e);
+                inner.file_search.report_read_error(e.to_string());
+                let buf = format!("<{} bytes at offset {} could not be read: {}>\n", buflen,
+                    start, e);
+                (SplitBuf::Read(buf.into_bytes()), true)
+            }
+        }
+    }
+
     pub fn get(&self, split_id: SplitId, patterns: &PatternSet) -> Result<Arc<Split>> {
         lD3!(CA, "get split {}", split_id);
         let mut inner = self.inner.borrow_mut();
+        let inner = &mut *inner;
         let mut split = match inner.lru.pop(&split_id) {
             Some(split) if split.pattern_seq == patterns.seq => {
                 lD3!(CA, "cache hit");
@@ -115,49 +344,17 @@ impl SplitCache {
                 inner.lru.put(split_id, split.clone());
                 return Ok(split.clone());
             }
-            Some(split) => {
+            Some(split) if !split.unreadable => {
                 lD3!(CA, "cache hit, but wrong pattern_seq");
-                Arc::into_inner(split).unwrap()
+                match Arc::into_inner(split) {
+                    Some(split) => split,
+                    // somebody else still holds it, so it can't be reused
+                    None => Self::read(inner, split_id, patterns.seq),
+                }
             }
-            None => {
+            _ => {
                 lD3!(CA, "cache miss");
-                let Some((start, end)) = inner.file_search.get_split(split_id) else {
-                    panic!("split {} not found", split_id);
-                };
-                inner.file.seek(SeekFrom::Start(start as u64))?;
-                // XXX avoid buffer init by using bytes crate?
-                // https://docs.rs/cbuffer/0.3.1/src/cbuffer/lib.rs.html#1-155
-                // you can write within capacity and unsafe set_len()
-                //< Widdershins> sensille: specifically you may be looking at set_len and
-                //   spare_capacity_mut in vec
-                // 22:10 < cehteh> ah yes that got stabilized meanwhile :D
-                let buflen = (end - start) as usize;
-                let mut buf = vec![0; buflen];
-                inner.file.read_exact(&mut buf)?;
-
-                let mut line_ends = Vec::new();
-                let mut start = 0;
-                loop {
-                    lD10!(CA, "start {} buflen {}", start, buflen);
-                    let Some(line_end) = memchr::memchr(b'\n', &buf[start..]) else {
-                        break;
-                    };
-                    lD10!(CA, "line_end {}", line_end);
-                    line_ends.push(start + line_end + 1);
-                    start += line_end + 1;
-                }
-                assert_eq!(line_ends.last(), Some(&buflen));
-
-                lD5!(CA, "split read done");
-
-                Split {
-                    pattern_seq: patterns.seq,
-                    buf,
-                    line_ends,
-                    tagged_lines: Vec::new(),
-                    search_lines: Vec::new(),
-                    hidden_lines: Vec::new(),
-                }
+                Self::read(inner, split_id, patterns.seq)
             }
         };
 
@@ -171,13 +368,16 @@ impl SplitCache {
         let mut search_lines = Vec::new();
         let mut hidden_lines = Vec::new();
         for (i, &end) in split.line_ends.iter().enumerate() {
-            if tagged_re.is_match(&split.buf[start..end-1]) {
+            // an unterminated last line keeps its last char
+            let bytes = &split.buf[start..end];
+            let line = patterns.visible(bytes.strip_suffix(b"\n").unwrap_or(bytes));
+            if tagged_re.is_match(&line) {
                 tagged_lines.push(i);
             }
-            if hidden_re.is_match(&split.buf[start..end-1]) {
+            if hidden_re.is_match(&line) {
                 hidden_lines.push(i);
             }
-            if search_re.is_match(&split.buf[start..end-1]) {
+            if search_re.is_match(&line) {
                 search_lines.push(i);
             }
             start = end;
@@ -201,6 +401,7 @@ impl SplitCache {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testutil::TempPath;
     use crate::MarkStyle;
     use crate::MarkType;
 
@@ -210,17 +411,296 @@ mod tests {
         let mark_style = MarkStyle::new();
         let mut ps = PatternSet::new(mark_style.clone());
 
-        ps.add("striped-8", MatchType::SmallWord, mark_style.get(MarkType::Mark),
-            PatternMode::Tagging);
-        ps.add("allocd-12", MatchType::SmallWord, mark_style.get(MarkType::Mark),
-            PatternMode::Hiding);
-        ps.add("baz", MatchType::SmallWord, mark_style.get(MarkType::Mark),
-            PatternMode::Marking);
+        ps.add("striped-8", MatchType::SmallWord, Anchor::default(), false,
+            mark_style.get(MarkType::Mark), PatternMode::Tagging).unwrap();
+        ps.add("allocd-12", MatchType::SmallWord, Anchor::default(), false,
+            mark_style.get(MarkType::Mark), PatternMode::Hiding).unwrap();
+        ps.add("baz", MatchType::SmallWord, Anchor::default(), false,
+            mark_style.get(MarkType::Mark), PatternMode::Marking).unwrap();
 
-        let sc = SplitCache::new(filename, NonZeroUsize::new(100).unwrap()).unwrap();
+        let sc = SplitCache::new(filename, NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
         let split = sc.get(0, &ps).unwrap();
         assert_eq!(split.tagged_lines, vec![0]);
         assert_eq!(split.hidden_lines, vec![1]);
     }
-}
 
+    #[test]
+    fn test_search_type_slots() {
+        for (i, st) in SearchType::ALL.iter().enumerate() {
+            assert_eq!(st.as_ix(), i);
+        }
+    }
+
+    #[test]
+    fn test_anchored_pattern() {
+        let path = TempPath::file("anchored", "INTERNAL ERROR: a\nERROR: b\nno ERROR\n");
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        let (pattern, anchor) = Anchor::parse("^ERROR");
+        ps.add(&pattern, MatchType::Text, anchor, false,
+            mark_style.get(MarkType::Tag), PatternMode::Tagging).unwrap();
+        let (pattern, anchor) = Anchor::parse("ERROR$");
+        ps.add(&pattern, MatchType::Text, anchor, false,
+            mark_style.get(MarkType::Hide), PatternMode::Hiding).unwrap();
+
+        // scan of the split
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        let split = sc.get(0, &ps).unwrap();
+        assert_eq!(split.tagged_lines, vec![1]);
+        assert_eq!(split.hidden_lines, vec![2]);
+
+        // processing of single lines, even when cropped
+        let (_, matches, _, _) = ps.process_line("INTERNAL ERROR: a\n", None);
+        assert!(matches.is_empty());
+        let (_, matches, _, _) = ps.process_line("ERROR: b\n", None);
+        assert_eq!(matches, vec![1]);
+        let (_, matches, _, _) = ps.process_line("no ERROR\n", None);
+        assert_eq!(matches, vec![2]);
+        let (_, matches, _, _) = ps.process_line("ERROR at no end\n", Some(5));
+        assert_eq!(matches, vec![1]);
+
+        // a match in the cropped part still counts for the line, without styling anything
+        let far = ps.add("far", MatchType::Text, Anchor::default(), false,
+            mark_style.get(MarkType::Tag), PatternMode::Tagging).unwrap();
+        let (pline, matches, cropped, _) = ps.process_line("ERROR: far out\n", Some(5));
+        assert_eq!(matches, vec![1, far]);
+        assert!(cropped);
+        assert_eq!(pline.len(), 5);
+        assert!(pline.iter().all(|c| c.matches.iter().flatten().all(|&(id, _)| id == 1)));
+    }
+
+    #[test]
+    fn test_grow() {
+        let path = TempPath::file("grow", "first\nsecond\n");
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        ps.add("third", MatchType::SmallWord, Anchor::default(), false,
+            mark_style.get(MarkType::Tag), PatternMode::Tagging).unwrap();
+
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        sc.set_re(SearchType::Tag, &ps);
+        assert_eq!(sc.get(0, &ps).unwrap().line_ends.len(), 2);
+        assert!(!sc.has_matches(SearchType::Tag, 0));
+        assert_eq!(sc.lines_before(1), 2);
+        assert_eq!(sc.grow().unwrap(), None);
+
+        // a partial line is only picked up once it is complete
+        std::fs::write(&path, "first\nsecond\nthird, partial").unwrap();
+        assert_eq!(sc.grow().unwrap(), None);
+        std::fs::write(&path, "first\nsecond\nthird, partial line\n").unwrap();
+        assert_eq!(sc.grow().unwrap(), Some(0));
+        assert_eq!(sc.num_splits(), 1);
+        let split = sc.get(0, &ps).unwrap();
+        assert_eq!(split.line_ends.len(), 3);
+        assert_eq!(split.tagged_lines, vec![2]);
+        assert!(sc.has_matches(SearchType::Tag, 0));
+        assert_eq!(sc.lines_before(1), 3);
+    }
+
+    #[test]
+    fn test_all_hidden() {
+        let path = TempPath::file("hidden", "noise 1\nnoise 2\nnoise 3\n");
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        sc.set_re(SearchType::Hide, &ps);
+        assert!(!sc.has_matches(SearchType::Hide, 0));
+
+        // one line that isn't hidden is enough to keep the split
+        ps.add("noise [12]$", MatchType::Regex, Anchor::default(), false,
+            mark_style.get(MarkType::Hide), PatternMode::Hiding).unwrap();
+        sc.set_re(SearchType::Hide, &ps);
+        assert!(!sc.has_matches(SearchType::Hide, 0));
+
+        ps.add("noise 3", MatchType::Text, Anchor::default(), false,
+            mark_style.get(MarkType::Hide), PatternMode::Hiding).unwrap();
+        sc.set_re(SearchType::Hide, &ps);
+        assert!(sc.has_matches(SearchType::Hide, 0));
+    }
+    #[test]
+    fn test_match_count() {
+        let path = TempPath::file("count", "error 1\nok\nerror 2, error 3\nok\n");
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        ps.add("error", MatchType::Text, Anchor::default(), false,
+            mark_style.get(MarkType::Search), PatternMode::Search).unwrap();
+        sc.set_re(SearchType::Search, &ps);
+        assert!(sc.has_matches(SearchType::Search, 0));
+        // lines are counted, not every match in them
+        assert_eq!(sc.match_count(SearchType::Search), Some((2, true)));
+        assert_eq!(sc.match_count(SearchType::Tag), None);
+    }
+
+    #[test]
+    fn test_literal_search() {
+        let path = TempPath::file("literal", "a.b\naxb\nA.B\n");
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        let id = ps.add("a.b", MatchType::Text, Anchor::default(), false,
+            mark_style.get(MarkType::Search), PatternMode::Search).unwrap();
+        assert_eq!(ps.literals(PatternMode::Search), Some(vec!["a.b".to_string()]));
+        let re = SearchType::Search.re(&ps);
+        assert!(re.is_match(b"x a.b y"));
+        assert!(!re.is_match(b"axb"));
+
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        sc.set_re(SearchType::Search, &ps);
+        assert!(sc.has_matches(SearchType::Search, 0));
+        assert_eq!(sc.match_count(SearchType::Search), Some((1, true)));
+
+        // one pattern that isn't plain text takes all of them to the regex engine
+        ps.remove(id);
+        ps.add("a.b", MatchType::Text, Anchor::default(), true,
+            mark_style.get(MarkType::Search), PatternMode::Search).unwrap();
+        assert_eq!(ps.literals(PatternMode::Search), None);
+        sc.set_re(SearchType::Search, &ps);
+        assert!(sc.has_matches(SearchType::Search, 0));
+        assert_eq!(sc.match_count(SearchType::Search), Some((2, true)));
+    }
+
+    #[test]
+    fn test_pattern_change_mid_scan() {
+        let path = TempPath::new("refine");
+        let content = (0..90000).map(|i| format!("line {:021}\n", i)).collect::<String>();
+        std::fs::write(&path, content).unwrap();
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        let nsplits = sc.num_splits();
+        assert!(nsplits > 1);
+        let id = ps.add("line", MatchType::Text, Anchor::default(), false,
+            mark_style.get(MarkType::Search), PatternMode::Search).unwrap();
+        sc.set_re(SearchType::Search, &ps);
+
+        // refined while the threads are still busy with the first one. The results are right
+        // whether they give up on it early or not, test_abandon in search.rs checks that they do
+        ps.remove(id);
+        ps.add("line [0-9]*89999$", MatchType::Regex, Anchor::default(), false,
+            mark_style.get(MarkType::Search), PatternMode::Search).unwrap();
+        sc.set_re(SearchType::Search, &ps);
+        for split_id in 0..nsplits {
+            assert_eq!(sc.has_matches(SearchType::Search, split_id), split_id == nsplits - 1);
+        }
+        assert_eq!(sc.match_count(SearchType::Search), Some((1, true)));
+    }
+
+    #[test]
+    fn test_rescan_referenced_split() {
+        let path = TempPath::file("rescan", "first\nsecond\n");
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        let old = sc.get(0, &ps).unwrap();
+
+        ps.add("second", MatchType::SmallWord, Anchor::default(), false,
+            mark_style.get(MarkType::Tag), PatternMode::Tagging).unwrap();
+        let split = sc.get(0, &ps).unwrap();
+        assert_eq!(split.tagged_lines, vec![1]);
+        assert_eq!(split.buf, old.buf);
+        assert!(old.tagged_lines.is_empty());
+    }
+
+    #[test]
+    fn test_unterminated_line() {
+        let path = TempPath::file("unterminated", "first\nsecond");
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        ps.add("second", MatchType::SmallWord, Anchor::default(), false,
+            mark_style.get(MarkType::Tag), PatternMode::Tagging).unwrap();
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        let split = sc.get(0, &ps).unwrap();
+        assert_eq!(split.line_ends, vec![6, 12]);
+        assert_eq!(split.tagged_lines, vec![1]);
+    }
+
+    #[test]
+    fn test_unreadable_split() {
+        let path = TempPath::file("unreadable", "first\nsecond\n");
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        ps.add("second", MatchType::SmallWord, Anchor::default(), false,
+            mark_style.get(MarkType::Tag), PatternMode::Tagging).unwrap();
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+
+        // truncated behind our back
+        std::fs::write(&path, "fir").unwrap();
+        let split = sc.get(0, &ps).unwrap();
+        assert!(split.unreadable);
+        assert_eq!(split.line_ends.len(), 1);
+        assert!(String::from_utf8_lossy(&split.buf).starts_with("<13 bytes at offset 0 could not"));
+        assert!(sc.take_read_error().is_some());
+
+        // the background search gives up on it instead of blocking
+        sc.set_re(SearchType::Tag, &ps);
+        assert!(!sc.has_matches(SearchType::Tag, 0));
+        assert_eq!(sc.lines_before(1), 1);
+
+        // it is read again once the file is back
+        std::fs::write(&path, "first\nsecond\n").unwrap();
+        ps.add("first", MatchType::SmallWord, Anchor::default(), false,
+            mark_style.get(MarkType::Tag), PatternMode::Tagging).unwrap();
+        let split = sc.get(0, &ps).unwrap();
+        assert!(!split.unreadable);
+        assert_eq!(split.tagged_lines, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_read_split() {
+        let path = TempPath::new("read");
+        let content = (0..90000).map(|i| format!("line {:021}\n", i)).collect::<String>();
+        std::fs::write(&path, &content).unwrap();
+        let ps = PatternSet::new(MarkStyle::new());
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        let (start, end) = sc.get_split(1).unwrap();
+        let split = sc.get(1, &ps).unwrap();
+        assert_eq!(split.buf[..], content.as_bytes()[start as usize..end as usize]);
+
+        // a reused buffer only holds the new bytes, even if it was longer before
+        let mut file = None;
+        let mut buf = vec![b'x'; 100];
+        search::read_split(&mut file, path.as_os_str(), 27, 54, &mut buf, false).unwrap();
+        assert_eq!(buf, content.as_bytes()[27..81]);
+        let len = content.len() as u64;
+        assert!(search::read_split(&mut file, path.as_os_str(), len - 10, 20, &mut buf, true)
+            .is_err());
+    }
+
+    #[test]
+    fn test_mapped_split() {
+        let path = TempPath::file("mapped", "first\nsecond\n");
+        let mark_style = MarkStyle::new();
+        let mut ps = PatternSet::new(mark_style.clone());
+        ps.add("third", MatchType::SmallWord, Anchor::default(), false,
+            mark_style.get(MarkType::Tag), PatternMode::Tagging).unwrap();
+        let sc = SplitCache::new(path.as_os_str(), NonZeroUsize::new(100).unwrap(),
+            search::SPLIT_CHUNK_SIZE).unwrap();
+        sc.map_file().unwrap();
+        let split = sc.get(0, &ps).unwrap();
+        assert!(matches!(split.buf, SplitBuf::Mapped(..)));
+        assert_eq!(split.buf[..], b"first\nsecond\n"[..]);
+        assert_eq!(split.line_ends, vec![6, 13]);
+        sc.set_re(SearchType::Tag, &ps);
+        assert!(!sc.has_matches(SearchType::Tag, 0));
+
+        // appended lines are mapped again
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        std::io::Write::write_all(&mut file, b"third\n").unwrap();
+        assert_eq!(sc.grow().unwrap(), Some(0));
+        let split = sc.get(0, &ps).unwrap();
+        assert!(matches!(split.buf, SplitBuf::Mapped(..)));
+        assert_eq!(split.tagged_lines, vec![2]);
+        assert!(sc.has_matches(SearchType::Tag, 0));
+    }
+}
@@ -0,0 +1,27 @@
+use anyhow::Result;
+
+#[cfg(feature = "clipboard")]
+thread_local! {
+    // on X11 the owner of the clipboard serves its content, so it has to stay around
+    static CLIPBOARD: std::cell::RefCell<Option<arboard::Clipboard>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+// put text on the system clipboard
+#[cfg(feature = "clipboard")]
+pub fn copy(text: &str) -> Result<()> {
+    CLIPBOARD.with_borrow_mut(|clipboard| {
+        if clipboard.is_none() {
+            *clipboard = Some(arboard::Clipboard::new()?);
+        }
+        clipboard.as_mut().unwrap().set_text(text)?;
+
+        Ok(())
+    })
+}
+
+// builds without the clipboard feature, the default, can't yank
+#[cfg(not(feature = "clipboard"))]
+pub fn copy(_text: &str) -> Result<()> {
+    Err(anyhow::anyhow!("built without the clipboard feature"))
+}
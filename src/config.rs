@@ -0,0 +1,180 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+
+use crate::OffsetDisplay;
+use crate::accel::AccelCurve;
+
+// user preferences, read from ~/.config/logrok/config or the file given with --config.
+// One `key = value` per line, `#` starts a comment. Everything is optional, command line
+// options take precedence
+#[derive(Debug, Default)]
+pub struct Config {
+    pub offsets: Option<OffsetDisplay>,
+    pub marker_width: Option<u16>,
+    pub status_width: Option<u16>,
+    pub help: Option<bool>,
+    pub indent: Option<u16>,
+    pub fast_horizontal: Option<u16>,
+    pub fast_vertical: Option<u16>,
+    pub separator: Option<bool>,
+    pub strip: Option<u16>,
+    pub accel: Option<AccelCurve>,
+    pub word_end: Option<bool>,     // w/W/b/B land on the end of words
+    pub confirm_size: Option<u64>,  // ask before opening bigger files, in bytes
+    pub alert: Option<String>,      // regex for new lines worth a notice in follow mode
+    pub alert_bell: Option<bool>,   // ring the bell on alerts, not only flash the status
+    pub context: Option<u16>,       // lines shown around tagged lines in tagged mode
+    pub collapse_hidden: Option<bool>, // runs of hidden lines show as one row
+    pub scrollbar: Option<bool>,    // position in the file at the right edge of the log
+    pub center_match: Option<bool>, // n/N put the line of the match in the middle of the screen
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config.join("logrok").join("config"))
+}
+
+fn parse_value<T: FromStr>(value: &str) -> Result<T>
+    where T::Err: std::error::Error + Send + Sync + 'static
+{
+    Ok(value.parse::<T>()?)
+}
+
+// a movement step, it has to move at least by one
+fn parse_step(value: &str) -> Result<u16> {
+    match parse_value(value)? {
+        0 => Err(anyhow!("must be at least 1")),
+        step => Ok(step),
+    }
+}
+
+// a number of bytes with an optional K, M, G or T suffix, or off for no limit
+pub fn parse_size(value: &str) -> Result<u64> {
+    if value == "off" {
+        return Ok(u64::MAX);
+    }
+    let (number, shift) = match value.char_indices().last() {
+        Some((i, 'K')) => (&value[..i], 10),
+        Some((i, 'M')) => (&value[..i], 20),
+        Some((i, 'G')) => (&value[..i], 30),
+        Some((i, 'T')) => (&value[..i], 40),
+        _ => (value, 0),
+    };
+    let number: u64 = parse_value(number.trim())?;
+    number.checked_mul(1 << shift).ok_or_else(|| anyhow!("too big"))
+}
+
+impl Config {
+    // a missing default config file is fine, a missing explicitly given one is not
+    pub fn load(path: Option<&OsStr>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (PathBuf::from(path), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default());
+            }
+            Err(e) => return Err(e).with_context(|| format!("{}", path.display())),
+        };
+
+        Self::parse(&content).with_context(|| format!("{}", path.display()))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config = Config::default();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(anyhow!("line {}: expected key = value", i + 1));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            config.set(key, value).with_context(|| format!("line {}: {}", i + 1, key))?;
+        }
+
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "offsets" => self.offsets = Some(OffsetDisplay::from_str(value, true)
+                .map_err(|e| anyhow!(e))?),
+            "marker_width" => self.marker_width = Some(parse_value(value)?),
+            "status_width" => self.status_width = Some(parse_value(value)?),
+            "help" => self.help = Some(parse_value(value)?),
+            "indent" => self.indent = Some(parse_value(value)?),
+            "fast_horizontal" => self.fast_horizontal = Some(parse_step(value)?),
+            "fast_vertical" => self.fast_vertical = Some(parse_step(value)?),
+            "separator" => self.separator = Some(parse_value(value)?),
+            "strip" => self.strip = Some(parse_value(value)?),
+            "accel" => self.accel = Some(AccelCurve::parse(value)?),
+            "word_end" => self.word_end = Some(parse_value(value)?),
+            "confirm_size" => self.confirm_size = Some(parse_size(value)?),
+            "alert" => self.alert = Some(value.to_string()),
+            "alert_bell" => self.alert_bell = Some(parse_value(value)?),
+            "context" => self.context = Some(parse_value(value)?),
+            "collapse_hidden" => self.collapse_hidden = Some(parse_value(value)?),
+            "scrollbar" => self.scrollbar = Some(parse_value(value)?),
+            "center_match" => self.center_match = Some(parse_value(value)?),
+            _ => return Err(anyhow!("unknown setting")),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("# layout\noffsets = line\n\nstatus_width=40  # narrow\n\
+            help = true\nindent = 4\n").unwrap();
+        assert_eq!(config.offsets, Some(OffsetDisplay::Line));
+        assert_eq!(config.status_width, Some(40));
+        assert_eq!(config.help, Some(true));
+        assert_eq!(config.indent, Some(4));
+        assert_eq!(config.marker_width, None);
+
+        assert!(Config::parse("colour = red\n").is_err());
+        assert!(Config::parse("indent = wide\n").is_err());
+        assert!(Config::parse("help\n").is_err());
+
+        let config = Config::parse("fast_horizontal = 10\nfast_vertical = 1\n").unwrap();
+        assert_eq!((config.fast_horizontal, config.fast_vertical), (Some(10), Some(1)));
+        assert!(Config::parse("fast_vertical = 0\n").is_err());
+        assert_eq!(Config::parse("strip = 24\n").unwrap().strip, Some(24));
+        assert_eq!(Config::parse("accel = off\n").unwrap().accel,
+            Some(AccelCurve::parse("off").unwrap()));
+        assert!(Config::parse("accel = fast\n").is_err());
+        assert_eq!(Config::parse("word_end = true\n").unwrap().word_end, Some(true));
+        assert_eq!(Config::parse("confirm_size = 4G\n").unwrap().confirm_size, Some(4 << 30));
+        assert_eq!(Config::parse("confirm_size = 1000\n").unwrap().confirm_size, Some(1000));
+        assert_eq!(Config::parse("confirm_size = off\n").unwrap().confirm_size, Some(u64::MAX));
+        assert!(Config::parse("confirm_size = 4X\n").is_err());
+        assert!(Config::parse("confirm_size = 99999999T\n").is_err());
+        let config = Config::parse("alert = ERROR|FATAL\nalert_bell = false\n").unwrap();
+        assert_eq!(config.alert.as_deref(), Some("ERROR|FATAL"));
+        assert_eq!(config.alert_bell, Some(false));
+        assert_eq!(Config::parse("context = 3\n").unwrap().context, Some(3));
+        assert_eq!(Config::parse("collapse_hidden = true\n").unwrap().collapse_hidden, Some(true));
+        assert_eq!(Config::parse("scrollbar = true\n").unwrap().scrollbar, Some(true));
+        assert_eq!(Config::parse("center_match = true\n").unwrap().center_match, Some(true));
+    }
+}
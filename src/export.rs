@@ -0,0 +1,270 @@
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::Path;
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::lines::*;
+use crate::pattern::*;
+use crate::cache::SearchType;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExportFormat {
+    Html,
+    Ansi,
+    Text,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Html => "html",
+            ExportFormat::Ansi => "ansi",
+            ExportFormat::Text => "txt",
+        }
+    }
+
+    // by the extension of the file written to, text unless it says otherwise
+    pub fn from_path(path: &OsStr) -> Self {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("html" | "htm") => ExportFormat::Html,
+            Some("ansi") => ExportFormat::Ansi,
+            _ => ExportFormat::Text,
+        }
+    }
+}
+
+// call f for all lines visible in the given display mode, from the start of the file.
+// Returns the number of lines
+pub fn for_each_visible<F>(lines: &Lines, patterns: &PatternSet, mode: DisplayMode, mut f: F)
+    -> io::Result<u64>
+    where F: FnMut(LineId) -> io::Result<()>
+{
+    let mut count = 0;
+    let mut next = lines.next_line(SearchType::Tag, 0, patterns, mode, true);
+    while let Some(line_id) = next {
+        f(line_id)?;
+        count += 1;
+        next = lines.next_line(SearchType::Tag, line_id, patterns, mode, false);
+    }
+
+    Ok(count)
+}
+
+// split a processed line into runs of chars with the same style
+fn style_runs(pline: &ProcessedLine) -> Vec<(Style, String)> {
+    let mut runs: Vec<(Style, String)> = Vec::new();
+    for sc in &pline.chars {
+        let style = sc.display_style();
+        match runs.last_mut() {
+            Some((s, text)) if *s == style => text.push(sc.c),
+            _ => runs.push((style, sc.c.to_string())),
+        }
+    }
+
+    runs
+}
+
+// the colors of a default xterm, as that's what the log was looked at with
+fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+    let rgb = match color {
+        Color::Reset => return None,
+        Color::Black => (0x00, 0x00, 0x00),
+        Color::Red => (0xcd, 0x00, 0x00),
+        Color::Green => (0x00, 0xcd, 0x00),
+        Color::Yellow => (0xcd, 0xcd, 0x00),
+        Color::Blue => (0x00, 0x00, 0xee),
+        Color::Magenta => (0xcd, 0x00, 0xcd),
+        Color::Cyan => (0x00, 0xcd, 0xcd),
+        Color::Gray => (0xe5, 0xe5, 0xe5),
+        Color::DarkGray => (0x7f, 0x7f, 0x7f),
+        Color::LightRed => (0xff, 0x00, 0x00),
+        Color::LightGreen => (0x00, 0xff, 0x00),
+        Color::LightYellow => (0xff, 0xff, 0x00),
+        Color::LightBlue => (0x5c, 0x5c, 0xff),
+        Color::LightMagenta => (0xff, 0x00, 0xff),
+        Color::LightCyan => (0x00, 0xff, 0xff),
+        Color::White => (0xff, 0xff, 0xff),
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(i) if i < 16 => {
+            const BASE: [Color; 16] = [Color::Black, Color::Red, Color::Green, Color::Yellow,
+                Color::Blue, Color::Magenta, Color::Cyan, Color::Gray, Color::DarkGray,
+                Color::LightRed, Color::LightGreen, Color::LightYellow, Color::LightBlue,
+                Color::LightMagenta, Color::LightCyan, Color::White];
+            return rgb(BASE[i as usize]);
+        }
+        Color::Indexed(i) if i < 232 => {
+            // 6x6x6 color cube
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let i = i - 16;
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        Color::Indexed(i) => {
+            let v = 8 + (i - 232) * 10;
+            (v, v, v)
+        }
+    };
+
+    Some(rgb)
+}
+
+fn css(style: Style) -> String {
+    let mut css = Vec::new();
+    if let Some((r, g, b)) = style.fg.and_then(rgb) {
+        css.push(format!("color:#{:02x}{:02x}{:02x}", r, g, b));
+    }
+    if let Some((r, g, b)) = style.bg.and_then(rgb) {
+        css.push(format!("background:#{:02x}{:02x}{:02x}", r, g, b));
+    }
+    if style.add_modifier.contains(Modifier::BOLD) {
+        css.push("font-weight:bold".to_string());
+    }
+    if style.add_modifier.contains(Modifier::ITALIC) {
+        css.push("font-style:italic".to_string());
+    }
+    if style.add_modifier.contains(Modifier::UNDERLINED) {
+        css.push("text-decoration:underline".to_string());
+    }
+
+    css.join(";")
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// write all visible lines with their highlighting as a standalone html document
+pub fn export_html<W: Write>(out: &mut W, title: &str, lines: &Lines, patterns: &PatternSet,
+    mode: DisplayMode) -> io::Result<u64>
+{
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", html_escape(title))?;
+    writeln!(out, "<style>body {{ background: #000000; color: #e5e5e5; }}</style>")?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    write!(out, "<pre>")?;
+    let count = for_each_visible(lines, patterns, mode, |line_id| {
+        let Some(pline) = lines.get(line_id, patterns, None) else {
+            return Ok(());
+        };
+        for (style, text) in style_runs(&pline) {
+            let css = css(style);
+            if css.is_empty() {
+                write!(out, "{}", html_escape(&text))?;
+            } else {
+                write!(out, "<span style=\"{}\">{}</span>", css, html_escape(&text))?;
+            }
+        }
+        writeln!(out)
+    })?;
+    writeln!(out, "</pre>")?;
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")?;
+
+    Ok(count)
+}
+
+fn sgr_color(color: Color, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    let code = match color {
+        Color::Reset => return None,
+        Color::Black => base,
+        Color::Red => base + 1,
+        Color::Green => base + 2,
+        Color::Yellow => base + 3,
+        Color::Blue => base + 4,
+        Color::Magenta => base + 5,
+        Color::Cyan => base + 6,
+        Color::Gray => base + 7,
+        Color::DarkGray => base + 60,
+        Color::LightRed => base + 61,
+        Color::LightGreen => base + 62,
+        Color::LightYellow => base + 63,
+        Color::LightBlue => base + 64,
+        Color::LightMagenta => base + 65,
+        Color::LightCyan => base + 66,
+        Color::White => base + 67,
+        Color::Indexed(i) => return Some(format!("{};5;{}", base + 8, i)),
+        Color::Rgb(r, g, b) => return Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+    };
+
+    Some(code.to_string())
+}
+
+// the escape sequence switching from the default to the given style
+fn sgr(style: Style) -> String {
+    let mut codes = Vec::new();
+    if style.add_modifier.contains(Modifier::BOLD) {
+        codes.push("1".to_string());
+    }
+    if style.add_modifier.contains(Modifier::ITALIC) {
+        codes.push("3".to_string());
+    }
+    if style.add_modifier.contains(Modifier::UNDERLINED) {
+        codes.push("4".to_string());
+    }
+    if style.add_modifier.contains(Modifier::REVERSED) {
+        codes.push("7".to_string());
+    }
+    codes.extend(style.fg.and_then(|c| sgr_color(c, false)));
+    codes.extend(style.bg.and_then(|c| sgr_color(c, true)));
+    if codes.is_empty() {
+        return String::new();
+    }
+
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+// write all visible lines with their highlighting as text with terminal escape sequences.
+// Every line starts and ends with the default style, so the output can be grepped
+pub fn export_ansi<W: Write>(out: &mut W, lines: &Lines, patterns: &PatternSet,
+    mode: DisplayMode) -> io::Result<u64>
+{
+    for_each_visible(lines, patterns, mode, |line_id| {
+        let Some(pline) = lines.get(line_id, patterns, None) else {
+            return Ok(());
+        };
+        let mut styled = false;
+        for (style, text) in style_runs(&pline) {
+            let sgr = sgr(style);
+            if styled {
+                write!(out, "\x1b[0m")?;
+            }
+            styled = !sgr.is_empty();
+            write!(out, "{}{}", sgr, text)?;
+        }
+        if styled {
+            write!(out, "\x1b[0m")?;
+        }
+        writeln!(out)
+    })
+}
+
+// write all visible lines as they are in the file, without highlighting. progress is called
+// with each line before it is written
+pub fn export_text<W: Write, F>(out: &mut W, lines: &Lines, patterns: &PatternSet,
+    mode: DisplayMode, mut progress: F) -> io::Result<u64>
+    where F: FnMut(LineId)
+{
+    for_each_visible(lines, patterns, mode, |line_id| {
+        progress(line_id);
+        let Some((_, bytes)) = lines.get_raw(line_id, patterns) else {
+            return Ok(());
+        };
+        out.write_all(&bytes)
+    })
+}
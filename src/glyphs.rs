@@ -0,0 +1,82 @@
+use std::env;
+
+// all glyphs used to decorate the display. Chosen once at startup, either from the
+// command line or from the locale
+#[derive(Debug, Clone)]
+pub struct Glyphs {
+    pub filler: &'static str,           // rows after the end of the file
+    pub hidden: &'static str,           // manually hidden line
+    pub hidden_match: &'static str,     // line matches a hide pattern
+    pub tagged: &'static str,           // manually tagged line
+    pub tagged_match: &'static str,     // line matches a tag pattern
+    pub fold: &'static str,             // continuation of a folded line
+    pub fold_before: &'static str,      // folded line has hidden parts above
+    pub fold_after: &'static str,       // folded line has hidden parts below
+    pub truncated: &'static str,        // line continues beyond the visible part
+    pub separator: &'static str,        // rule between the log and the status row
+    pub file_start: &'static str,       // first line of a file in a timeline of rotated logs
+    pub pinned: &'static str,           // the pinned line above the log
+    pub context: &'static str,          // line near a tagged line, in tagged mode
+    pub context_break: &'static str,    // context after a gap, like -- in grep output
+    pub collapsed: &'static str,        // a run of hidden lines is collapsed
+    pub scroll_track: &'static str,     // scrollbar beside the visible part of the file
+    pub scroll_thumb: &'static str,     // scrollbar where the visible part of the file is
+}
+
+impl Glyphs {
+    pub fn new(ascii: bool) -> Self {
+        if ascii {
+            Glyphs {
+                filler: "~ ",
+                hidden: "H ",
+                hidden_match: "- ",
+                tagged: "T ",
+                tagged_match: "* ",
+                fold: "F ",
+                fold_before: "F-",
+                fold_after: "F+",
+                truncated: ">",
+                separator: "-",
+                file_start: "= ",
+                pinned: "P ",
+                context: "| ",
+                context_break: "--",
+                collapsed: "..",
+                scroll_track: "|",
+                scroll_thumb: "#",
+            }
+        } else {
+            Glyphs {
+                filler: "~ ",
+                hidden: "H ",
+                hidden_match: "- ",
+                tagged: "T ",
+                tagged_match: "* ",
+                fold: "F ",
+                fold_before: "F-",
+                fold_after: "F+",
+                truncated: "…",
+                separator: "─",
+                file_start: "▸ ",
+                pinned: "P ",
+                context: "│ ",
+                context_break: "┄┄",
+                collapsed: "⋯ ",
+                scroll_track: "│",
+                scroll_thumb: "█",
+            }
+        }
+    }
+
+    // use ascii glyphs unless the locale indicates utf-8
+    pub fn from_locale() -> Self {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|val| !val.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        let utf8 = locale.contains("utf-8") || locale.contains("utf8");
+
+        Self::new(!utf8)
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// what a key does in the log view. The names in a key file are the kebab-case variant names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Action {
+    Help,
+    Interrupt,
+    Offsets,
+    TogglePin,
+    Quit,
+    ScrollDown,
+    ScrollDownHalf,
+    ScrollDownPage,
+    ScrollUp,
+    ScrollUpHalf,
+    ScrollUpPage,
+    FoldScrollDown,
+    FoldScrollDownHalf,
+    FoldScrollDownPage,
+    FoldScrollUp,
+    FoldScrollUpHalf,
+    FoldScrollUpPage,
+    ScrollRightHalf,
+    ScrollLeftHalf,
+    ScrollRightPage,
+    ScrollLeftPage,
+    Redo,
+    Hexdump,
+    TagList,
+    PatternList,
+    MessageLog,
+    Info,
+    ToggleFollow,
+    Reload,
+    Down,
+    Up,
+    Left,
+    Right,
+    FastDown,
+    FastUp,
+    FastLeft,
+    FastRight,
+    WordForward,
+    BigWordForward,
+    WordBackward,
+    BigWordBackward,
+    WordEnd,
+    BigWordEnd,
+    Start,
+    End,
+    StartOfLine,
+    EndOfLine,
+    NextPrefix,
+    PreviousPrefix,
+    GotoPrefix,
+    SetBookmark,
+    JumpToBookmark,
+    FoldLine,
+    ToggleWrap,
+    FoldMore,
+    FoldLess,
+    SetIndent,
+    Tag,
+    Untag,
+    TagMark,
+    DisplayForward,
+    DisplayBackward,
+    ToggleDiffAbove,
+    MarkWord,
+    MarkBigWord,
+    MarkText,
+    MarkField,
+    ToggleColumnLock,
+    MoreContext,
+    LessContext,
+    ToggleCollapse,
+    ExpandCollapsed,
+    CycleColor,
+    CycleColorBackward,
+    SearchForward,
+    SearchBackward,
+    RegexSearchForward,
+    RegexSearchBackward,
+    RegexTest,
+    LogLevel,
+    NextMatch,
+    PreviousMatch,
+    ClearSearch,
+    ExtendMarkRight,
+    ShrinkMarkRight,
+    ExtendMarkLeft,
+    ShrinkMarkLeft,
+    ExtendMarkWordRight,
+    ShrinkMarkWordRight,
+    ExtendMarkWordLeft,
+    ShrinkMarkWordLeft,
+    Hide,
+    Unhide,
+    HideOthers,
+    Undo,
+    Yank,
+    ShowAnchor,
+    ExportHtml,
+    ExportAnsi,
+    ExportText,
+    Command,
+    HighlightWord,
+}
+
+const DEFAULT_KEYS: &[(&str, Action)] = &[
+    ("C-h", Action::Help),
+    ("C-c", Action::Interrupt),
+    ("@", Action::Offsets),
+    ("P", Action::TogglePin),
+    ("q", Action::Quit),
+    ("C-e", Action::ScrollDown),
+    ("C-d", Action::ScrollDownHalf),
+    ("C-f", Action::ScrollDownPage),
+    ("C-y", Action::ScrollUp),
+    ("C-u", Action::ScrollUpHalf),
+    ("C-b", Action::ScrollUpPage),
+    ("M-e", Action::FoldScrollDown),
+    ("M-d", Action::FoldScrollDownHalf),
+    ("M-f", Action::FoldScrollDownPage),
+    ("M-y", Action::FoldScrollUp),
+    ("M-u", Action::FoldScrollUpHalf),
+    ("M-b", Action::FoldScrollUpPage),
+    ("M-l", Action::ScrollRightHalf),
+    ("M-h", Action::ScrollLeftHalf),
+    ("M-L", Action::ScrollRightPage),
+    ("M-H", Action::ScrollLeftPage),
+    ("C-r", Action::Redo),
+    ("C-x", Action::Hexdump),
+    ("C-t", Action::TagList),
+    ("C-p", Action::PatternList),
+    ("C-g", Action::MessageLog),
+    ("I", Action::Info),
+    ("C-w", Action::ToggleFollow),
+    ("R", Action::Reload),
+    ("j", Action::Down),
+    ("Down", Action::Down),
+    ("k", Action::Up),
+    ("Up", Action::Up),
+    ("h", Action::Left),
+    ("Left", Action::Left),
+    ("l", Action::Right),
+    ("Right", Action::Right),
+    ("J", Action::FastDown),
+    ("K", Action::FastUp),
+    ("H", Action::FastLeft),
+    ("L", Action::FastRight),
+    ("w", Action::WordForward),
+    ("W", Action::BigWordForward),
+    ("b", Action::WordBackward),
+    ("B", Action::BigWordBackward),
+    ("e", Action::WordEnd),
+    ("E", Action::BigWordEnd),
+    ("g", Action::GotoPrefix),
+    ("Home", Action::Start),
+    ("G", Action::End),
+    ("End", Action::End),
+    ("0", Action::StartOfLine),
+    ("$", Action::EndOfLine),
+    ("]", Action::NextPrefix),
+    ("[", Action::PreviousPrefix),
+    ("`", Action::SetBookmark),
+    ("'", Action::JumpToBookmark),
+    ("F", Action::FoldLine),
+    ("v", Action::ToggleWrap),
+    ("+", Action::FoldMore),
+    ("-", Action::FoldLess),
+    ("i", Action::SetIndent),
+    ("t", Action::Tag),
+    ("T", Action::Untag),
+    ("p", Action::TagMark),
+    ("f", Action::DisplayForward),
+    ("d", Action::DisplayBackward),
+    ("D", Action::ToggleDiffAbove),
+    ("m", Action::MarkWord),
+    ("M", Action::MarkBigWord),
+    ("*", Action::MarkText),
+    ("_", Action::MarkField),
+    ("|", Action::ToggleColumnLock),
+    ("}", Action::MoreContext),
+    ("{", Action::LessContext),
+    ("Z", Action::ToggleCollapse),
+    ("z", Action::ExpandCollapsed),
+    ("c", Action::CycleColor),
+    ("C", Action::CycleColorBackward),
+    ("/", Action::SearchForward),
+    ("?", Action::SearchBackward),
+    ("&", Action::RegexSearchForward),
+    ("#", Action::RegexSearchBackward),
+    ("%", Action::RegexTest),
+    ("!", Action::LogLevel),
+    ("n", Action::NextMatch),
+    ("N", Action::PreviousMatch),
+    ("\\", Action::ClearSearch),
+    (".", Action::ExtendMarkRight),
+    (",", Action::ShrinkMarkRight),
+    ("<", Action::ExtendMarkLeft),
+    (">", Action::ShrinkMarkLeft),
+    ("M-.", Action::ExtendMarkWordRight),
+    ("M-,", Action::ShrinkMarkWordRight),
+    ("M-<", Action::ExtendMarkWordLeft),
+    ("M->", Action::ShrinkMarkWordLeft),
+    ("x", Action::Hide),
+    ("X", Action::Unhide),
+    ("~", Action::HideOthers),
+    ("u", Action::Undo),
+    ("y", Action::Yank),
+    ("=", Action::ShowAnchor),
+    ("S", Action::ExportHtml),
+    ("A", Action::ExportAnsi),
+    ("Y", Action::ExportText),
+    (":", Action::Command),
+    ("O", Action::HighlightWord),
+];
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Space", KeyCode::Char(' ')),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+];
+
+type Key = (KeyCode, KeyModifiers);
+
+// a char, or one of the named keys, with C- for control or M- for alt in front
+fn parse_key(s: &str) -> Result<Key> {
+    let (modifiers, name) = match (s.strip_prefix("C-"), s.strip_prefix("M-")) {
+        (Some(name), _) if !name.is_empty() => (KeyModifiers::CONTROL, name),
+        (_, Some(name)) if !name.is_empty() => (KeyModifiers::ALT, name),
+        _ => (KeyModifiers::NONE, s),
+    };
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => NAMED_KEYS.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, code)| code)
+            .ok_or_else(|| anyhow!("unknown key {}", s))?,
+    };
+
+    Ok((code, modifiers))
+}
+
+// only control and alt tell keys apart, shift is in the char already
+fn key_of(event: &KeyEvent) -> Key {
+    let modifiers = if event.modifiers.contains(KeyModifiers::CONTROL) {
+        KeyModifiers::CONTROL
+    } else if event.modifiers.contains(KeyModifiers::ALT) {
+        KeyModifiers::ALT
+    } else {
+        KeyModifiers::NONE
+    };
+
+    (event.code, modifiers)
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    keys: HashMap<Key, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let keys = DEFAULT_KEYS.iter()
+            .map(|&(key, action)| (parse_key(key).unwrap(), action))
+            .collect();
+
+        Keymap { keys }
+    }
+}
+
+impl Keymap {
+    // the default keys, changed by a key file
+    pub fn from_config(path: &OsStr) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("{}", path.to_string_lossy()))?;
+        let mut keymap = Self::default();
+        keymap.parse(&content).with_context(|| format!("{}", path.to_string_lossy()))?;
+
+        Ok(keymap)
+    }
+
+    // one `key = action` per line, `none` unbinds the key. `#` at the start of a line is a
+    // comment, unless it binds the # key
+    fn parse(&mut self, content: &str) -> Result<()> {
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            let comment = line.starts_with('#') && !line[1..].trim_start().starts_with('=');
+            if line.is_empty() || comment {
+                continue;
+            }
+            // the action has no =, the key might be one
+            let Some((key, action)) = line.rsplit_once('=') else {
+                return Err(anyhow!("line {}: expected key = action", i + 1));
+            };
+            let key = parse_key(key.trim()).with_context(|| format!("line {}", i + 1))?;
+            match action.trim() {
+                "none" => {
+                    self.keys.remove(&key);
+                }
+                action => {
+                    let action = Action::from_str(action, true)
+                        .map_err(|_| anyhow!("line {}: unknown action {}", i + 1, action))?;
+                    self.keys.insert(key, action);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, event: &KeyEvent) -> Option<Action> {
+        self.keys.get(&key_of(event)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keymap() {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        let mut keymap = Keymap::default();
+        assert_eq!(keymap.get(&key(KeyCode::Char('J'), KeyModifiers::SHIFT)),
+            Some(Action::FastDown));
+        assert_eq!(keymap.get(&key(KeyCode::Char('d'), KeyModifiers::CONTROL)),
+            Some(Action::ScrollDownHalf));
+
+        keymap.parse("# colemak\nn = down\ne = up\nj = none\n# = search-forward\n= = quit\n\
+            C-Space = fold-scroll-down\n").unwrap();
+        assert_eq!(keymap.get(&key(KeyCode::Char('n'), KeyModifiers::NONE)), Some(Action::Down));
+        assert_eq!(keymap.get(&key(KeyCode::Char('j'), KeyModifiers::NONE)), None);
+        assert_eq!(keymap.get(&key(KeyCode::Char('#'), KeyModifiers::NONE)),
+            Some(Action::SearchForward));
+        assert_eq!(keymap.get(&key(KeyCode::Char('='), KeyModifiers::NONE)), Some(Action::Quit));
+        assert_eq!(keymap.get(&key(KeyCode::Char(' '), KeyModifiers::CONTROL)),
+            Some(Action::FoldScrollDown));
+
+        assert!(keymap.parse("j down\n").is_err());
+        assert!(keymap.parse("jj = down\n").is_err());
+        assert!(keymap.parse("j = fly\n").is_err());
+    }
+}
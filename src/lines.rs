@@ -1,8 +1,7 @@
 use std::collections::BTreeSet;
 use anyhow::Result;
 use std::num::NonZeroUsize;
-use std::ffi::OsStr;
-use bitvec::prelude::*;
+use std::ffi::{OsStr, OsString};
 use clog::prelude::*;
 use std::sync::Arc;
 
@@ -13,6 +12,9 @@ use crate::search::*;
 
 pub type LineId = u64;
 
+// splits kept in memory by default
+pub const CACHE_SPLITS: usize = 50;
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum DisplayMode {
     All,
@@ -27,26 +29,35 @@ pub struct ProcessedLine {
     pub chars: Vec<StyledChar>,
     pub matches: Vec<PatternId>,
     pub cropped: bool,
+    pub broad: bool,    // a pattern matched too often, not all matches are tracked
+    pub hidden: u64,    // stands for a collapsed run of so many hidden lines, 0 for a real line
 }
 
 #[derive(Debug)]
 pub struct Lines {
     tagged_lines: BTreeSet<LineId>,
     hidden_lines: BTreeSet<LineId>,
-    all_hidden_splits: BitVec<usize, Lsb0>,
+    context: usize,     // lines shown around tagged lines in tagged mode, like grep -C
     split_cache: SplitCache,
-    _hidden_seq: usize,
 }
 
 impl Lines {
+    // the default split size and cache, the program itself takes them from the command line
+    #[cfg(test)]
     pub fn new(filename: &OsStr) -> Result<Self> {
-        let split_cache = SplitCache::new(filename, NonZeroUsize::new(50).unwrap())?;
+        Self::with_splits(filename, SPLIT_CHUNK_SIZE, NonZeroUsize::new(CACHE_SPLITS).unwrap())
+    }
+
+    // split the file in chunks of about chunk_size bytes and keep cache_splits of them
+    pub fn with_splits(filename: &OsStr, chunk_size: LineId, cache_splits: NonZeroUsize)
+        -> Result<Self>
+    {
+        let split_cache = SplitCache::new(filename, cache_splits, chunk_size)?;
         Ok(Self {
             tagged_lines: BTreeSet::new(),
             hidden_lines: BTreeSet::new(),
-            all_hidden_splits: bitvec![0; split_cache.num_splits()],
+            context: 0,
             split_cache,
-            _hidden_seq: 0,
         })
     }
 
@@ -58,6 +69,20 @@ impl Lines {
         }
     }
 
+    pub fn tagged_lines(&self) -> &BTreeSet<LineId> {
+        &self.tagged_lines
+    }
+
+    pub fn hidden_lines(&self) -> &BTreeSet<LineId> {
+        &self.hidden_lines
+    }
+
+    // manual tags and hides from a previous session, or an undo
+    pub fn restore(&mut self, tagged_lines: BTreeSet<LineId>, hidden_lines: BTreeSet<LineId>) {
+        self.tagged_lines = tagged_lines;
+        self.hidden_lines = hidden_lines;
+    }
+
     pub fn is_tagged(&self, line_id: LineId) -> bool {
         self.tagged_lines.contains(&line_id)
     }
@@ -66,6 +91,14 @@ impl Lines {
         self.hidden_lines.contains(&line_id)
     }
 
+    pub fn context(&self) -> usize {
+        self.context
+    }
+
+    pub fn set_context(&mut self, context: usize) {
+        self.context = context;
+    }
+
     pub fn toggle_hide(&mut self, line_id: LineId) {
         if self.hidden_lines.contains(&line_id) {
             self.hidden_lines.remove(&line_id);
@@ -91,29 +124,71 @@ impl Lines {
         Some((split_id, split_start, split, line_ix))
     }
 
+    // byte range of a line relative to the split start, including the newline
+    fn line_range(split: &Split, line_ix: usize) -> (usize, usize) {
+        if line_ix == 0 {
+            (0, split.line_ends[0])
+        } else {
+            (split.line_ends[line_ix - 1], split.line_ends[line_ix])
+        }
+    }
+
+    // the undecoded bytes of a line, including the newline, and the id of the line
+    pub fn get_raw(&self, line_id: LineId, patterns: &PatternSet) -> Option<(LineId, Vec<u8>)> {
+        let (_, split_start, split, line_ix) = self.resolve_line_id(line_id, patterns)?;
+        let (rel_start, rel_end) = Self::line_range(&split, line_ix);
+
+        Some((split_start + rel_start as LineId, split.buf[rel_start..rel_end].to_vec()))
+    }
+
     pub fn get(&self, line_id: LineId, patterns: &PatternSet, crop_chars: Option<usize>)
         -> Option<ProcessedLine>
     {
         let (_, split_start, split, line_ix) = self.resolve_line_id(line_id, patterns)?;
-
-        let (rel_start, rel_end) = if line_ix == 0 {
-            (0, split.line_ends[0])
-        } else {
-            (split.line_ends[line_ix - 1], split.line_ends[line_ix])
-        };
+        let (rel_start, rel_end) = Self::line_range(&split, line_ix);
 
         // XXX handle/convert non-utf8 lines
         let line = String::from_utf8(split.buf[rel_start..rel_end].to_vec()).unwrap();
-        let (pline, matches, cropped) = patterns.process_line(&line, crop_chars);
+        let (pline, matches, cropped, broad) = patterns.process_line(&line, crop_chars);
 
         return Some(ProcessedLine {
             line_id: split_start + rel_start as LineId,
             chars: pline,
             cropped,
+            broad,
             matches,
+            hidden: 0,
         });
     }
 
+    // shown in tagged mode only because a tagged line is near it
+    pub fn is_context_line(&self, line_id: LineId, mode: DisplayMode, patterns: &PatternSet)
+        -> bool
+    {
+        if mode != DisplayMode::Tagged || self.context == 0 {
+            return false;
+        }
+        let Some((_, split_start, split, line_ix)) = self.resolve_line_id(line_id, patterns) else {
+            return false;
+        };
+
+        !split.search_lines.contains(&line_ix) && !self.is_tagged_ix(line_ix, &split, split_start)
+    }
+
+    // the number of hidden lines from line_id on, and whether the run ends there. Only counted
+    // in the split of line_id, a run going on into the next split is longer
+    pub fn hidden_run(&self, line_id: LineId, patterns: &PatternSet) -> Option<(u64, bool)> {
+        let (split_id, split_start, split, line_ix) = self.resolve_line_id(line_id, patterns)?;
+        let len = split.line_ends.len();
+        let shown = (line_ix..len).find(|&ix|
+            !self.is_filtered(SearchType::Tag, ix, &split, split_start, DisplayMode::Normal));
+
+        Some(match shown {
+            Some(ix) => ((ix - line_ix) as u64, true),
+            None => ((len - line_ix) as u64, split_id + 1 == self.split_cache.num_splits()),
+        })
+    }
+
     #[allow(dead_code)]
     pub fn is_filtered_line(&self, line_id: LineId, mode: DisplayMode, patterns: &PatternSet)
         -> Option<bool>
@@ -136,23 +211,36 @@ impl Lines {
         if st == SearchType::Search {
             return true;
         }
-        let line_id = if line_ix == 0 {
-            0
-        } else {
-            split.line_ends[line_ix - 1]
-        } as LineId + split_start;
+        let line_id = split_start + Self::line_range(split, line_ix).0 as LineId;
         lD5!(LI, "line_id {}", line_id);
         match mode {
             DisplayMode::Normal =>
                 split.hidden_lines.contains(&line_ix) || self.hidden_lines.contains(&line_id),
-            DisplayMode::Tagged =>
-                !(split.tagged_lines.contains(&line_ix) || self.tagged_lines.contains(&line_id)),
+            // context only reaches as far as the split
+            DisplayMode::Tagged => {
+                let first = line_ix.saturating_sub(self.context);
+                let last = (line_ix + self.context).min(split.line_ends.len() - 1);
+                !(first..=last).any(|ix| self.is_tagged_ix(ix, split, split_start))
+            }
             DisplayMode::Manual =>
                 !self.tagged_lines.contains(&line_id),
             DisplayMode::All => false,
         }
     }
 
+    // tagged by a pattern or manually
+    pub fn is_tagged_line(&self, line_id: LineId, patterns: &PatternSet) -> bool {
+        self.resolve_line_id(line_id, patterns)
+            .is_some_and(|(_, split_start, split, line_ix)|
+                self.is_tagged_ix(line_ix, &split, split_start))
+    }
+
+    // tagged by a pattern or manually
+    fn is_tagged_ix(&self, line_ix: usize, split: &Split, split_start: LineId) -> bool {
+        let line_id = split_start + Self::line_range(split, line_ix).0 as LineId;
+        split.tagged_lines.contains(&line_ix) || self.tagged_lines.contains(&line_id)
+    }
+
     fn skip_split(&self, st: SearchType, split_id: SplitId, split_start: LineId, split_end: LineId,
         mode: DisplayMode) -> bool
     {
@@ -166,7 +254,8 @@ impl Lines {
         }
         match mode {
             DisplayMode::Normal => {
-                if self.all_hidden_splits[split_id] {
+                // all lines hidden by patterns, found by the background scan
+                if self.split_cache.has_matches(SearchType::Hide, split_id) {
                     return true;
                 }
             }
@@ -200,12 +289,6 @@ impl Lines {
     {
         lD3!(LI, "next line for {} mode {:?}", line_id, mode);
 
-        /*
-        if patterns.hidden_seq != self.hidden_seq {
-            self.all_hidden_splits = bitvec![0; self.split_cache.file_search.num_splits()];
-        }
-        */
-
         let (mut split_id, _, split, mut line_ix) = self.resolve_line_id(line_id, patterns)?;
         let num_splits = self.split_cache.num_splits();
         lD5!(LI, "current_line is split_id {} ({}) line_ix {}", split_id, num_splits, line_ix);
@@ -258,6 +341,57 @@ impl Lines {
         None
     }
 
+    // the next line matching the search, but only as far as the background search got
+    // without a gap. Doesn't wait for the splits still to be searched
+    pub fn next_searched_line(&self, line_id: LineId, patterns: &PatternSet, inclusive: bool)
+        -> Option<LineId>
+    {
+        let (mut split_id, mut split_start, mut split, line_ix) =
+            self.resolve_line_id(line_id, patterns)?;
+        let mut first = if inclusive { line_ix } else { line_ix + 1 };
+        loop {
+            if let Some(&ix) = split.search_lines.iter().find(|&&ix| ix >= first) {
+                return Some(split_start + Self::line_range(&split, ix).0 as LineId);
+            }
+            loop {
+                split_id += 1;
+                if split_id >= self.split_cache.num_splits() {
+                    return None;
+                }
+                if self.split_cache.searched(SearchType::Search, split_id)? {
+                    break;
+                }
+            }
+            split_start = self.split_cache.get_split(split_id)?.0;
+            split = self.split_cache.get(split_id, patterns).ok()?;
+            first = 0;
+        }
+    }
+
+    // the same backwards
+    pub fn prev_searched_line(&self, line_id: LineId, patterns: &PatternSet, inclusive: bool)
+        -> Option<LineId>
+    {
+        let (mut split_id, mut split_start, mut split, line_ix) =
+            self.resolve_line_id(line_id, patterns)?;
+        // one past the last line to look at
+        let mut end = if inclusive { line_ix + 1 } else { line_ix };
+        loop {
+            if let Some(&ix) = split.search_lines.iter().rev().find(|&&ix| ix < end) {
+                return Some(split_start + Self::line_range(&split, ix).0 as LineId);
+            }
+            loop {
+                split_id = split_id.checked_sub(1)?;
+                if self.split_cache.searched(SearchType::Search, split_id)? {
+                    break;
+                }
+            }
+            split_start = self.split_cache.get_split(split_id)?.0;
+            split = self.split_cache.get(split_id, patterns).ok()?;
+            end = usize::MAX;
+        }
+    }
+
     // line_id points somewhere into the current line. Returns the id of the previous unfiltered line
     // if inclusive is true, the current line is included in the search
     pub fn prev_line(&self, st: SearchType, line_id: LineId, patterns: &PatternSet,
@@ -265,12 +399,6 @@ impl Lines {
     {
         lD3!(LI, "prev line for {} mode {:?}", line_id, mode);
 
-        /*
-        if patterns.hidden_seq != self.hidden_seq {
-            self.all_hidden_splits = bitvec![0; self.split_cache.file_search.num_splits()];
-        }
-        */
-
         let (mut split_id, _, _, mut line_ix) = self.resolve_line_id(line_id, patterns)?;
         let num_splits = self.split_cache.num_splits();
         lD5!(LI, "current_line is split_id {} ({}) line_ix {}", split_id, num_splits, line_ix);
@@ -295,13 +423,14 @@ impl Lines {
                     return None;
                 }
                 split_id -= 1;
+                line_ix = usize::MAX;
                 continue;
             }
             let split = self.split_cache.get(split_id, patterns).ok()?;
             lD8!(LI, "split tagged lines {:?} hidden lines {:?}",
                 split.tagged_lines, split.hidden_lines);
             if line_ix == usize::MAX {
-                line_ix = split.line_ends.len() - 2;
+                line_ix = split.line_ends.len() - 1;
             }
 
             loop {
@@ -332,10 +461,73 @@ impl Lines {
         }
     }
 
+    // 1-based number of the line, None until the lines before it are counted
+    pub fn line_number(&self, line_id: LineId, patterns: &PatternSet) -> Option<u64> {
+        let (split_id, _, _, line_ix) = self.resolve_line_id(line_id, patterns)?;
+
+        Some(self.split_cache.counted_lines_before(split_id)? + line_ix as u64 + 1)
+    }
+
+    // id of the line with the given 1-based number. Might block until the lines are counted
+    pub fn line_id_of_number(&self, number: u64, patterns: &PatternSet) -> Option<LineId> {
+        if number == 0 {
+            return None;
+        }
+        // the first split that ends after the line
+        let (mut lo, mut hi) = (0, self.split_cache.num_splits());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.split_cache.lines_before(mid + 1) < number {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let (split_start, _) = self.split_cache.get_split(lo)?;
+        let line_ix = (number - 1 - self.split_cache.lines_before(lo)) as usize;
+        if line_ix == 0 {
+            return Some(split_start);
+        }
+        let split = self.split_cache.get(lo, patterns).ok()?;
+
+        Some(split_start + *split.line_ends.get(line_ix - 1)? as LineId)
+    }
+
+    // lines counted so far, and whether that are all. Doesn't block
+    pub fn line_count(&self) -> (u64, bool) {
+        self.split_cache.line_count()
+    }
+
     pub fn update_patterns(&self, st: SearchType, patterns: &PatternSet) {
         self.split_cache.set_re(st, patterns);
     }
 
+    // lines matching the search so far, and if all of the file has been searched
+    pub fn match_count(&self) -> Option<(u64, bool)> {
+        self.split_cache.match_count(SearchType::Search)
+    }
+
+    pub fn take_read_error(&self) -> Option<String> {
+        self.split_cache.take_read_error()
+    }
+
+    pub fn num_splits(&self) -> usize {
+        self.split_cache.num_splits()
+    }
+
+    pub fn find_split(&self, line_id: LineId) -> Option<SplitId> {
+        self.split_cache.find_split(line_id)
+    }
+
+    // byte range of the split
+    pub fn get_split(&self, split_id: SplitId) -> Option<(LineId, LineId)> {
+        self.split_cache.get_split(split_id)
+    }
+
+    pub fn split_cached(&self, split_id: SplitId, patterns: &PatternSet) -> Option<bool> {
+        self.split_cache.cached(split_id, patterns)
+    }
+
     pub fn last_line_id(&self) -> LineId {
         let num_splits = self.split_cache.num_splits();
         let (_, split_end) = self.split_cache.get_split(num_splits - 1).unwrap();
@@ -343,12 +535,281 @@ impl Lines {
         split_end - 1
     }
 
+    // pick up lines appended to the file. Returns true if there are new lines
+    pub fn grow(&self) -> Result<bool> {
+        Ok(self.split_cache.grow()?.is_some())
+    }
+
+    // read the file from scratch. Manual tags and hides are dropped, their lines are gone
+    pub fn reset(&mut self) -> Result<()> {
+        self.tagged_lines.clear();
+        self.hidden_lines.clear();
+        self.split_cache.reset()
+    }
+
+    // the file that is read, the spool file for stdin
+    pub fn filename(&self) -> OsString {
+        self.split_cache.filename()
+    }
+
     pub fn set_current_line(&self, line_id: LineId) {
         let split_id = self.split_cache.find_split(line_id).unwrap();
         self.split_cache.set_current_split(split_id);
     }
 
+    // use a memory map of the file instead of reading it
+    pub fn is_mapped(&self) -> bool {
+        self.split_cache.is_mapped()
+    }
+
+    pub fn map_file(&self) -> Result<()> {
+        self.split_cache.map_file()
+    }
+
     pub fn get_file_search(&self) -> FileSearch {
         self.split_cache.get_file_search()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempPath;
+    use crate::{MarkStyle, MarkType};
+
+    // start of each line in the content
+    fn line_ids(content: &str) -> Vec<LineId> {
+        let mut ids = vec![0];
+        ids.extend(content.match_indices('\n').map(|(i, _)| i as LineId + 1));
+        ids.pop();
+        ids
+    }
+
+    // all lines shown in the mode, walking forward from the first and back from the last
+    fn walk(lines: &Lines, st: SearchType, patterns: &PatternSet, mode: DisplayMode)
+        -> (Vec<LineId>, Vec<LineId>)
+    {
+        let mut forward = Vec::new();
+        let mut line_id = lines.next_line(st, 0, patterns, mode, true);
+        while let Some(id) = line_id {
+            forward.push(id);
+            line_id = lines.next_line(st, id, patterns, mode, false);
+        }
+        let mut backward = Vec::new();
+        let mut line_id = lines.prev_line(st, lines.last_line_id(), patterns, mode, true);
+        while let Some(id) = line_id {
+            backward.push(id);
+            line_id = lines.prev_line(st, id, patterns, mode, false);
+        }
+        backward.reverse();
+
+        (forward, backward)
+    }
+
+    fn add_patterns(patterns: &mut PatternSet, mark_style: &MarkStyle,
+        list: &[(&str, PatternMode)])
+    {
+        for &(pattern, mode) in list {
+            let mark_type = match mode {
+                PatternMode::Tagging => MarkType::Tag,
+                PatternMode::Hiding => MarkType::Hide,
+                PatternMode::Search => MarkType::Search,
+                _ => MarkType::Mark,
+            };
+            patterns.add(pattern, MatchType::SmallWord, Anchor::default(), false,
+                mark_style.get(mark_type), mode).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_display_modes() {
+        let path = TempPath::new("modes");
+        let content = "start\ntag me\nhide me\nplain\nmanual\nhide and tag\ngone\nfound\nend\n";
+        std::fs::write(&path, content).unwrap();
+        let ids = line_ids(content);
+        let mark_style = MarkStyle::new();
+        let mut patterns = PatternSet::new(mark_style.clone());
+        add_patterns(&mut patterns, &mark_style, &[("tag", PatternMode::Tagging),
+            ("hide", PatternMode::Hiding), ("found", PatternMode::Search)]);
+        let mut lines = Lines::new(path.as_os_str()).unwrap();
+        for st in SearchType::ALL {
+            lines.update_patterns(st, &patterns);
+        }
+        lines.toggle_tag(ids[4]);
+        lines.toggle_hide(ids[6]);
+
+        // search results are shown in every mode
+        let expect = |ixs: &[usize]| ixs.iter().map(|&i| ids[i]).collect::<Vec<_>>();
+        for (mode, shown) in [
+            (DisplayMode::All, expect(&[0, 1, 2, 3, 4, 5, 6, 7, 8])),
+            (DisplayMode::Normal, expect(&[0, 1, 3, 4, 7, 8])),
+            (DisplayMode::Tagged, expect(&[1, 4, 5, 7])),
+            (DisplayMode::Manual, expect(&[4, 7])),
+        ] {
+            assert_eq!(walk(&lines, SearchType::Tag, &patterns, mode), (shown.clone(), shown),
+                "{:?}", mode);
+            assert_eq!(walk(&lines, SearchType::Search, &patterns, mode), (expect(&[7]),
+                expect(&[7])), "{:?}", mode);
+        }
+
+        // from the middle of a line, and whether it counts itself
+        let mode = DisplayMode::Tagged;
+        assert_eq!(lines.next_line(SearchType::Tag, ids[1] + 2, &patterns, mode, true),
+            Some(ids[1]));
+        assert_eq!(lines.next_line(SearchType::Tag, ids[1] + 2, &patterns, mode, false),
+            Some(ids[4]));
+        assert_eq!(lines.prev_line(SearchType::Tag, ids[3] + 2, &patterns, mode, true),
+            Some(ids[1]));
+        assert_eq!(lines.prev_line(SearchType::Tag, ids[4], &patterns, mode, false),
+            Some(ids[1]));
+
+        // the ends of the file
+        let mode = DisplayMode::All;
+        assert_eq!(lines.next_line(SearchType::Tag, ids[8], &patterns, mode, false), None);
+        assert_eq!(lines.next_line(SearchType::Tag, lines.last_line_id(), &patterns, mode,
+            true), Some(ids[8]));
+        assert_eq!(lines.prev_line(SearchType::Tag, 0, &patterns, mode, false), None);
+        assert_eq!(lines.prev_line(SearchType::Tag, 0, &patterns, mode, true), Some(0));
+        assert_eq!(lines.next_line(SearchType::Tag, ids[8], &patterns, DisplayMode::Manual,
+            false), None);
+        assert_eq!(lines.prev_line(SearchType::Tag, ids[4], &patterns, DisplayMode::Manual,
+            false), None);
+
+        // nothing left to show
+        lines.toggle_tag(ids[4]);
+        patterns = PatternSet::new(mark_style.clone());
+        for st in SearchType::ALL {
+            lines.update_patterns(st, &patterns);
+        }
+        assert_eq!(walk(&lines, SearchType::Tag, &patterns, DisplayMode::Manual),
+            (Vec::new(), Vec::new()));
+        assert_eq!(walk(&lines, SearchType::Tag, &patterns, DisplayMode::Tagged),
+            (Vec::new(), Vec::new()));
+    }
+
+    #[test]
+    fn test_context() {
+        let path = TempPath::new("context");
+        let content = "a\nb\ntag\nc\nd\ne\nf\ng\nh\ntag\n";
+        std::fs::write(&path, content).unwrap();
+        let ids = line_ids(content);
+        let mark_style = MarkStyle::new();
+        let mut patterns = PatternSet::new(mark_style.clone());
+        add_patterns(&mut patterns, &mark_style, &[("tag", PatternMode::Tagging)]);
+        let mut lines = Lines::new(path.as_os_str()).unwrap();
+        for st in SearchType::ALL {
+            lines.update_patterns(st, &patterns);
+        }
+        let expect = |ixs: &[usize]| ixs.iter().map(|&i| ids[i]).collect::<Vec<_>>();
+        let mode = DisplayMode::Tagged;
+
+        lines.set_context(1);
+        let shown = expect(&[1, 2, 3, 8, 9]);
+        assert_eq!(walk(&lines, SearchType::Tag, &patterns, mode), (shown.clone(), shown));
+        assert!(lines.is_context_line(ids[1], mode, &patterns));
+        assert!(!lines.is_context_line(ids[2], mode, &patterns));
+        assert!(!lines.is_context_line(ids[1], DisplayMode::All, &patterns));
+
+        // a manual tag brings its own context, overlapping blocks merge
+        lines.set_context(2);
+        lines.toggle_tag(ids[5]);
+        let shown = expect(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(walk(&lines, SearchType::Tag, &patterns, mode), (shown.clone(), shown));
+        assert!(!lines.is_context_line(ids[5], mode, &patterns));
+
+        // manual mode has no context
+        assert_eq!(walk(&lines, SearchType::Tag, &patterns, DisplayMode::Manual),
+            (expect(&[5]), expect(&[5])));
+    }
+
+    #[test]
+    fn test_split_boundaries() {
+        let path = TempPath::new("boundaries");
+        // three splits, only the first and the last line match
+        let content = (0..90000)
+            .map(|i| format!("{} {:021}\n", if i == 0 || i == 89999 { "mark" } else { "line" }, i))
+            .collect::<String>();
+        std::fs::write(&path, &content).unwrap();
+        let mark_style = MarkStyle::new();
+        let mut patterns = PatternSet::new(mark_style.clone());
+        add_patterns(&mut patterns, &mark_style, &[("mark", PatternMode::Tagging)]);
+        let mut lines = Lines::new(path.as_os_str()).unwrap();
+        for st in SearchType::ALL {
+            lines.update_patterns(st, &patterns);
+        }
+        assert_eq!(lines.num_splits(), 3);
+        let (start1, _) = lines.get_split(1).unwrap();
+        let (start2, _) = lines.get_split(2).unwrap();
+        let last = 89999 * 27;
+        assert_eq!(lines.last_line_id(), last + 26);
+
+        // the lines around the boundaries
+        let st = SearchType::Tag;
+        let mode = DisplayMode::All;
+        assert_eq!(lines.next_line(st, start1 - 27, &patterns, mode, false), Some(start1));
+        assert_eq!(lines.next_line(st, start1 - 1, &patterns, mode, false), Some(start1));
+        assert_eq!(lines.prev_line(st, start1, &patterns, mode, false), Some(start1 - 27));
+        assert_eq!(lines.prev_line(st, start2, &patterns, mode, false), Some(start2 - 27));
+        assert_eq!(lines.next_line(st, last, &patterns, mode, false), None);
+        assert_eq!(lines.prev_line(st, last + 26, &patterns, mode, true), Some(last));
+
+        // the split in the middle has no tagged lines and is skipped as a whole
+        let mode = DisplayMode::Tagged;
+        assert_eq!(walk(&lines, st, &patterns, mode), (vec![0, last], vec![0, last]));
+
+        // manual tags on both sides of the boundary
+        lines.toggle_tag(start1 - 27);
+        lines.toggle_tag(start1);
+        let shown = vec![0, start1 - 27, start1, last];
+        assert_eq!(walk(&lines, st, &patterns, mode), (shown.clone(), shown));
+        let shown = vec![start1 - 27, start1];
+        assert_eq!(walk(&lines, st, &patterns, DisplayMode::Manual), (shown.clone(), shown));
+        assert_eq!(lines.prev_line(st, start2, &patterns, DisplayMode::Manual, true),
+            Some(start1));
+        assert_eq!(lines.next_line(st, start1, &patterns, DisplayMode::Manual, false), None);
+        // coming from a skipped split starts at the last line of the previous one
+        lines.toggle_tag(start2 - 27);
+        assert_eq!(lines.prev_line(st, last, &patterns, DisplayMode::Manual, true),
+            Some(start2 - 27));
+
+        // hidden lines at the end of a split
+        let mode = DisplayMode::Normal;
+        lines.toggle_hide(start1 - 27);
+        lines.toggle_hide(start1);
+        assert_eq!(lines.next_line(st, start1 - 54, &patterns, mode, false),
+            Some(start1 + 27));
+        assert_eq!(lines.prev_line(st, start1 + 27, &patterns, mode, false),
+            Some(start1 - 54));
+        assert_eq!(lines.next_line(st, start1 - 27, &patterns, mode, true),
+            Some(start1 + 27));
+    }
+
+    #[test]
+    fn test_searched_lines() {
+        let path = TempPath::new("searched");
+        // three splits, matches in the first and the last
+        let content = (0..90000)
+            .map(|i| format!("{} {:021}\n", if i == 1 || i == 89999 { "find" } else { "line" }, i))
+            .collect::<String>();
+        std::fs::write(&path, &content).unwrap();
+        let mark_style = MarkStyle::new();
+        let mut patterns = PatternSet::new(mark_style.clone());
+        add_patterns(&mut patterns, &mark_style, &[("find", PatternMode::Search)]);
+        let lines = Lines::new(path.as_os_str()).unwrap();
+        for st in SearchType::ALL {
+            lines.update_patterns(st, &patterns);
+        }
+        let last = 89999 * 27;
+        let st = SearchType::Search;
+        let mode = DisplayMode::Normal;
+        // the blocking search waits until all splits are searched
+        assert_eq!(lines.next_line(st, 27, &patterns, mode, false), Some(last));
+        assert_eq!(lines.next_searched_line(0, &patterns, false), Some(27));
+        assert_eq!(lines.next_searched_line(27, &patterns, true), Some(27));
+        assert_eq!(lines.next_searched_line(27, &patterns, false), Some(last));
+        assert_eq!(lines.next_searched_line(last, &patterns, false), None);
+        assert_eq!(lines.prev_searched_line(last, &patterns, false), Some(27));
+        assert_eq!(lines.prev_searched_line(last, &patterns, true), Some(last));
+        assert_eq!(lines.prev_searched_line(27, &patterns, false), None);
+    }
+}
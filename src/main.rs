@@ -1,7 +1,9 @@
 use std::io;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 use crossterm::event::{self, KeyEvent, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::event::{MouseEvent, MouseEventKind, MouseButton};
+use crossterm::event::{EnableMouseCapture, DisableMouseCapture};
 use ratatui::{
     prelude::*,
     buffer::Buffer,
@@ -10,14 +12,18 @@ use ratatui::{
     style::Stylize,
     text::Line,
     widgets::{Paragraph, Widget, Block, Clear, Padding},
+    widgets::{Scrollbar, ScrollbarOrientation, ScrollbarState},
     DefaultTerminal, Frame,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::collections::VecDeque;
 use clog::prelude::*;
 use std::panic;
 use std::process;
-use std::io::Write;
-use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::ffi::{OsStr, OsString};
+use std::num::NonZeroUsize;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::fmt::{self, Debug, Formatter};
@@ -25,7 +31,14 @@ use std::fmt::{self, Debug, Formatter};
 use crate::log::LogKeys::MA;
 use crate::lines::*;
 use crate::pattern::*;
-use crate::cache::SearchType;
+use crate::cache::{SearchType, SplitCache};
+use crate::glyphs::Glyphs;
+use crate::export::ExportFormat;
+use crate::config::Config;
+use crate::spool::Spool;
+use crate::anchor::LineAnchor;
+use crate::accel::Accel;
+use crate::keymap::{Action, Keymap};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MarkType {
@@ -74,8 +87,8 @@ impl MarkStyle {
         m
     }
 
-    pub fn new() -> Self {
-        let mark_styles = vec![
+    fn default_sets() -> Vec<MarkStyleSet> {
+        vec![
             // None
             MarkStyleSet { styles: vec![Style::default()] },
             // Mark
@@ -110,13 +123,31 @@ impl MarkStyle {
             ] },
             // Search
             MarkStyleSet { styles: vec![Style::default().bold()] },
-        ];
+        ]
+    }
+
+    pub fn new() -> Self {
         MarkStyle {
             index: 0,
             variant: MarkType::None,
-            styles: Arc::new(mark_styles),
+            styles: Arc::new(Self::default_sets()),
         }
     }
+
+    // the palettes of a theme file, the defaults for those it doesn't give
+    pub fn from_config(path: &OsStr) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("{}", path.to_string_lossy()))?;
+        let mut sets = Self::default_sets();
+        theme::parse(&content, &mut sets)
+            .with_context(|| format!("{}", path.to_string_lossy()))?;
+
+        Ok(MarkStyle {
+            index: 0,
+            variant: MarkType::None,
+            styles: Arc::new(sets),
+        })
+    }
 }
 
 #[macro_use]
@@ -127,6 +158,100 @@ mod search;
 mod pattern;
 mod cache;
 mod lines;
+mod glyphs;
+mod export;
+mod qhash;
+mod state;
+mod config;
+mod spool;
+mod rotate;
+mod clipboard;
+mod anchor;
+mod accel;
+mod keymap;
+mod ansi;
+mod theme;
+#[cfg(test)]
+mod testutil;
+
+// minimum number of content columns on wrapped lines, regardless of the indent
+const MIN_WRAP_COLUMNS: u16 = 8;
+
+// default layout, some of it can be changed in the config file
+const DEFAULT_INDENT: u16 = 0;
+const MARKER_WIDTH: u16 = 2;
+const STATUS_WIDTH: u16 = 50;
+// " Follow" or " Paused"
+const FOLLOW_STATUS_WIDTH: u16 = 7;
+// on narrow screens the status gives way, so messages and the search stay readable
+const MIN_INPUT_WIDTH: u16 = 20;
+const FAST_HORIZONTAL: u16 = 5;
+const FAST_VERTICAL: u16 = 2;
+const WHEEL_ROWS: usize = 3;
+
+// upper limit for numeric command prefixes
+const MAX_COUNT: usize = 100000;
+
+// limits for the list of tagged lines, the lines looked at include search results and context
+const MAX_TAG_LIST: usize = 1000;
+const MAX_TAG_LIST_SCAN: usize = 100000;
+const TAG_LIST_WIDTH: usize = 100;
+
+// lines looked at for ]l/[l per key press, again continues from there
+const MAX_LONG_LINE_SCAN: usize = 100000;
+
+// files bigger than this are only opened after asking, unless set in the config
+const CONFIRM_SIZE: u64 = 16 << 30;
+
+// more context around tagged lines shows most of the file anyway
+const MAX_CONTEXT: usize = 100;
+
+// how often to check for new lines in follow mode
+const FOLLOW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+// how long the status row stays red after an alert
+const ALERT_FLASH: std::time::Duration = std::time::Duration::from_secs(1);
+
+// how long a message stays in the input row after the next key
+const MESSAGE_TIME: std::time::Duration = std::time::Duration::from_secs(1);
+
+// messages kept for ^G
+const MAX_MESSAGE_LOG: usize = 50;
+
+// new lines checked against the alert pattern at most, at once
+const MAX_ALERT_LINES: usize = 10000;
+
+// searches remembered for recalling them with Up/Down
+const MAX_SEARCH_HISTORY: usize = 100;
+
+// lines written between updates of the export progress
+const PROGRESS_INTERVAL: u64 = 4096;
+
+// a movement step repeated count times
+fn repeat(step: i16, count: usize) -> i16 {
+    step.saturating_mul(count.min(i16::MAX as usize) as i16)
+}
+
+// words and the delimiters between them come in runs. The end of the run that starts at pos
+fn run_end(len: usize, pos: usize, is_delimiter: impl Fn(usize) -> bool) -> usize {
+    let delimiter = is_delimiter(pos);
+    (pos..len).find(|&i| is_delimiter(i) != delimiter).unwrap_or(len)
+}
+
+// the start of the run that ends right before pos, not before start
+fn run_start(start: usize, pos: usize, is_delimiter: impl Fn(usize) -> bool) -> usize {
+    let delimiter = is_delimiter(pos - 1);
+    (start..pos).rev().find(|&i| is_delimiter(i) != delimiter).map_or(start, |i| i + 1)
+}
+
+// what the marker area shows in front of each line
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+enum OffsetDisplay {
+    Off,
+    Offset,     // byte offset in the file
+    Line,       // line number
+    Relative,   // distance to the cursor line
+}
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Direction {
@@ -139,12 +264,42 @@ enum Focus {
     Main,
     Search,
     Help,
+    Overlay,
+    TagList,
+    PatternList,
+    RegexTest,
+    LogLevel,
+    Command,
 }
 
 #[derive(Debug)]
 enum Undo {
     Pattern((PatternMode, PatternSet)),
     TagHide((LineId, PatternMode)),
+    Reset(Marks),
+}
+
+// all patterns, manual tags and hides and the last search
+type Marks = (PatternSet, BTreeSet<LineId>, BTreeSet<LineId>, Option<PatternId>);
+
+// progress of a slow command, shown by the ui thread while the worker is busy with it
+#[derive(Debug, Clone, Default)]
+struct Progress {
+    inner: Arc<Mutex<Option<(&'static str, f32)>>>,
+}
+
+impl Progress {
+    fn set(&self, what: &'static str, fraction: f32) {
+        *self.inner.lock().unwrap() = Some((what, fraction));
+    }
+
+    fn clear(&self) {
+        *self.inner.lock().unwrap() = None;
+    }
+
+    fn get(&self) -> Option<(&'static str, f32)> {
+        *self.inner.lock().unwrap()
+    }
 }
 
 #[derive(Debug)]
@@ -154,29 +309,88 @@ struct LogrokInner {
     // kept for reference as how the cursor is calculated, needed for resize
     area_width: u16,
     area_height: u16,
+    // the column j/k keep to and where the cursor went for it, at the end of a shorter row
+    desired_x: Option<(i16, i16)>,
     first_line: LineId,
     line_offset: usize,
     exit: bool,
+    interrupted: bool,
+    follow: bool,
+    scroll_past_end: bool,  // allow scrolling the last line up to the top
+    wrap: bool,             // wrap long lines instead of truncating them
+    horizontal_offset: usize,   // columns scrolled off to the left when truncating
+    follow_paused: bool,    // follow mode, but the end of the file is not on screen
+    counting: bool,         // matches of the search are still being counted
+    counting_lines: bool,   // line numbers are shown, but not all lines are counted yet
+    alert: Option<PatternId>, // tagging pattern that new lines in follow mode are checked for
+    alert_bell: bool,       // ring the bell on alerts, besides flashing the status row
+    bell: bool,             // an alert happened, the main thread rings the bell
+    alert_flash: Option<std::time::Instant>, // when the status row started flashing
     patterns: PatternSet,
     lines: Lines,
     display_mode: DisplayMode,
     focus: Focus,
+    keymap: Keymap,
+    pending_prefix: Option<char>,
+    bookmarks: HashMap<char, (LineId, usize)>, // line and the screen row it was shown in
+    pending_count: Option<usize>,   // numeric prefix of the next command
+    accel_rows: u16,        // rows moved by j/k, more while the key is held
+    filename: OsString,
+    fingerprint: Option<qhash::QHash>, // start of the file, tells if R can just read on
+    keep_state: bool,       // restore tags, hides and marks on start, save them on quit
     current_search: String,
+    search_history: Vec<String>,    // oldest first
+    history_pos: Option<usize>,     // the recalled entry, None while editing the input
+    history_input: String,          // what was typed before recalling an entry
+    search_origin: Option<SearchOrigin>,
+    search_preview: Option<PatternId>,  // the search being typed, before Enter
+    paused_search: Option<PatternId>,   // the last search, switched off during the preview
+    regex_test: String,     // regex being tried out on the visible lines
+    log_spec: String,       // log levels being entered, like with -l
+    command: String,        // : command being entered
+    log_output: bool,       // logging goes to a file, not to the terminal
     last_search: Option<PatternId>,
     search_direction: Direction,
     search_match_type: MatchType,
+    search_ignore_case: bool,   // toggled with Tab while typing a search
     mark_style: MarkStyle,
-    display_offset: bool,
+    display_offset: OffsetDisplay,
     display_offset_len: usize,
     before_filter_pos: HashMap<usize, (LineId, usize, i16)>,
-    status_message: Option<String>,
-    overlong_fold: HashMap<LineId, (usize, usize)>,       // crop lines to this many display lines
+    status_message: Option<String>,    // set by the last event
+    message_at: Option<std::time::Instant>, // when the status message was first shown
+    old_message: Option<(String, std::time::Instant)>, // of an earlier event, still shown
+    message_log: VecDeque<String>,
+    overlong_fold: HashMap<LineId, (usize, usize)>,
+    marker_width: u16,      // columns for the tag/hide markers, without offsets
+    status_width: u16,      // columns of the status area at the right of the bottom row
+    fast_horizontal: i16,   // columns moved by H/L
+    fast_vertical: i16,     // rows moved by J/K
+    word_end: bool,         // w/W/b/B move to the end of words like e
+    separator: bool,        // draw a rule above the status row
+    scrollbar: bool,        // show the position in the file right of the log
+    diff_above: bool,       // emphasize chars that differ from the line above
+    highlight_word: bool,   // show where else the word under the cursor is on screen
+    column_lock: bool,      // j/k move by whole lines and keep the cursor column
+    center_match: bool,     // every jump of n/N puts the match in the middle of the screen
+    collapse_hidden: bool,  // runs of hidden lines show as one row in normal mode
+    expanded: Vec<(LineId, LineId)>, // collapsed runs the user opened again
+    file_starts: Vec<LineId>, // where the files of a timeline of rotated logs start
+    pinned_line: Option<LineId>, // shown in the top row, above the scrolling log
+    strip_chars: usize,     // chars at the start of each line that are not displayed
+    debug_splits: bool,     // show the split of the cursor line, ]s/[s move between splits
     render_cursor: (u16, u16),
-    indent: String,
-    indent_chars: u16,
+    indent_chars: u16,      // wrapped rows start in this column, see wrap_indent
     help_first_line: usize,
-    help: Help,
+    help: Overlay,
+    overlay: Option<Overlay>,
+    overlay_first_line: usize,
+    tag_list: Vec<LineId>,
+    pattern_list: Vec<PatternId>,
+    glyphs: Glyphs,
     undo_stack: Vec<Undo>,
+    redo_stack: Vec<Undo>,
+    progress: Progress,
     // the fields below are rebuilt on each render
     plines: Vec<ProcessedLine>,
     line_indexes: Vec<LineIndex>,
@@ -188,9 +402,22 @@ struct LogrokInner {
 #[derive(Debug, Clone)]
 struct Logrok {
     inner: Arc<Mutex<LogrokInner>>,
+    pending_event: Option<Event>,   // read while coalescing, but not coalescable
+    accel: Accel,
 }
 
-#[derive(Debug)]
+// the view when a search was started. The preview of the search being typed starts from
+// there, like the search will, and cancelling it goes back there
+#[derive(Debug, Clone)]
+struct SearchOrigin {
+    first_line: LineId,
+    line_offset: usize,
+    cursor: (i16, i16),
+    plines: Vec<ProcessedLine>,
+    line_indexes: Vec<LineIndex>,
+}
+
+#[derive(Debug, Clone)]
 struct LineIndex {
     line_ix: usize,  // index into the lines vector
     char_index: usize,
@@ -198,27 +425,264 @@ struct LineIndex {
 }
 
 impl LogrokInner {
+    // command line options take precedence over the config file
+    fn new(mut lines: Lines, cli: &Cli, config: &Config) -> Self {
+        // a broken theme shouldn't keep the log from being shown
+        let (mark_style, theme_error) = match cli.theme {
+            Some(ref path) => match MarkStyle::from_config(path) {
+                Ok(style) => (style, None),
+                Err(e) => (MarkStyle::new(), Some(format!("using the default theme: {:#}", e))),
+            },
+            None => (MarkStyle::new(), None),
+        };
+        let (keymap, keys_error) = match cli.keys {
+            Some(ref path) => match Keymap::from_config(path) {
+                Ok(keymap) => (keymap, None),
+                Err(e) => (Keymap::default(), Some(format!("using the default keys: {:#}", e))),
+            },
+            None => (Keymap::default(), None),
+        };
+        lines.set_context(cli.context.or(config.context).unwrap_or(0) as usize);
+        let map_error = cli.mmap.then(|| lines.map_file().err()).flatten()
+            .map(|e| format!("reading the file instead of mapping it: {:#}", e));
+        let fingerprint = qhash::qhash(&lines.filename()).ok();
+        let mut inner = LogrokInner {
+            exit: false,
+            interrupted: false,
+            follow: cli.follow,
+            scroll_past_end: cli.scroll_past_end,
+            wrap: !cli.no_wrap,
+            horizontal_offset: 0,
+            follow_paused: false,
+            counting: false,
+            counting_lines: false,
+            alert: None,
+            alert_bell: config.alert_bell.unwrap_or(true),
+            bell: false,
+            alert_flash: None,
+            cursor_x: 0,
+            cursor_y: 0,
+            area_width: 1,
+            area_height: 1,
+            desired_x: None,
+            first_line: 0,
+            line_offset: 0,
+            patterns: PatternSet::new(mark_style.clone())
+                .with_tab_width(cli.tab_width as usize)
+                .with_bigword_delimiters(&cli.bigword_delimiters)
+                .with_ansi(cli.ansi),
+            lines,
+            display_mode: DisplayMode::Normal,
+            mark_style,
+            display_offset: cli.offsets.or(config.offsets).unwrap_or(OffsetDisplay::Off),
+            display_offset_len: 0,
+            focus: if config.help == Some(true) { Focus::Help } else { Focus::Main },
+            keymap,
+            pending_prefix: None,
+            bookmarks: HashMap::new(),
+            pending_count: None,
+            accel_rows: 1,
+            filename: match cli.reads_stdin() {
+                true => OsString::from("stdin"),
+                false => OsString::from(&cli.files[0]),
+            },
+            fingerprint,
+            // a temporary copy has nothing worth remembering
+            keep_state: !cli.no_state && !cli.spooled(),
+            before_filter_pos: HashMap::new(),
+            current_search: String::new(),
+            search_history: Vec::new(),
+            history_pos: None,
+            history_input: String::new(),
+            search_origin: None,
+            search_preview: None,
+            paused_search: None,
+            regex_test: String::new(),
+            log_spec: String::new(),
+            command: String::new(),
+            log_output: cli.output.is_some(),
+            search_direction: Direction::Forward,
+            search_match_type: MatchType::Text,
+            search_ignore_case: false,
+            last_search: None,
+            status_message: theme_error.or(keys_error).or(map_error),
+            message_at: None,
+            old_message: None,
+            message_log: VecDeque::new(),
+            plines: Vec::new(),
+            line_indexes: Vec::new(),
+            render_cursor: (0, 0),
+            indent_chars: cli.indent.or(config.indent).unwrap_or(DEFAULT_INDENT),
+            overlong_fold: HashMap::new(),
+            marker_width: config.marker_width.unwrap_or(MARKER_WIDTH).max(MARKER_WIDTH),
+            status_width: config.status_width.unwrap_or(STATUS_WIDTH),
+            fast_horizontal: config.fast_horizontal.unwrap_or(FAST_HORIZONTAL)
+                .min(i16::MAX as u16) as i16,
+            fast_vertical: config.fast_vertical.unwrap_or(FAST_VERTICAL)
+                .min(i16::MAX as u16) as i16,
+            word_end: config.word_end.unwrap_or(false),
+            separator: cli.separator || config.separator.unwrap_or(false),
+            scrollbar: cli.scrollbar || config.scrollbar.unwrap_or(false),
+            diff_above: false,
+            highlight_word: false,
+            column_lock: false,
+            center_match: config.center_match.unwrap_or(false),
+            collapse_hidden: config.collapse_hidden.unwrap_or(false),
+            expanded: Vec::new(),
+            file_starts: Vec::new(),
+            pinned_line: None,
+            strip_chars: cli.strip.or(config.strip).unwrap_or(0) as usize,
+            debug_splits: cli.debug_splits,
+            help_first_line: 0,
+            help: build_help(),
+            overlay: None,
+            overlay_first_line: 0,
+            tag_list: Vec::new(),
+            pattern_list: Vec::new(),
+            glyphs: if cli.ascii { Glyphs::new(true) } else { Glyphs::from_locale() },
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            progress: Progress::default(),
+            input_area: Rect::default(),
+            input_content: Vec::new(),
+        };
+        inner.update_offset_len();
+        if inner.keep_state {
+            inner.restore_state();
+        }
+        if let Some(alert) = cli.alert.as_ref().or(config.alert.as_ref()) {
+            inner.set_alert(alert);
+        }
+        for (patterns, mode) in [(&cli.tag, PatternMode::Tagging), (&cli.hide, PatternMode::Hiding),
+            (&cli.mark, PatternMode::Marking)]
+        {
+            for pattern in patterns {
+                inner.add_cli_pattern(pattern, mode);
+            }
+        }
+        if cli.tagged {
+            inner.display_mode = DisplayMode::Tagged;
+        }
+
+        inner
+    }
+
+    // the alert pattern is tagged like any other
+    fn set_alert(&mut self, alert: &str) {
+        self.alert = self.add_cli_pattern(alert, PatternMode::Tagging);
+    }
+
+    // a regex of the command line. A restored one is reused
+    fn add_cli_pattern(&mut self, pattern: &str, mode: PatternMode) -> Option<PatternId> {
+        let restored = self.patterns.iter_ids()
+            .find(|&(_, p)| p.mode == mode && p.match_type == MatchType::Regex
+                && p.pattern == pattern)
+            .map(|(id, _)| id);
+        if restored.is_some() {
+            return restored;
+        }
+        let style = match mode {
+            PatternMode::Tagging => self.mark_style.get(MarkType::Tag),
+            PatternMode::Hiding => self.mark_style.get(MarkType::Hide),
+            _ => {
+                let style = self.mark_style.get(MarkType::Mark);
+                self.mark_style.cycle_forward();
+                style
+            }
+        };
+        self.add_pattern(pattern, MatchType::Regex, Anchor::default(), false, style, mode)
+    }
+
+    fn restore_state(&mut self) {
+        let state = match state::load(&self.filename) {
+            Ok(Some(state)) => state,
+            Ok(None) => return,
+            Err(e) => {
+                self.status_message = Some(format!("failed to restore state: {}", e));
+                return;
+            }
+        };
+        self.lines.restore(state.tagged_lines, state.hidden_lines);
+        for p in state.patterns {
+            let variant = match p.mode {
+                PatternMode::Tagging => MarkType::Tag,
+                PatternMode::Hiding => MarkType::Hide,
+                _ => MarkType::Mark,
+            };
+            let mut style = self.mark_style.get(variant);
+            style.index = p.color;
+            if p.mode == PatternMode::Marking {
+                // new marks continue with the next color
+                self.mark_style.index = self.mark_style.index.max(p.color + 1);
+            }
+            // already checked when the state file was read
+            if let Ok(id) = self.patterns.add(&p.pattern, p.match_type, p.anchor, p.ignore_case,
+                style, p.mode)
+            {
+                if p.negative {
+                    self.patterns.with(id, |pattern| pattern.negative = true);
+                }
+            }
+        }
+        self.update_patterns(PatternMode::Tagging);
+        self.update_patterns(PatternMode::Hiding);
+        self.status_message = Some("restored tags, hides and marks".to_string());
+    }
+
+    fn save_state(&self) -> Result<()> {
+        let patterns = self.patterns.iter()
+            .filter(|p| p.mode != PatternMode::Search)
+            .map(|p| state::SavedPattern {
+                pattern: p.pattern.clone(),
+                match_type: p.match_type,
+                anchor: p.anchor,
+                ignore_case: p.ignore_case,
+                mode: p.mode,
+                negative: p.negative,
+                color: p.style.index,
+            })
+            .collect();
+        let state = state::State {
+            tagged_lines: self.lines.tagged_lines().clone(),
+            hidden_lines: self.lines.hidden_lines().clone(),
+            patterns,
+        };
+
+        state::save(&self.filename, &state)
+    }
+
     fn undo_push_pattern(&mut self, mode: PatternMode) {
         let p = self.patterns.clone();
         lD3!(MA, "push pattern to undo stack: {:?}", p);
-        self.undo_stack.push(Undo::Pattern((mode, p)));
+        self.undo_push(Undo::Pattern((mode, p)));
     }
 
     fn update_patterns(&mut self, mode: PatternMode) {
         match mode {
             PatternMode::Tagging => self.lines.update_patterns(SearchType::Tag, &self.patterns),
             PatternMode::Search => self.lines.update_patterns(SearchType::Search, &self.patterns),
+            PatternMode::Hiding => self.lines.update_patterns(SearchType::Hide, &self.patterns),
             _ => (),
         }
     }
 
-    fn add_pattern(&mut self, pattern: &str, match_type: MatchType, style: MarkStyle,
-        mode: PatternMode) -> PatternId
+    // an invalid regex is reported in the status line
+    fn add_pattern(&mut self, pattern: &str, match_type: MatchType, anchor: Anchor,
+        ignore_case: bool, style: MarkStyle, mode: PatternMode) -> Option<PatternId>
     {
-        let id = self.patterns.add(&pattern, match_type, style, mode);
+        let id = match self.patterns.add(&pattern, match_type, anchor, ignore_case, style, mode) {
+            Ok(id) => id,
+            Err(e) => {
+                // the last line of the error is the gist, the others point into the pattern
+                let e = e.to_string();
+                self.status_message = Some(e.lines().last().unwrap_or_default().to_string());
+                return None;
+            }
+        };
         self.update_patterns(mode);
+        self.redo_stack.clear();
 
-        id
+        Some(id)
     }
 
     fn remove_pattern(&mut self, id: PatternId) {
@@ -226,138 +690,284 @@ impl LogrokInner {
         lD1!(MA, "mark: removing pattern: {} mode {:?}", id, mode);
         self.patterns.remove(id);
         self.update_patterns(mode);
+        self.redo_stack.clear();
+    }
+
+    // a held movement key piles up presses while a slow step is worked on, of which the last
+    // is as good as all. Other keys might be typed text, a count or belong to a prefix
+    fn repeats_movement(&self, key_event: &KeyEvent) -> bool {
+        if self.focus != Focus::Main || self.pending_prefix.is_some()
+            || self.pending_count.is_some()
+        {
+            return false;
+        }
+        matches!(self.keymap.get(key_event), Some(Action::ScrollDown | Action::ScrollDownHalf
+            | Action::ScrollDownPage | Action::ScrollUp | Action::ScrollUpHalf
+            | Action::ScrollUpPage | Action::FoldScrollDown | Action::FoldScrollDownHalf
+            | Action::FoldScrollDownPage | Action::FoldScrollUp | Action::FoldScrollUpHalf
+            | Action::FoldScrollUpPage | Action::ScrollRightHalf | Action::ScrollLeftHalf
+            | Action::ScrollRightPage | Action::ScrollLeftPage | Action::Down | Action::Up
+            | Action::Left | Action::Right | Action::FastDown | Action::FastUp
+            | Action::FastLeft | Action::FastRight))
     }
 
     // events that don't need the layout or may change the layout
     fn handle_event_before_layout(&mut self, key_event: &KeyEvent) -> bool {
-        if key_event.modifiers.contains(KeyModifiers::CONTROL) {
-            match key_event.code {
-                KeyCode::Char('h') => self.help(),
-                _ => false,
-            }
-        } else if key_event.modifiers.contains(KeyModifiers::ALT) {
-            false
-        } else {
-            match key_event.code {
-                KeyCode::Char('@') => self.offsets(),
-                KeyCode::Char('q') => self.exit(),
-                _ => false,
-            }
+        let action = self.keymap.get(key_event);
+        // the key after ` ' ] [ g belongs to them, like the q of `q
+        if self.pending_prefix.is_some() && action != Some(Action::Interrupt) {
+            return false;
+        }
+        match action {
+            Some(Action::Help) => self.help(),
+            Some(Action::Interrupt) => self.interrupt(),
+            Some(Action::Offsets) => self.offsets(),
+            Some(Action::TogglePin) => self.toggle_pin(),
+            Some(Action::Quit) => self.exit(),
+            _ => false,
         }
     }
 
-    // events that need the layout. this must not change the layout. It is possible
-    // to split an event in both before and after.
-    fn handle_event_after_layout(&mut self, key_event: &KeyEvent) -> bool {
-        if key_event.modifiers.contains(KeyModifiers::CONTROL) {
-            let area_height = self.area_height;
-            let cnt = match key_event.code {
-                KeyCode::Char('e') => 1,
-                KeyCode::Char('d') => area_height / 2,
-                KeyCode::Char('f') => area_height,
-                _ => 0,
-            };
-            for _ in 0..cnt {
+    // scroll the view by rows, a single row keeps the cursor on its line
+    fn scroll_rows(&mut self, rows: u16, direction: Direction) -> bool {
+        for _ in 0..rows {
+            if direction == Direction::Forward {
                 let scrolled = self.scroll_down();
-                if cnt == 1 && scrolled && self.cursor_y > 0 {
+                if rows == 1 && scrolled && self.cursor_y > 0 {
                     self.move_cursor(0, -1);
                 }
-            }
-            if cnt > 0 {
-                return true;
-            }
-            let cnt = match key_event.code {
-                KeyCode::Char('y') => 1,
-                KeyCode::Char('u') => area_height / 2,
-                KeyCode::Char('b') => area_height,
-                _ => 0,
-            };
-            for _ in 0..cnt {
+            } else {
                 let scrolled = self.scroll_up();
-                if cnt == 1 && scrolled &&
-                    self.cursor_y < (area_height - 1) as i16
+                if rows == 1 && scrolled &&
+                    self.cursor_y < (self.area_height - 1) as i16
                 {
                     self.move_cursor(0, 1);
                 }
             }
-            if cnt > 0 {
-                return true;
+        }
+
+        rows > 0
+    }
+
+    // events that need the layout. this must not change the layout. It is possible
+    // to split an event in both before and after.
+    fn handle_event_after_layout(&mut self, key_event: &KeyEvent) -> bool {
+        if let Some(prefix) = self.pending_prefix.take() {
+            return self.handle_prefixed_event(prefix, key_event);
+        }
+        // numeric count prefix like in 10+. A leading 0 is still start of line
+        let count = self.pending_count.take();
+        if let KeyCode::Char(c @ '0'..='9') = key_event.code {
+            if key_event.modifiers.is_empty() && (c != '0' || count.is_some()) {
+                let digit = c.to_digit(10).unwrap() as usize;
+                self.pending_count = Some((count.unwrap_or(0) * 10 + digit).min(MAX_COUNT));
+                return false;
             }
-            match key_event.code {
-                KeyCode::Char('r') => self.redo(),
-                _ => false,
+        }
+        let pending_count = count;
+        let count = count.unwrap_or(1);
+        if key_event.code == KeyCode::Esc && self.highlight_word {
+            return self.toggle_highlight_word();
+        }
+        let Some(action) = self.keymap.get(key_event) else {
+            return false;
+        };
+        let area_height = self.area_height;
+        let area_width = self.area_width;
+        match action {
+            Action::ScrollDown => self.scroll_rows(1, Direction::Forward),
+            Action::ScrollDownHalf => self.scroll_rows(area_height / 2, Direction::Forward),
+            Action::ScrollDownPage => self.scroll_rows(area_height, Direction::Forward),
+            Action::ScrollUp => self.scroll_rows(1, Direction::Backward),
+            Action::ScrollUpHalf => self.scroll_rows(area_height / 2, Direction::Backward),
+            Action::ScrollUpPage => self.scroll_rows(area_height, Direction::Backward),
+            Action::FoldScrollDown | Action::FoldScrollDownHalf | Action::FoldScrollDownPage |
+            Action::FoldScrollUp | Action::FoldScrollUpHalf | Action::FoldScrollUpPage => {
+                let (rows, direction) = match action {
+                    Action::FoldScrollDown => (1, Direction::Forward),
+                    Action::FoldScrollDownHalf => (area_height / 2, Direction::Forward),
+                    Action::FoldScrollDownPage => (area_height, Direction::Forward),
+                    Action::FoldScrollUp => (1, Direction::Backward),
+                    Action::FoldScrollUpHalf => (area_height / 2, Direction::Backward),
+                    _ => (area_height, Direction::Backward),
+                };
+                if rows > 0 {
+                    self.scroll_fold_up_down(rows as usize * count, direction);
+                }
+                true
             }
-        } else if key_event.modifiers.contains(KeyModifiers::ALT) {
-            let area_height = self.area_height;
-            let cnt = match key_event.code {
-                KeyCode::Char('e') => 1,
-                KeyCode::Char('d') => area_height as usize / 2,
-                KeyCode::Char('f') => area_height as usize,
-                _ => 0,
-            };
-            if cnt > 0 {
-                self.scroll_fold_up_down(cnt, Direction::Forward);
-            }
-            let cnt = match key_event.code {
-                KeyCode::Char('y') => 1,
-                KeyCode::Char('u') => area_height as usize / 2,
-                KeyCode::Char('b') => area_height as usize,
-                _ => 0,
-            };
-            if cnt > 0 {
-                self.scroll_fold_up_down(cnt, Direction::Backward);
+            Action::Redo => self.redo(),
+            Action::Hexdump => self.hexdump(),
+            Action::TagList => self.tag_list(),
+            Action::PatternList => self.pattern_list(),
+            Action::MessageLog => self.message_log(),
+            Action::Info => self.info(),
+            Action::ToggleFollow => self.toggle_follow(),
+            Action::Reload => self.reload(),
+            Action::Down => self.move_vertical(repeat(self.accel_rows as i16, count)),
+            Action::Up => self.move_vertical(repeat(-(self.accel_rows as i16), count)),
+            Action::ScrollRightHalf => self.scroll_sideways(area_width / 2, Direction::Forward),
+            Action::ScrollLeftHalf => self.scroll_sideways(area_width / 2, Direction::Backward),
+            Action::ScrollRightPage => self.scroll_sideways(area_width, Direction::Forward),
+            Action::ScrollLeftPage => self.scroll_sideways(area_width, Direction::Backward),
+            Action::Left => self.move_cursor(repeat(-1, count), 0),
+            Action::Right => self.move_cursor(repeat(1, count), 0),
+            Action::FastDown => self.move_cursor(0, repeat(self.fast_vertical, count)),
+            Action::FastUp => self.move_cursor(0, repeat(-self.fast_vertical, count)),
+            Action::FastLeft => self.move_cursor(repeat(-self.fast_horizontal, count), 0),
+            Action::FastRight => self.move_cursor(repeat(self.fast_horizontal, count), 0),
+            Action::WordForward =>
+                self.move_words(MatchType::SmallWord, Direction::Forward, count),
+            Action::BigWordForward =>
+                self.move_words(MatchType::BigWord, Direction::Forward, count),
+            Action::WordBackward =>
+                self.move_words(MatchType::SmallWord, Direction::Backward, count),
+            Action::BigWordBackward =>
+                self.move_words(MatchType::BigWord, Direction::Backward, count),
+            Action::WordEnd =>
+                self.move_words_end(MatchType::SmallWord, Direction::Forward, count),
+            Action::BigWordEnd =>
+                self.move_words_end(MatchType::BigWord, Direction::Forward, count),
+            Action::Start => self.move_start(),
+            Action::End => match pending_count {
+                Some(number) => self.move_to_line_number(number as u64),
+                None => self.move_end(),
+            },
+            Action::StartOfLine => self.start_of_line(),
+            Action::NextPrefix => self.set_prefix(']'),
+            Action::PreviousPrefix => self.set_prefix('['),
+            Action::GotoPrefix => {
+                // for 3ge and 10gg
+                self.pending_count = pending_count;
+                self.set_prefix('g')
             }
-            true
-        } else {
-            match key_event.code {
-                KeyCode::Char('j') => self.move_cursor(0, 1),
-                KeyCode::Char('k') => self.move_cursor(0, -1),
-                KeyCode::Char('h') => self.move_cursor(-1, 0),
-                KeyCode::Char('l') => self.move_cursor(1, 0),
-                KeyCode::Char('J') => self.move_cursor(0, 2),
-                KeyCode::Char('K') => self.move_cursor(0, -2),
-                KeyCode::Char('H') => self.move_cursor(-5, 0),
-                KeyCode::Char('L') => self.move_cursor(5, 0),
-                KeyCode::Char('w') => self.move_word(MatchType::SmallWord, Direction::Forward),
-                KeyCode::Char('W') => self.move_word(MatchType::BigWord, Direction::Forward),
-                KeyCode::Char('b') => self.move_word(MatchType::SmallWord, Direction::Backward),
-                KeyCode::Char('B') => self.move_word(MatchType::BigWord, Direction::Backward),
-                KeyCode::Char('g') => self.move_start(),
-                KeyCode::Char('G') => self.move_end(),
-                KeyCode::Char('0') => self.start_of_line(),
-                KeyCode::Char('$') => self.end_of_line(),
-                KeyCode::Char('F') => self.fold_line(),
-                KeyCode::Char('+') => self.fold_more_less(true),
-                KeyCode::Char('-') => self.fold_more_less(false),
-                KeyCode::Char('i') => self.set_indent(),
-                KeyCode::Char('t') => self.tag_hide(true, PatternMode::Tagging),
-                KeyCode::Char('T') => self.tag_hide(false, PatternMode::Tagging),
-                KeyCode::Char('f') => self.display(Direction::Forward),
-                KeyCode::Char('d') => self.display(Direction::Backward),
-                KeyCode::Char('m') => self.mark(MatchType::SmallWord),
-                KeyCode::Char('M') => self.mark(MatchType::BigWord),
-                KeyCode::Char('c') => self.cycle_color(Direction::Forward),
-                KeyCode::Char('C') => self.cycle_color(Direction::Backward),
-                KeyCode::Char('/') => self.search(Direction::Forward, MatchType::Text),
-                KeyCode::Char('&') => self.search(Direction::Forward, MatchType::Regex),
-                KeyCode::Char('?') => self.search(Direction::Backward, MatchType::Text),
-                KeyCode::Char('n') => self.search_cont(Direction::Forward),
-                KeyCode::Char('N') => self.search_cont(Direction::Backward),
-                KeyCode::Char('.') => self.mark_extend(true, Direction::Forward),
-                KeyCode::Char(',') => self.mark_extend(false, Direction::Forward),
-                KeyCode::Char('<') => self.mark_extend(true, Direction::Backward),
-                KeyCode::Char('>') => self.mark_extend(false, Direction::Backward),
-                KeyCode::Char('x') => self.tag_hide(true, PatternMode::Hiding),
-                KeyCode::Char('X') => self.tag_hide(false, PatternMode::Hiding),
-                KeyCode::Char('u') => self.undo(),
-                // todo: fast movement with shift
-                KeyCode::Left => self.move_cursor(-1, 0),
-                KeyCode::Right => self.move_cursor(1, 0),
-                KeyCode::Up => self.move_cursor(0, -1),
-                KeyCode::Down => self.move_cursor(0, 1),
+            Action::SetBookmark => self.set_prefix('`'),
+            Action::JumpToBookmark => self.set_prefix('\''),
+            Action::EndOfLine => self.end_of_line(),
+            Action::FoldLine => self.fold_line(),
+            Action::ToggleWrap => self.toggle_wrap(),
+            Action::FoldMore => self.fold_more_less(true, count),
+            Action::FoldLess => self.fold_more_less(false, count),
+            Action::SetIndent => self.set_indent(),
+            Action::Tag => self.tag_hide(true, PatternMode::Tagging),
+            Action::Untag => self.tag_hide(false, PatternMode::Tagging),
+            Action::TagMark => self.tag_mark(),
+            Action::DisplayForward => self.display(Direction::Forward),
+            Action::DisplayBackward => self.display(Direction::Backward),
+            Action::ToggleDiffAbove => self.toggle_diff_above(),
+            Action::MarkWord => self.mark(MatchType::SmallWord),
+            Action::MarkBigWord => self.mark(MatchType::BigWord),
+            Action::MarkText => self.mark(MatchType::Text),
+            Action::MarkField => self.mark_field(),
+            Action::ToggleColumnLock => self.toggle_column_lock(),
+            Action::MoreContext => self.change_context(count as isize),
+            Action::LessContext => self.change_context(-(count as isize)),
+            Action::ToggleCollapse => self.toggle_collapse(),
+            Action::ExpandCollapsed => self.expand_collapsed(),
+            Action::CycleColor => self.cycle_color(Direction::Forward),
+            Action::CycleColorBackward => self.cycle_color(Direction::Backward),
+            Action::SearchForward => self.search(Direction::Forward, MatchType::Text),
+            Action::SearchBackward => self.search(Direction::Backward, MatchType::Text),
+            Action::RegexSearchForward => self.search(Direction::Forward, MatchType::Regex),
+            Action::RegexSearchBackward => self.search(Direction::Backward, MatchType::Regex),
+            Action::RegexTest => self.regex_test(),
+            Action::LogLevel => self.log_level(),
+            Action::NextMatch => self.search_cont(Direction::Forward),
+            Action::PreviousMatch => self.search_cont(Direction::Backward),
+            Action::ClearSearch => self.clear_search(),
+            Action::ExtendMarkRight => self.mark_extend(true, Direction::Forward),
+            Action::ShrinkMarkRight => self.mark_extend(false, Direction::Forward),
+            Action::ExtendMarkLeft => self.mark_extend(true, Direction::Backward),
+            Action::ShrinkMarkLeft => self.mark_extend(false, Direction::Backward),
+            Action::ExtendMarkWordRight => self.mark_extend_word(true, Direction::Forward),
+            Action::ShrinkMarkWordRight => self.mark_extend_word(false, Direction::Forward),
+            Action::ExtendMarkWordLeft => self.mark_extend_word(true, Direction::Backward),
+            Action::ShrinkMarkWordLeft => self.mark_extend_word(false, Direction::Backward),
+            Action::Hide => self.tag_hide(true, PatternMode::Hiding),
+            Action::Unhide => self.tag_hide(false, PatternMode::Hiding),
+            Action::HideOthers => self.hide_others(),
+            Action::Undo => self.undo(),
+            Action::Yank => self.yank(),
+            Action::ShowAnchor => self.show_anchor(),
+            Action::ExportHtml => self.export(ExportFormat::Html),
+            Action::ExportAnsi => self.export(ExportFormat::Ansi),
+            Action::ExportText => self.export(ExportFormat::Text),
+            Action::Command => self.command(),
+            Action::HighlightWord => self.toggle_highlight_word(),
+            // handled before the layout
+            Action::Help | Action::Interrupt | Action::Offsets | Action::TogglePin |
+            Action::Quit => false,
+        }
+    }
+
+    // second key of a two-key command like ]l
+    fn handle_prefixed_event(&mut self, prefix: char, key_event: &KeyEvent) -> bool {
+        if prefix == 'g' {
+            let count = self.pending_count.take();
+            return match key_event.code {
+                KeyCode::Char('g') => match count {
+                    Some(number) => self.move_to_line_number(number as u64),
+                    None => self.move_start(),
+                },
+                KeyCode::Char('e') => self.move_words_end(MatchType::SmallWord,
+                    Direction::Backward, count.unwrap_or(1)),
+                KeyCode::Char('E') => self.move_words_end(MatchType::BigWord,
+                    Direction::Backward, count.unwrap_or(1)),
                 _ => false,
+            };
+        }
+        if let KeyCode::Char(name @ 'a'..='z') = key_event.code {
+            match prefix {
+                '`' => return self.set_bookmark(name),
+                '\'' => return self.jump_to_bookmark(name),
+                _ => {}
             }
         }
+        let direction = if prefix == ']' {
+            Direction::Forward
+        } else {
+            Direction::Backward
+        };
+        match key_event.code {
+            KeyCode::Char('l') => self.move_long_line(direction),
+            KeyCode::Char('t') => self.move_tagged(direction),
+            KeyCode::Char('s') if self.debug_splits => self.move_split(direction),
+            _ => false,
+        }
+    }
+
+    fn set_bookmark(&mut self, name: char) -> bool {
+        let Some((line_id, line_part)) = self.cursor_anchor() else {
+            return false;
+        };
+        let row = (self.cursor_y as usize).saturating_sub(line_part);
+        self.bookmarks.insert(name, (line_id, row));
+        self.status_message = Some(format!("bookmark {} set, '{} to go back", name, name));
+
+        false
+    }
+
+    // show the line of the bookmark in the same row again. If it isn't shown in the current
+    // display mode, the nearest line that is
+    fn jump_to_bookmark(&mut self, name: char) -> bool {
+        let Some(&(line_id, row)) = self.bookmarks.get(&name) else {
+            self.status_message = Some(format!("bookmark {} is not set", name));
+            return false;
+        };
+        if self.lines.is_filtered_line(line_id, self.display_mode, &self.patterns) != Some(false) {
+            self.status_message = Some(format!("line of bookmark {} is not shown", name));
+        }
+        self.set_cursor_x(0);
+        self.cursor_y = (row as i16).min(self.area_height as i16 - 1);
+        self.lines.set_current_line(line_id);
+        self.anchor_cursor(line_id, 0);
+
+        true
+    }
+
+    fn set_prefix(&mut self, prefix: char) -> bool {
+        self.pending_prefix = Some(prefix);
+        false
     }
 
     fn handle_search_event_before_layout(&mut self, _key_event: &KeyEvent) -> bool {
@@ -373,21 +983,38 @@ impl LogrokInner {
         match key_event.code {
             KeyCode::Char(c) => {
                 self.current_search.push(c);
-                false
+                self.preview_search()
             }
+            KeyCode::Backspace if self.current_search.is_empty() => self.cancel_search(),
             KeyCode::Backspace => {
-                if self.current_search.is_empty() {
-                    self.focus = Focus::Main;
-                    return true;
-                }
                 self.current_search.pop();
-                false
+                self.preview_search()
+            }
+            KeyCode::Esc => self.cancel_search(),
+            KeyCode::Tab => {
+                self.search_ignore_case = !self.search_ignore_case;
+                self.preview_search()
+            }
+            KeyCode::Up => {
+                self.recall_search(Direction::Backward);
+                self.preview_search()
+            }
+            KeyCode::Down => {
+                self.recall_search(Direction::Forward);
+                self.preview_search()
             }
             KeyCode::Enter => {
-                self.focus = Focus::Main;
                 let input = self.current_search.clone();
+                // the real search starts from where the preview did
+                self.clear_preview();
+                self.restore_search_origin();
+                if !self.do_search(input) {
+                    // keep the input open to fix the pattern
+                    return true;
+                }
+                self.search_origin = None;
+                self.focus = Focus::Main;
                 self.current_search.clear();
-                self.do_search(input);
                 true
             }
             _ => false,
@@ -413,6 +1040,7 @@ impl LogrokInner {
             match key_event.code {
                 KeyCode::Char('q') |
                 KeyCode::Char(' ') |
+                KeyCode::Esc |
                 KeyCode::Enter => {
                     self.focus = Focus::Main;
                     true
@@ -432,1064 +1060,3011 @@ impl LogrokInner {
         }
     }
 
-    fn move_cursor(&mut self, dx: i16, mut dy: i16) -> bool {
-        self.cursor_x = (self.cursor_x + dx).max(0).min(self.area_width as i16 - 1);
-        let mut cursor_y = self.cursor_y;
-        let area_height = self.area_height as i16;
+    fn handle_overlay_event_after_layout(&mut self, key_event: &KeyEvent) -> bool {
+        lD3!(MA, "overlay event: {:?}", key_event);
 
-        let mut moved = false;
-        while dy > 0 {
-            if cursor_y == area_height as i16 - 1 {
-                moved |= self.scroll_down();
-            } else {
-                cursor_y += 1;
+        match key_event.code {
+            KeyCode::Char('q') |
+            KeyCode::Char(' ') |
+            KeyCode::Enter => {
+                self.focus = Focus::Main;
+                self.overlay = None;
+                true
             }
-            dy -= 1;
-        }
-        while dy < 0 {
-            if cursor_y == 0 {
-                moved |= self.scroll_up();
-            } else {
-                cursor_y -= 1;
+            KeyCode::Char('j') => {
+                self.overlay_first_line += 1;
+                true
             }
-            dy += 1;
+            KeyCode::Char('k') => {
+                if self.overlay_first_line > 0 {
+                    self.overlay_first_line -= 1;
+                }
+                true
+            }
+            _ => false,
         }
-        self.cursor_y = cursor_y;
-        self.before_filter_pos.clear();
-
-        moved
     }
 
-    fn move_start(&mut self) -> bool {
-        self.cursor_x = 0;
-        self.cursor_y = 0;
-        self.first_line = 0;
-        self.line_offset = 0;
-        self.lines.set_current_line(self.first_line);
-        if let Some(id) = self.adjust_to_unfiltered_line(0) {
-            self.first_line = id;
+    fn handle_tag_list_event_after_layout(&mut self, key_event: &KeyEvent) -> bool {
+        lD3!(MA, "tag list event: {:?}", key_event);
+
+        let Some(ref mut overlay) = self.overlay else {
+            self.focus = Focus::Main;
+            return true;
+        };
+        let selected = overlay.selected.unwrap_or(0);
+        match key_event.code {
+            KeyCode::Char('q') => {
+                self.focus = Focus::Main;
+                self.overlay = None;
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                overlay.selected = Some((selected + 1).min(self.tag_list.len() - 1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                overlay.selected = Some(selected.saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                self.focus = Focus::Main;
+                self.overlay = None;
+                let line_id = self.tag_list[selected];
+                self.set_cursor_x(0);
+                self.move_line_under_cursor(line_id, 0);
+            }
+            _ => return false,
         }
 
         true
     }
 
-    fn move_end(&mut self) -> bool {
-        let mut last_line_id = self.lines.last_line_id();
-        self.lines.set_current_line(self.first_line);
-        if let Some(id) = self.adjust_to_unfiltered_line(last_line_id) {
-            last_line_id = id;
+    fn handle_pattern_list_event_after_layout(&mut self, key_event: &KeyEvent) -> bool {
+        lD3!(MA, "pattern list event: {:?}", key_event);
+
+        let Some(ref mut overlay) = self.overlay else {
+            self.focus = Focus::Main;
+            return true;
+        };
+        let selected = overlay.selected.unwrap_or(0);
+        match key_event.code {
+            KeyCode::Char('q') => {
+                self.focus = Focus::Main;
+                self.overlay = None;
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                overlay.selected = Some((selected + 1).min(self.pattern_list.len() - 1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                overlay.selected = Some(selected.saturating_sub(1));
+            }
+            KeyCode::Char(' ') | KeyCode::Enter | KeyCode::Char('c') | KeyCode::Char('C') |
+                KeyCode::Char('m') | KeyCode::Char('d') =>
+            {
+                let anchor = self.cursor_anchor();
+                self.change_pattern(selected, key_event.code);
+                if let Some((line_id, line_part)) = anchor {
+                    self.anchor_cursor(line_id, line_part);
+                }
+                if self.pattern_list.is_empty() {
+                    self.focus = Focus::Main;
+                    self.overlay = None;
+                    return true;
+                }
+                let mut overlay = Overlay::new(self.pattern_list_text(),
+                    self.overlay.as_ref().unwrap().bottom.clone());
+                overlay.selected = Some(selected.min(self.pattern_list.len() - 1));
+                self.overlay = Some(overlay);
+            }
+            _ => return false,
         }
 
-        self.cursor_x = 0;
-        self.cursor_y = self.area_height as i16 - 1;
+        true
+    }
 
-        lD3!(MA, "move_end: last_line_id: {}", last_line_id);
-        self.first_line = last_line_id;
-
-        let pline = self.get_line(last_line_id).unwrap();
-        let parts = self.line_parts(&pline, self.area_width) as usize;
-        self.move_line_under_cursor(last_line_id, parts - 1);
-
-        true
-    }
-
-    fn move_word(&mut self, match_type: MatchType, direction: Direction) -> bool {
-        let Some((pos, line_ix, line_part)) = self.resolve_cursor_position() else {
-            return false;
+    // Space switches the selected pattern off and on, c/C cycle its color, m cycles it
+    // between marking, tagging, hiding and hiding others and d deletes it
+    fn change_pattern(&mut self, selected: usize, code: KeyCode) {
+        let id = self.pattern_list[selected];
+        let (mode, enabled, negative) = {
+            let p = self.patterns.get(id);
+            (p.mode, p.enabled, p.negative)
         };
-        let pline = &self.plines[line_ix];
-        let linelen = pline.chars.len();
-        let mut pos = if let Some(pos) = pos {
-            pos
-        } else {
-            let parts = self.line_parts(pline, self.area_width) as usize;
-            // set pos to non-indent part of the line
-            if line_part == parts - 1 && self.cursor_x >= self.indent_chars as i16 {
-                if direction == Direction::Forward {
-                    return false;
-                }
-                linelen - 1
-            } else {
-                assert!(self.cursor_x < self.indent_chars as i16);
-                self.area_width as usize +
-                    (line_part - 1) * (self.area_width as usize - self.indent_chars as usize)
+        match code {
+            KeyCode::Char('c') | KeyCode::Char('C') => {
+                self.patterns.with(id, |p| {
+                    if code == KeyCode::Char('c') {
+                        p.style.cycle_forward();
+                    } else {
+                        p.style.cycle_backward();
+                    }
+                });
+                self.redo_stack.clear();
             }
-        };
-        lD5!(MA, "move_word: pos: {} line_ix: {} line_part: {}", pos, line_ix, line_part);
-
-        let deliminator = match_type.delimiter();
-
-        if direction == Direction::Forward {
-            let invert = !deliminator.contains(pline.chars[pos].c);
-            while pos < linelen - 1 && (invert ^ deliminator.contains(pline.chars[pos].c)) {
-                pos += 1;
+            KeyCode::Char('m') => {
+                let (new_mode, variant, new_negative) = match mode {
+                    PatternMode::Marking => (PatternMode::Tagging, MarkType::Tag, false),
+                    PatternMode::Tagging => (PatternMode::Hiding, MarkType::Hide, false),
+                    PatternMode::Hiding if !negative => (PatternMode::Hiding, MarkType::Hide, true),
+                    PatternMode::Hiding => (PatternMode::Marking, MarkType::Mark, false),
+                    PatternMode::Search => {
+                        self.status_message = Some("a search stays a search".to_string());
+                        return;
+                    }
+                };
+                self.undo_push_pattern(mode);
+                self.patterns.with(id, |p| {
+                    p.mode = new_mode;
+                    p.style.variant = variant;
+                    p.negative = new_negative;
+                });
+                self.update_patterns(mode);
+                self.update_patterns(new_mode);
+                self.redo_stack.clear();
             }
-        } else {
-            if pos == 0 {
-                return false;
+            KeyCode::Char('d') => {
+                self.undo_push_pattern(mode);
+                if self.last_search == Some(id) {
+                    self.last_search = None;
+                }
+                self.remove_pattern(id);
+                self.pattern_list.remove(selected);
             }
-            let invert = !deliminator.contains(pline.chars[pos - 1].c);
-            while pos > 0 && (invert ^ deliminator.contains(pline.chars[pos - 1].c)) {
-                pos -= 1;
+            _ => {
+                self.undo_push_pattern(mode);
+                self.patterns.set_enabled(id, !enabled);
+                self.update_patterns(mode);
+                self.redo_stack.clear();
             }
         }
-        lD5!(MA, "move_word: new pos: {}", pos);
+    }
 
-        let (x, y) = self.cursor_from_pos_ix(pos, line_ix, self.area_width);
-        self.cursor_x = x as i16;
-        self.cursor_y = y as i16;
+    fn handle_regex_test_event_after_layout(&mut self, key_event: &KeyEvent) -> bool {
+        lD3!(MA, "regex test event: {:?}", key_event);
+
+        if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+            return match key_event.code {
+                KeyCode::Char('t') => self.regex_test_promote(PatternMode::Tagging),
+                _ => false,
+            };
+        }
+        match key_event.code {
+            KeyCode::Char(c) => self.regex_test.push(c),
+            KeyCode::Backspace => {
+                if self.regex_test.pop().is_none() {
+                    self.focus = Focus::Main;
+                }
+            }
+            KeyCode::Enter => return self.regex_test_promote(PatternMode::Search),
+            _ => return false,
+        }
 
         false
     }
 
-    fn exit(&mut self) -> bool {
-        self.exit = true;
+    fn regex_test(&mut self) -> bool {
+        self.focus = Focus::RegexTest;
+        self.regex_test.clear();
+
         false
     }
 
-    fn scroll_down(&mut self) -> bool {
-        lD4!(MA, "scroll_down: self.line_offset: {} indexes {:?}",
-            self.line_offset, self.line_indexes);
-
-        /*
-         * don't scroll down if the bottom line is the last line
-         */
-        let mode = self.display_mode;
-        let last_line_index = self.line_indexes.last().unwrap();
-        let last_pline = &self.plines[last_line_index.line_ix];
-        let last_parts = self.line_parts(last_pline, self.area_width);
-        lD5!(MA, "scroll_down: last_line_index: {:?} last_parts: {}", last_line_index, last_parts);
-        if last_line_index.line_part == last_parts - 1 && self.lines.next_line(SearchType::Tag,
-            last_pline.line_id, &self.patterns, mode, false).is_none()
-        {
+    // turn the tested regex into a real search or tag pattern
+    fn regex_test_promote(&mut self, mode: PatternMode) -> bool {
+        let re = self.patterns.build_re(MatchType::Regex, &self.regex_test, Anchor::default(),
+            false);
+        if self.regex_test.is_empty() || regex::Regex::new(&re).is_err() {
             return false;
         }
-
-        let Some(index1) = self.line_indexes.get(1) else {
-            return false;
-        };
-        if index1.line_part > 0 && self.line_offset < index1.line_part {
-            self.line_offset += 1;
+        self.focus = Focus::Main;
+        let input = std::mem::take(&mut self.regex_test);
+        if mode == PatternMode::Search {
+            self.search_direction = Direction::Forward;
+            self.search_match_type = MatchType::Regex;
+            self.do_search(input);
             return true;
         }
-        self.line_offset = 0;
-
-        let first_line = self.first_line;
-        let Some(next_line_id) = self.lines.next_line(SearchType::Tag, first_line,
-            &self.patterns, mode, false) else
-        {
+        let Some((line_id, line_part)) = self.cursor_anchor() else {
             return false;
         };
+        let style = self.mark_style.get(MarkType::Tag);
+        self.undo_push_pattern(PatternMode::Tagging);
+        self.add_pattern(&input, MatchType::Regex, Anchor::default(), false, style,
+            PatternMode::Tagging);
+        self.anchor_cursor(line_id, line_part);
 
-        self.first_line = next_line_id;
-        self.lines.set_current_line(self.first_line);
-        return true;
+        true
     }
 
-    fn scroll_up(&mut self) -> bool {
-        lD4!(MA, "scroll_up: self.line_offset: {} indexes {:?}",
-            self.line_offset, self.line_indexes);
-
-        if self.line_offset > 0 {
-            self.line_offset -= 1;
-            return true;
+    // the chars of the visible lines matched by the tested regex, and the number of matches.
+    // None if the regex doesn't compile
+    fn regex_test_matches(&self) -> Option<(Vec<Vec<bool>>, usize)> {
+        let re = regex::Regex::new(&self.regex_test).ok()?;
+        let mut count = 0;
+        let mut matched = Vec::new();
+        for pline in &self.plines {
+            let text = pline.chars.iter().map(|sc| sc.c).collect::<String>();
+            let mut chars = vec![false; pline.chars.len()];
+            for m in re.find_iter(&text).filter(|m| !m.is_empty()) {
+                let start = text[..m.start()].chars().count();
+                let len = m.as_str().chars().count();
+                chars[start..start + len].fill(true);
+                count += 1;
+            }
+            matched.push(chars);
         }
 
-        let mode = self.display_mode;
-        let first_line = self.first_line;
-        let Some(line_id) = self.lines.prev_line(SearchType::Tag, first_line,
-            &self.patterns, mode, false) else
-        {
-            return false;
-        };
-        self.first_line = line_id;
-        self.lines.set_current_line(self.first_line);
-
-        let pline = self.get_line(line_id).unwrap();
-        let linelen = pline.chars.len() as u16;
-        if linelen > self.area_width {
-            self.line_offset = (linelen - self.area_width) as usize /
-                (self.area_width as usize - self.indent_chars as usize) + 1;
-        }
-        return true;
+        Some((matched, count))
     }
 
-    fn start_of_line(&mut self) -> bool {
-        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
-            return false;
-        };
-        let (x, y) = self.cursor_from_pos_ix(0, line_ix, self.area_width);
-        self.cursor_x = x as i16;
-        self.cursor_y = y as i16;
+    fn handle_log_level_event_after_layout(&mut self, key_event: &KeyEvent) -> bool {
+        lD3!(MA, "log level event: {:?}", key_event);
+
+        match key_event.code {
+            KeyCode::Char(c) => self.log_spec.push(c),
+            KeyCode::Backspace => {
+                if self.log_spec.pop().is_none() {
+                    self.focus = Focus::Main;
+                }
+            }
+            KeyCode::Enter => {
+                self.focus = Focus::Main;
+                let spec = std::mem::take(&mut self.log_spec);
+                self.status_message = Some(match CLog::set_mod_level(vec![spec.as_str()]) {
+                    Ok(_) => {
+                        lD1!(MA, "log levels changed at runtime: {}", spec);
+                        format!("log levels set: {}", spec)
+                    }
+                    Err(e) => format!("invalid log levels {}: {}", spec, e),
+                });
+            }
+            _ => return false,
+        }
 
         false
     }
 
-    fn end_of_line(&mut self) -> bool {
-        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
-            return false;
-        };
-        let (x, y) = self.cursor_from_pos_ix(self.plines[line_ix].chars.len() - 1,
-            line_ix, self.area_width);
-        self.cursor_x = x as i16;
-        self.cursor_y = y as i16;
+    fn handle_command_event_after_layout(&mut self, key_event: &KeyEvent) -> bool {
+        match key_event.code {
+            KeyCode::Char(c) => self.command.push(c),
+            KeyCode::Backspace if self.command.is_empty() => self.focus = Focus::Main,
+            KeyCode::Backspace => {
+                self.command.pop();
+            }
+            KeyCode::Esc => {
+                self.focus = Focus::Main;
+                self.command.clear();
+            }
+            KeyCode::Enter => {
+                self.focus = Focus::Main;
+                let command = std::mem::take(&mut self.command);
+                return self.run_command(&command);
+            }
+            _ => {}
+        }
 
         false
     }
 
-    fn fold_line(&mut self) -> bool {
-        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
-            return false;
-        };
-        let line_id = self.plines[line_ix].line_id;
-        if self.overlong_fold.get(&line_id).is_some() {
-            self.overlong_fold.remove(&line_id);
-        } else {
-            let parts = self.line_parts(&self.plines[line_ix], self.area_width) as usize;
-            self.overlong_fold.insert(line_id, (parts.min(self.area_height as usize / 2), 0));
-        }
+    fn command(&mut self) -> bool {
+        self.focus = Focus::Command;
+        self.command.clear();
 
-        true
+        false
     }
 
-    fn fold_more_less(&mut self, more: bool) -> bool {
-        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
-            return false;
-        };
-        let line_id = self.plines[line_ix].line_id;
-        let full_line = self.lines.get(line_id, &self.patterns, None).unwrap();
-        let parts = self.line_parts(&full_line, self.area_width) as usize;
-        if let Some((lines, _)) = self.overlong_fold.get_mut(&line_id) {
-            if more && *lines < parts {
-                *lines += 1;
-            } else if !more && *lines > 2 {
-                *lines -= 1;
+    fn run_command(&mut self, command: &str) -> bool {
+        lD1!(MA, "command: {}", command);
+        let words = command.split_whitespace().collect::<Vec<_>>();
+        let unknown = match words[..] {
+            [] => return false,
+            ["q"] => return self.exit(),
+            [w @ ("w" | "w!"), path] => {
+                let path = OsStr::new(path);
+                return self.export_path(ExportFormat::from_path(path), path, w == "w!");
             }
-            return true;
-        }
+            ["nohl"] => return self.clear_search(),
+            ["clear"] => return self.clear_all(),
+            ["e"] => return self.reload(),
+            ["set", "wrap"] => return !self.wrap && self.toggle_wrap(),
+            ["set", "nowrap"] => return self.wrap && self.toggle_wrap(),
+            ["set", "center"] => return !self.center_match && self.toggle_center_match(),
+            ["set", "nocenter"] => return self.center_match && self.toggle_center_match(),
+            ["set", setting] => match setting.strip_prefix("indent=").map(str::parse) {
+                Some(Ok(indent)) => {
+                    self.indent_chars = indent;
+                    return true;
+                }
+                Some(Err(_)) => format!("invalid indent: {}", setting),
+                None => format!("unknown setting: {}", setting),
+            },
+            [number] => match number.parse() {
+                Ok(number) => return self.move_to_line_number(number),
+                Err(_) => format!("unknown command: {}", command),
+            },
+            _ => format!("unknown command: {}", command),
+        };
+        self.status_message = Some(unknown);
 
         false
     }
 
-    fn scroll_fold_up_down(&mut self, cnt: usize, direction: Direction) -> bool {
-        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
-            return false;
-        };
-        let line_id = self.plines[line_ix].line_id;
-        let full_line = self.lines.get(line_id, &self.patterns, None).unwrap();
-        let parts = self.line_parts(&full_line, self.area_width) as usize;
-        if parts == 1 {
+    // without a log file, the log would end up on the screen
+    fn log_level(&mut self) -> bool {
+        if !self.log_output {
+            self.status_message = Some("no log file, start with --output".to_string());
             return false;
         }
-        if let Some((lines, first)) = self.overlong_fold.get_mut(&line_id) {
-            if direction == Direction::Forward {
-                for _ in 0..cnt {
-                    if *first + *lines >= parts {
-                        break;
-                    }
-                    *first += 1;
-                }
-            } else {
-                *first = first.saturating_sub(cnt);
-            }
-            return true;
-        }
+        self.focus = Focus::LogLevel;
+        self.log_spec.clear();
 
         false
     }
 
-    fn set_indent(&mut self) -> bool {
-        self.indent_chars = self.cursor_x as u16;
-        self.indent = vec![" "; self.indent_chars as usize].join("");
+    fn show_overlay(&mut self, overlay: Overlay) {
+        self.overlay = Some(overlay);
+        self.overlay_first_line = 0;
+        self.focus = Focus::Overlay;
+    }
 
-        true
+    // a jump sideways, j/k then keep to the new column
+    fn set_cursor_x(&mut self, x: i16) {
+        self.cursor_x = x;
+        self.desired_x = None;
     }
 
-    // return : None if cursor is not on a line
-    // return : Some(None, index, part) if cursor is on the whitespace part of the line
-    // return : Some(Some(position), index, part) if cursor is on the text part of the line
-    fn resolve_cursor_position(&self) -> Option<(Option<usize>, usize, usize)> {
-        let Some(index) = self.line_indexes.get(self.cursor_y as usize) else {
-            return None;
+    fn move_cursor(&mut self, dx: i16, mut dy: i16) -> bool {
+        if dx == 0 && dy != 0 {
+            // back to the column of before a short row, unless the cursor was moved sideways
+            let want = match self.desired_x {
+                Some((want, shown)) if shown == self.cursor_x => want,
+                _ => self.cursor_x,
+            };
+            self.desired_x = Some((want, want));
+            self.cursor_x = want;
+        } else if dx != 0 {
+            self.desired_x = None;
+        }
+        let x = self.cursor_x + dx;
+        let width = self.area_width as i16;
+        let mut moved = false;
+        // truncated lines scroll sideways when the cursor would leave the screen, to the
+        // right only as long as there is more to see
+        if !self.wrap && x >= width && self.plines.iter().any(|l| l.cropped) {
+            self.horizontal_offset += (x - width + 1) as usize;
+            moved = true;
+        } else if !self.wrap && x < 0 && self.horizontal_offset > 0 {
+            self.horizontal_offset = self.horizontal_offset.saturating_sub(-x as usize);
+            moved = true;
+        }
+        self.cursor_x = x.max(0).min(width - 1);
+        let mut cursor_y = self.cursor_y;
+        let area_height = self.area_height as i16;
+        // the rows after the end of the file are not for the cursor. Before the first
+        // render there are no rows to know about
+        let last_row = match self.line_indexes.len() {
+            0 => area_height - 1,
+            len => (len as i16 - 1).min(area_height - 1),
         };
-        lD5!(MA, "cursor_y {} index {:?}", self.cursor_y, index);
-        let pos = if index.line_part > 0 {
-            if self.cursor_x < self.indent_chars as i16 {
-                lD5!(MA, "returns None, line_ix: {} line_part: {}", index.line_ix, index.line_part);
-                return Some((None, index.line_ix, index.line_part));
+
+        while dy > 0 {
+            if cursor_y == area_height as i16 - 1 {
+                moved |= self.scroll_down();
+            } else if cursor_y < last_row {
+                cursor_y += 1;
             }
-            index.char_index + self.cursor_x as usize - self.indent_chars as usize
-        } else {
-            index.char_index + self.cursor_x as usize
-        };
-        if pos >= self.plines[index.line_ix].chars.len() {
-            lD5!(MA, "returns None, line_ix: {} line_part: {}", index.line_ix, index.line_part);
-            return Some((None, index.line_ix, index.line_part));
+            dy -= 1;
+        }
+        while dy < 0 {
+            if cursor_y == 0 {
+                moved |= self.scroll_up();
+            } else {
+                cursor_y -= 1;
+            }
+            dy += 1;
         }
+        self.cursor_y = cursor_y;
 
-        lD5!(MA, "returns Some({}), line_ix: {} line_part: {}", pos, index.line_ix,
-            index.line_part);
-        Some((Some(pos), index.line_ix, index.line_part))
+        moved
     }
 
-    fn cursor_from_pos_ix(&self, pos: usize, ix: usize, width: u16) -> (u16, u16) {
-        // find new y position
-        lD5!(MA, "cursor_from_pos_ix: pos: {} ix: {} width: {}", pos, ix, width);
-        let mut y = 0;
-        for i in 0..ix {
-            let mut parts = self.line_parts(&self.plines[i], width);
-            if i == 0 {
-                parts -= self.line_offset;
-            }
-            y += parts;
-            lD5!(MA, "line {} has {} parts new y {}", i, parts, y);
+    // rows with the column unlocked, whole lines with it locked
+    fn move_vertical(&mut self, dy: i16) -> bool {
+        if !self.column_lock {
+            return self.move_cursor(0, dy);
         }
-        let mut chars_per_line = width as usize;
-        let mut pos = pos;
-        let mut x_off = 0;
-        while pos >= chars_per_line {
-            pos -= chars_per_line;
-            y += 1;
-            chars_per_line = width as usize - self.indent_chars as usize;
-            x_off = self.indent_chars as usize;
+        let Some((_, line_ix, line_part)) = self.resolve_cursor_position() else {
+            return self.move_cursor(0, dy);
+        };
+        // rows to the first row of the target line
+        let mut line_id = self.plines[line_ix].line_id;
+        let mut rows = -(line_part as i16);
+        for _ in 0..dy.unsigned_abs() {
+            let next = if dy > 0 {
+                self.next_shown(line_id)
+            } else {
+                self.prev_shown(line_id)
+            };
+            let Some(next) = next else {
+                break;
+            };
+            let parts = |id| self.get_line(id).map_or(1, |pline| {
+                self.line_parts(&pline, self.area_width)
+            }) as i16;
+            if dy > 0 {
+                rows = rows.saturating_add(parts(line_id));
+            } else {
+                rows = rows.saturating_sub(parts(next));
+            }
+            line_id = next;
         }
+        let moved = self.move_cursor(0, rows);
+        // the locked column is kept, also past the end of a line
+        self.desired_x = None;
 
-        lD5!(MA, "returns x: {} y: {}", pos + x_off, y);
-        ((pos + x_off) as u16, y as u16)
+        moved
     }
 
-    fn cursor_from_pos_len(&self, pos: usize, width: u16) -> (u16, u16) {
-        if pos < width as usize {
-            return (pos as u16, 0);
+    // widen or narrow the lines shown around tagged lines in tagged mode
+    fn change_context(&mut self, delta: isize) -> bool {
+        let context = self.lines.context().saturating_add_signed(delta).min(MAX_CONTEXT);
+        let anchor = self.cursor_anchor();
+        self.lines.set_context(context);
+        self.status_message = Some(match self.display_mode {
+            DisplayMode::Tagged => format!("context {} lines", context),
+            _ => format!("context {} lines, shown in tagged mode", context),
+        });
+        if let Some((line_id, line_part)) = anchor {
+            self.anchor_cursor(line_id, line_part);
         }
-        let y = (pos - width as usize) / (width - self.indent_chars) as usize;
-        let x = (pos - width as usize) % (width - self.indent_chars) as usize;
 
-        return (x as u16 + self.indent_chars, (y + 1) as u16);
+        true
     }
 
-    fn line_parts(&self, pline: &ProcessedLine, width: u16) -> usize {
-        if pline.chars.len() <= width as usize {
-            1
-        } else {
-            (pline.chars.len() - width as usize - 1) /
-                ((width - self.indent_chars) as usize) + 2
+    fn toggle_column_lock(&mut self) -> bool {
+        self.column_lock = !self.column_lock;
+        self.status_message = Some(match self.column_lock {
+            true => "column lock on, j/k keep the column".to_string(),
+            false => "column lock off".to_string(),
+        });
+
+        false
+    }
+
+    fn move_start(&mut self) -> bool {
+        self.set_cursor_x(0);
+        self.cursor_y = 0;
+        self.first_line = 0;
+        self.line_offset = 0;
+        self.lines.set_current_line(self.first_line);
+        if let Some(id) = self.adjust_to_unfiltered_line(0) {
+            self.first_line = id;
         }
+
+        true
     }
 
-    fn tag_hide(&mut self, all: bool, patmode: PatternMode) -> bool {
-        let Some((pos, line_ix, line_part)) = self.resolve_cursor_position() else {
-            return false;
-        };
+    fn move_end(&mut self) -> bool {
+        let mut last_line_id = self.lines.last_line_id();
+        self.lines.set_current_line(self.first_line);
+        if let Some(id) = self.adjust_to_unfiltered_line(last_line_id) {
+            last_line_id = id;
+        }
 
-        let line = &self.plines[line_ix];
-        let line_id = line.line_id;
+        self.set_cursor_x(0);
+        self.cursor_y = self.area_height as i16 - 1;
 
-        if all {
-            if let Some(pos) = pos {
-                if let Some(ref matches) = line.chars[pos].matches {
-                    if matches.len() != 1 {
-                        // don't do anything on more that one match, it might be confusing
-                        self.status_message = Some("ambiguous selection".to_string());
-                        return false;
-                    }
-                    let (id, _) = matches[0];
-                    let mode = self.patterns.get(id).mode;
-                    self.undo_push_pattern(mode);
-                    let (new_mode, new_variant) = if mode == PatternMode::Marking {
-                        if patmode == PatternMode::Tagging {
-                            (PatternMode::Tagging, MarkType::Tag)
-                        } else {
-                            (PatternMode::Hiding, MarkType::Hide)
-                        }
-                    } else if mode == patmode {
-                        (PatternMode::Marking, MarkType::Mark)
-                    } else if mode == PatternMode::Search {
-                        // convert search to tag
-                        self.last_search = None;
-                        (PatternMode::Tagging, MarkType::Tag)
-                    } else {
-                        return false;
-                    };
+        lD3!(MA, "move_end: last_line_id: {}", last_line_id);
+        self.first_line = last_line_id;
 
-                    lD1!(MA, "mark/hide: set pattern {} tagging to {:?}", id, new_mode);
-                    let mode = self.patterns.get(id).mode;
-                    self.patterns.with(id, |p| {
-                        p.mode = new_mode;
-                        p.style.variant = new_variant;
-                    });
-                    self.update_patterns(mode);
-                    self.update_patterns(new_mode);
+        let pline = self.get_line(last_line_id).unwrap();
+        let parts = self.line_parts(&pline, self.area_width) as usize;
+        self.move_line_under_cursor(last_line_id, parts - 1);
 
-                    self.move_line_under_cursor(line_id, line_part);
+        true
+    }
 
-                    return true;
+    // where a word motion starts: the char under the cursor, the last one when past the end
+    // of the line or the first one of the row when in the indent
+    fn word_motion_start(&self, direction: Direction) -> Option<(usize, usize)> {
+        let (pos, line_ix, line_part) = self.resolve_cursor_position()?;
+        let pline = &self.plines[line_ix];
+        let pos = if let Some(pos) = pos {
+            pos
+        } else {
+            let parts = self.line_parts(pline, self.area_width) as usize;
+            // set pos to non-indent part of the line
+            let indent = self.wrap_indent(self.area_width);
+            if line_part == parts - 1 && self.cursor_x >= indent as i16 {
+                if direction == Direction::Forward {
+                    return None;
                 }
+                pline.chars.len() - 1
+            } else {
+                assert!(self.cursor_x < indent as i16);
+                self.hidden_prefix(pline) + self.area_width as usize +
+                    (line_part - 1) * (self.area_width as usize - indent as usize)
             }
-        }
+        };
+        lD5!(MA, "word motion: pos: {} line_ix: {} line_part: {}", pos, line_ix, line_part);
 
-        lD3!(MA, "tag: line_ix: {} pos: {:?} id {}", line_ix, pos, line.line_id);
-        lD5!(MA, "line_indexes: {:?}", self.line_indexes);
-        let line_id = line.line_id;
-        match patmode {
-            PatternMode::Tagging => self.lines.toggle_tag(line_id),
-            PatternMode::Hiding => self.lines.toggle_hide(line_id),
-            _ => panic!("unexpected pattern mode {:?}", patmode),
+        Some((pos, line_ix))
+    }
+
+    fn move_word(&mut self, match_type: MatchType, direction: Direction) -> bool {
+        let Some((mut pos, line_ix)) = self.word_motion_start(direction) else {
+            return false;
+        };
+        let pline = &self.plines[line_ix];
+        let linelen = pline.chars.len();
+        // don't wander into the part of the line that isn't displayed
+        let start = self.hidden_prefix(pline);
+
+        let deliminator = self.patterns.delimiter(match_type);
+        let is_delimiter = |i: usize| deliminator.contains(pline.chars[i].c);
+
+        if direction == Direction::Forward {
+            pos = run_end(linelen, pos, is_delimiter).min(linelen - 1);
+        } else {
+            if pos <= start {
+                return false;
+            }
+            pos = run_start(start, pos, is_delimiter);
         }
-        self.undo_stack.push(Undo::TagHide((line_id, patmode)));
+        lD5!(MA, "move_word: new pos: {}", pos);
 
-        self.move_line_under_cursor(line_id, line_part);
+        let (x, y) = self.cursor_from_pos_ix(pos, line_ix, self.area_width);
+        self.set_cursor_x(x as i16);
+        self.cursor_y = y as i16;
 
-        true
+        false
     }
 
-    fn undo(&mut self) -> bool {
-        let Some(undo) = self.undo_stack.pop() else {
-            lD3!(MA, "undo stack empty");
+    // to the last char of the current or next word, or of the previous word, like e/ge in vi
+    fn move_word_end(&mut self, match_type: MatchType, direction: Direction) -> bool {
+        let Some((pos, line_ix)) = self.word_motion_start(direction) else {
             return false;
         };
+        let pline = &self.plines[line_ix];
+        let linelen = pline.chars.len();
+        let start = self.hidden_prefix(pline);
+        let deliminator = self.patterns.delimiter(match_type);
+        let in_word = |pos: usize| !deliminator.contains(pline.chars[pos].c);
 
-        match undo {
-            Undo::Pattern((mode, p)) => {
-                lD3!(MA, "undo pattern: {:?}", p);
-                self.patterns = p;
-                self.update_patterns(mode);
+        let mut pos = pos;
+        if direction == Direction::Forward {
+            pos += 1;
+            while pos < linelen && !in_word(pos) {
+                pos += 1;
             }
-            Undo::TagHide((line_id, mode)) => {
-                lD3!(MA, "undo tag/hide: line_id: {} mode: {:?}", line_id, mode);
-                match mode {
-                    PatternMode::Tagging => self.lines.toggle_tag(line_id),
-                    PatternMode::Hiding => self.lines.toggle_hide(line_id),
-                    _ => panic!("unexpected pattern mode {:?}", mode),
-                }
+            if pos >= linelen {
+                return false;
+            }
+            while pos + 1 < linelen && in_word(pos + 1) {
+                pos += 1;
+            }
+        } else {
+            // out of the current word, then over the delimiters before it
+            while pos > start && in_word(pos) {
+                pos -= 1;
+            }
+            if in_word(pos) {
+                return false;
+            }
+            while pos > start && !in_word(pos) {
+                pos -= 1;
+            }
+            if !in_word(pos) {
+                return false;
             }
         }
+        lD5!(MA, "move_word_end: new pos: {}", pos);
 
-        true
+        let (x, y) = self.cursor_from_pos_ix(pos, line_ix, self.area_width);
+        self.set_cursor_x(x as i16);
+        self.cursor_y = y as i16;
+
+        false
     }
 
-    fn redo(&mut self) -> bool {
+    fn move_words(&mut self, match_type: MatchType, direction: Direction, count: usize) -> bool {
+        if self.word_end {
+            return self.move_words_end(match_type, direction, count);
+        }
+        for _ in 0..count {
+            self.move_word(match_type, direction);
+        }
+
         false
     }
 
-    fn mark(&mut self, match_type: MatchType) -> bool {
-        let Some((pos, line_ix, line_part)) = self.resolve_cursor_position() else {
-            return false;
-        };
-        let Some(mut pos) = pos else {
-            return false;
-        };
-        lD1!(MA, "mark: line: {} pos: {} char: {}", line_ix, pos,
-            self.plines[line_ix].chars[pos].c);
-        if let Some(ref matches) = self.plines[line_ix].chars[pos].matches {
-            let matches = matches.clone();
-            // only act on last match
-            if let Some(&(id, _)) = matches.last() {
-                // convert search to mark
-                if self.patterns.get(id).mode == PatternMode::Search {
-                    self.undo_push_pattern(PatternMode::Search);
-                    // give it a new color
-                    let match_index = self.mark_style.index;
-                    self.mark_style.cycle_forward();
-                    self.patterns.with(id, |p| {
-                        p.mode = PatternMode::Marking;
-                        p.style.variant = MarkType::Mark;
-                        p.style.index = match_index;
-                    });
-                    self.update_patterns(PatternMode::Search);
-                    if Some(id) == self.last_search {
-                        self.last_search = None;
-                    }
-                    return true;
-                }
-                self.undo_push_pattern(self.patterns.get(id).mode);
-                self.remove_pattern(id);
-                let line_id = self.plines[line_ix].line_id;
-                self.move_line_under_cursor(line_id, line_part);
+    fn move_words_end(&mut self, match_type: MatchType, direction: Direction, count: usize)
+        -> bool
+    {
+        for _ in 0..count {
+            self.move_word_end(match_type, direction);
+        }
 
-                return true;
-            }
+        false
+    }
 
+    // like G in vi, with the number in the file, not on the screen
+    fn move_to_line_number(&mut self, number: u64) -> bool {
+        let Some(mut line_id) = self.lines.line_id_of_number(number, &self.patterns) else {
+            self.status_message = Some(format!("no line {}", number));
             return false;
+        };
+        self.lines.set_current_line(line_id);
+        if let Some(id) = self.adjust_to_unfiltered_line(line_id) {
+            line_id = id;
         }
+        self.set_cursor_x(0);
+        self.move_line_under_cursor(line_id, 0);
 
-        let deliminator = match_type.delimiter();
+        true
+    }
 
-        let line = &self.plines[line_ix];
-        if deliminator.contains(line.chars[pos].c) {
+    // move to the next line that doesn't fit on one row
+    fn move_long_line(&mut self, direction: Direction) -> bool {
+        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
             return false;
-        }
-        while pos > 0 && !deliminator.contains(line.chars[pos - 1].c) {
-            pos -= 1;
-        }
-        let mut pattern = String::new();
-        for i in pos..line.chars.len() {
-            if deliminator.contains(line.chars[i].c) {
+        };
+        let width = self.area_width as usize;
+        let mut line_id = self.plines[line_ix].line_id;
+        for scanned in 0.. {
+            if scanned == MAX_LONG_LINE_SCAN {
+                self.status_message = Some(format!("no long line in the next {} lines, again \
+                    to look further", MAX_LONG_LINE_SCAN));
+                break;
+            }
+            let next = if direction == Direction::Forward {
+                self.lines.next_line(SearchType::Tag, line_id, &self.patterns,
+                    self.display_mode, false)
+            } else {
+                self.lines.prev_line(SearchType::Tag, line_id, &self.patterns,
+                    self.display_mode, false)
+            };
+            let Some(next) = next else {
+                self.status_message = Some("No long lines".to_string());
+                return false;
+            };
+            // one more char than fits is enough to know the line is long
+            let crop = Some(self.strip_chars + width + 1);
+            let Some(pline) = self.lines.get(next, &self.patterns, crop) else {
+                self.status_message = Some(format!("line {} can't be read", next));
+                return false;
+            };
+            line_id = next;
+            if pline.chars.len() - self.hidden_prefix(&pline) > width {
                 break;
             }
-            pattern.push(line.chars[i].c);
         }
-
-        lD1!(MA, "mark: pattern: {}", pattern);
-        let style = self.mark_style.get(MarkType::Mark);
-        self.mark_style.cycle_forward();
-        self.undo_push_pattern(PatternMode::Marking);
-        self.add_pattern(&pattern, match_type, style, PatternMode::Marking);
+        lD2!(MA, "move_long_line: to {}", line_id);
+        self.set_cursor_x(0);
+        self.move_line_under_cursor(line_id, 0);
 
         true
     }
 
-    fn cycle_color(&mut self, direction: Direction) -> bool {
-        let Some((pos, line_ix, _)) = self.resolve_cursor_position() else {
-            return false;
-        };
-        let Some(pos) = pos else {
+    // move to the next/previous tagged line without leaving the display mode. Tagged lines
+    // that aren't shown in it are skipped
+    fn move_tagged(&mut self, direction: Direction) -> bool {
+        let Some((mut line_id, _)) = self.cursor_anchor() else {
             return false;
         };
-        let line = &self.plines[line_ix];
-        lD1!(MA, "mark: line: {} pos: {} char: {}", line_ix, pos, line.chars[pos].c);
-        if let Some(ref matches) = line.chars[pos].matches {
-            if matches.len() != 1 {
-                // we don't know what to do when the char has multiple matches
+        loop {
+            // tagged mode also shows search results and context, they are passed over
+            let next = if direction == Direction::Forward {
+                self.lines.next_line(SearchType::Tag, line_id, &self.patterns,
+                    DisplayMode::Tagged, false)
+            } else {
+                self.lines.prev_line(SearchType::Tag, line_id, &self.patterns,
+                    DisplayMode::Tagged, false)
+            };
+            let Some(next) = next else {
+                self.status_message = Some("no more tagged lines".to_string());
                 return false;
+            };
+            line_id = next;
+            if self.lines.is_tagged_line(line_id, &self.patterns) &&
+                self.lines.is_filtered_line(line_id, self.display_mode, &self.patterns)
+                    == Some(false)
+            {
+                break;
             }
-            let (id, _) = matches[0];
-            self.patterns.with(id, |p| {
-                if direction == Direction::Forward {
-                    p.style.cycle_forward();
-                } else {
-                    p.style.cycle_backward();
-                }
-            });
-
-            return true;
         }
+        lD2!(MA, "move_tagged: found {}", line_id);
+        self.set_cursor_x(0);
+        self.move_line_under_cursor(line_id, 0);
 
-        return false;
+        true
     }
 
-    fn mark_extend(&mut self, extend: bool, direction: Direction) -> bool {
-        let Some((pos, line_ix, _)) = self.resolve_cursor_position() else {
+    // move to the first line of the next/previous split
+    fn move_split(&mut self, direction: Direction) -> bool {
+        let Some((line_id, _)) = self.cursor_anchor() else {
             return false;
         };
-        let pline = &self.plines[line_ix];
-        let Some(mut pos) = pos else {
+        let Some(split_id) = self.lines.find_split(line_id) else {
             return false;
         };
-        lD1!(MA, "line: {} pos: {} char: {}", line_ix, pos, pline.chars[pos].c);
-        if let Some(ref matches) = pline.chars[pos].matches {
-            if matches.len() != 1 {
-                // we don't know what to do when the char has multiple matches
-                return false;
-            }
-            let idm = matches[0];
-            let (id, _) = idm;
-            loop {
-                if extend {
-                    if pline.chars[pos].matches.is_some() &&
-                       pline.chars[pos].matches.as_ref().unwrap().contains(&idm)
-                    {
-                        if direction == Direction::Forward {
-                            if pos == pline.chars.len() - 1 {
-                                break;
-                            }
-                            pos += 1;
-                        } else {
-                            if pos == 0 {
-                                break;
-                            }
-                            pos -= 1;
-                        }
-                        continue;
-                    }
-                    // extend the match
-                    let c = pline.chars[pos].c;
-                    self.patterns.with(id, |p| {
-                        if direction == Direction::Forward {
-                            p.pattern.push(c);
-                        } else {
-                            p.pattern.insert(0, c);
-                        }
-                        p.match_type = MatchType::Text;
-                        lD1!(MA, "mark: pattern: {}", p.pattern);
-                    });
-                    break;
-                } else {
-                    self.patterns.with(id, |p| {
-                        if p.pattern.len() > 1 {
-                            if direction == Direction::Forward {
-                                p.pattern.pop();
-                            } else {
-                                p.pattern.remove(0);
-                            }
-                        }
-                        p.match_type = MatchType::Text;
-                        lD1!(MA, "mark: pattern: {}", p.pattern);
-                    });
-                }
-                break;
-            }
-            let mode = self.patterns.get(id).mode;
-            self.update_patterns(mode);
-        } else if extend {
-            let c = pline.chars[pos].c;
-            let style = self.mark_style.get(MarkType::Mark);
-            self.mark_style.cycle_forward();
-            self.add_pattern(&c.to_string(), MatchType::Text, style, PatternMode::Marking);
+        let target = if direction == Direction::Forward {
+            Some(split_id + 1)
+        } else {
+            split_id.checked_sub(1)
+        };
+        let Some((start, _)) = target.and_then(|t| self.lines.get_split(t)) else {
+            self.status_message = Some("no more splits".to_string());
+            return false;
+        };
+        lD2!(MA, "move_split: {} -> {:?} at {}", split_id, target, start);
+        self.set_cursor_x(0);
+        self.cursor_y = 0;
+        self.first_line = start;
+        self.line_offset = 0;
+        self.lines.set_current_line(start);
+        if let Some(id) = self.adjust_to_unfiltered_line(start) {
+            self.first_line = id;
         }
 
-        return true;
+        true
     }
 
-    fn offsets(&mut self) -> bool {
-        let line_id_len = self.lines.last_line_id().to_string().len();
-        self.display_offset_len = line_id_len;
-        self.display_offset = !self.display_offset;
+    // split of the cursor line, its byte range and whether it is cached
+    fn split_info(&self) -> Option<String> {
+        let line_id = self.plines[self.line_indexes.get(self.cursor_y as usize)?.line_ix].line_id;
+        let split_id = self.lines.find_split(line_id)?;
+        let (start, end) = self.lines.get_split(split_id)?;
+        let cached = match self.lines.split_cached(split_id, &self.patterns) {
+            Some(true) => "cached",
+            Some(false) => "stale",
+            None => "not cached",
+        };
 
-        return true;
+        Some(format!("split {}/{} {}..{} {}", split_id, self.lines.num_splits(), start, end,
+            cached))
     }
 
-    fn adjust_to_unfiltered_line(&mut self, line_id: LineId) -> Option<LineId> {
-        lD2!(MA, "filter: current line {} is filtered", line_id);
-        let mut res = self.lines.next_line(SearchType::Tag, line_id, &self.patterns,
-            self.display_mode, true);
-        if res.is_none() {
-            lD2!(MA, "filter: trying next line backwards");
-            res = self.lines.prev_line(SearchType::Tag, line_id, &self.patterns,
-                self.display_mode, true);
-        }
-        let Some(id) = res else {
-            lD2!(MA, "filter: nothing found, staying in normal mode");
-            self.status_message = Some("nothing to display".to_string());
-            self.display_mode = DisplayMode::Normal;
-            return None;
-        };
+    fn exit(&mut self) -> bool {
+        self.exit = true;
+        false
+    }
 
-        Some(id)
+    fn interrupt(&mut self) -> bool {
+        self.interrupted = true;
+        self.exit()
     }
 
-    fn get_line(&self, line_id: LineId) -> Option<ProcessedLine> {
-        let Some(&(lines, mut first)) = self.overlong_fold.get(&line_id) else {
-            return self.lines.get(line_id, &self.patterns, None);
+    // true if the last displayable line of the file is completely on screen
+    fn bottom_visible(&self) -> bool {
+        let Some(last_line_index) = self.line_indexes.last() else {
+            return false;
         };
-        assert!(lines >= 1);
-        let width = self.area_width as usize;
-        let indented = self.area_width as usize - self.indent_chars as usize;
+        let last_pline = &self.plines[last_line_index.line_ix];
+        let last_parts = self.line_parts(last_pline, self.area_width);
+        lD5!(MA, "bottom_visible: last_line_index: {:?} last_parts: {}", last_line_index,
+            last_parts);
+        last_line_index.line_part == last_parts - 1 &&
+            self.next_shown(last_pline.line_id).is_none()
+    }
 
-        let crop_chars = Some(width + (lines + first - 1) * indented);
-        let mut line = self.lines.get(line_id, &self.patterns, crop_chars)?;
-        if first == 0 {
-            return Some(line);
-        }
-        // lines - total number of lines to show, including the first line
-        // first - number of lines to skip after the first line
-        let parts = self.line_parts(&line, self.area_width) as usize;
-        if first + lines > parts {
-            first = parts - lines;
-        }
-        // cut out the first /first/ indented lines
-        let cut_size = first * indented;
-        line.chars.drain(width .. width + cut_size);
+    // runs of hidden lines show as one row, in normal mode only
+    fn collapsing(&self) -> bool {
+        self.collapse_hidden && self.display_mode == DisplayMode::Normal
+    }
 
-        Some(line)
+    fn is_expanded(&self, line_id: LineId) -> bool {
+        self.expanded.iter().any(|&(start, end)| (start..end).contains(&line_id))
     }
 
-    fn move_line_under_cursor(&mut self, line_id: LineId, line_part: usize) {
-        // we want line_id in display line line_ix. find lines backwards to find a suitable
-        // first_line and offset
-        let mut first_line = line_id;
-        let mut lines_to_go_back = self.cursor_y as isize - line_part as isize;
-        lD2!(MA, "move_under_cursor: line_id {} line_part: {} lines_to_go_back: {} first_line {}",
-            line_id, line_part, lines_to_go_back, first_line);
-        lD2!(MA, "cursor_x {} cursor_y: {}", self.cursor_x, self.cursor_y);
-        let mut first_parts = None;
-        while lines_to_go_back > 0 {
-            let Some(prev_line_id) = self.lines.prev_line(SearchType::Tag, first_line,
-                &self.patterns, self.display_mode, false) else
-            {
-                lD2!(MA, "can't get back any further");
-                break;
-            };
-            let pline = self.get_line(prev_line_id).unwrap();
-            first_line = prev_line_id;
-            let parts = self.line_parts(&pline, self.area_width) as isize;
-            lines_to_go_back -= parts;
-            lD2!(MA, "line {} has {} parts, lines_to_go_back {}", prev_line_id, parts,
-                lines_to_go_back);
-            first_parts = Some(parts);
+    // the hidden line stands for the run of hidden lines it starts
+    fn is_collapsed(&self, line_id: LineId) -> bool {
+        self.collapsing() && !self.is_expanded(line_id) &&
+            self.lines.is_filtered_line(line_id, DisplayMode::Normal, &self.patterns) == Some(true)
+    }
+
+    // the line in the row below. A shown line is followed by the next line of the file, be it
+    // visible or the start of a hidden run, a collapsed run by the next visible line
+    fn next_shown(&self, line_id: LineId) -> Option<LineId> {
+        let mode = if self.collapsing() && !self.is_collapsed(line_id) {
+            DisplayMode::All
+        } else {
+            self.display_mode
+        };
+        self.lines.next_line(SearchType::Tag, line_id, &self.patterns, mode, false)
+    }
+
+    // the line in the row above, the first line of a run of hidden lines for a collapsed run
+    fn prev_shown(&self, line_id: LineId) -> Option<LineId> {
+        let prev = self.lines.prev_line(SearchType::Tag, line_id, &self.patterns,
+            self.display_mode, false);
+        if !self.collapsing() {
+            return prev;
         }
-        lD2!(MA, "first_line: {} lines_to_go_back: {} first_parts {:?}", first_line,
-            lines_to_go_back, first_parts);
-        self.first_line = first_line;
-        self.lines.set_current_line(self.first_line);
-        if lines_to_go_back > 0 {
-            self.cursor_y -= lines_to_go_back as i16;
+        let before = self.lines.prev_line(SearchType::Tag, line_id, &self.patterns,
+            DisplayMode::All, false)?;
+        if Some(before) == prev || self.is_expanded(before) {
+            return Some(before);
         }
-        self.line_offset = 0;
-        if let Some(first_parts) = first_parts {
-            if lines_to_go_back < 0 && -lines_to_go_back < first_parts {
-                self.line_offset = -lines_to_go_back as usize;
-                lD2!(MA, "filter: line_offset: {}", self.line_offset);
-            }
+        match prev {
+            Some(id) => self.lines.next_line(SearchType::Tag, id, &self.patterns,
+                DisplayMode::All, false),
+            None => Some(0),
         }
     }
 
-    fn display(&mut self, direction: Direction) -> bool {
-        let old_mode = self.display_mode;
+    // the row standing for a collapsed run, it tells how many lines it hides. It is drawn
+    // often, so it only counts in the split the run starts in and doesn't wait for the rest
+    fn collapsed_line(&self, line_id: LineId) -> ProcessedLine {
+        let (hidden, ends) = self.lines.hidden_run(line_id, &self.patterns).unwrap_or((1, true));
+        let hidden = hidden.max(1);
+        let text = match (hidden, ends) {
+            (1, true) => "--- 1 line hidden ---".to_string(),
+            (n, true) => format!("--- {} lines hidden ---", n),
+            (n, false) => format!("--- {}+ lines hidden ---", n),
+        };
 
-        if direction == Direction::Forward {
-            match self.display_mode {
-                DisplayMode::All => self.display_mode = DisplayMode::Normal,
-                DisplayMode::Normal => self.display_mode = DisplayMode::Tagged,
-                DisplayMode::Tagged => self.display_mode = DisplayMode::Manual,
-                DisplayMode::Manual => return false,
-            }
-        } else {
-            match self.display_mode {
-                DisplayMode::Manual => self.display_mode = DisplayMode::Tagged,
-                DisplayMode::Tagged => self.display_mode = DisplayMode::Normal,
-                DisplayMode::Normal => self.display_mode = DisplayMode::All,
-                DisplayMode::All => return false,
-            }
+        let style = &self.patterns.default_style;
+        ProcessedLine {
+            line_id,
+            chars: text.chars()
+                .map(|c| StyledChar {
+                    c,
+                    raw: Some(c),
+                    matches: None,
+                    style: style.clone(),
+                    ansi: Style::default(),
+                })
+                .collect(),
+            matches: Vec::new(),
+            cropped: false,
+            broad: false,
+            hidden,
         }
+    }
 
-        let line_index = &self.line_indexes[self.cursor_y as usize];
-        let line_ix = line_index.line_ix;
-        let line_part = line_index.line_part;
-        let line_id = self.plines[line_ix].line_id;
-        let y = self.cursor_y;
-        self.before_filter_pos.insert(old_mode as usize, (line_id, line_part, y));
-
-        // move cursor to next unfiltered line
-        let (line_id, line_part) = if let Some(&(id, part, y)) =
-            self.before_filter_pos.get(&(self.display_mode as usize))
-        {
-            // cursor hasn't moved sind last mode change, try to restore the old position
-            self.cursor_y = y as i16;
-            (id, part)
-        } else if let Some(new) = self.adjust_to_unfiltered_line(line_id) {
-            if new != line_id {
-                (new, 0)
-            } else {
-                (line_id, line_part)
-            }
-        } else {
-            // if we can't find a line, stay in previous mode
-            self.display_mode = old_mode;
-            self.undo_stack.pop();
-            return true;
+    fn toggle_collapse(&mut self) -> bool {
+        let Some((line_id, line_part)) = self.cursor_anchor() else {
+            return false;
         };
-
-        self.move_line_under_cursor(line_id, line_part);
+        self.collapse_hidden = !self.collapse_hidden;
+        self.expanded.clear();
+        self.status_message = Some(match self.collapse_hidden {
+            true => "collapsing runs of hidden lines".to_string(),
+            false => "not collapsing hidden lines".to_string(),
+        });
+        self.anchor_cursor(line_id, line_part);
 
         true
     }
 
-    fn search(&mut self, direction: Direction, match_type: MatchType) -> bool {
-        self.focus = Focus::Search;
-        self.current_search = String::new();
-        self.search_direction = direction;
-        self.search_match_type = match_type;
+    // show the lines of the collapsed run under the cursor again
+    fn expand_collapsed(&mut self) -> bool {
+        let Some((line_id, _)) = self.cursor_anchor() else {
+            return false;
+        };
+        if !self.is_collapsed(line_id) {
+            self.status_message = Some("no collapsed lines here".to_string());
+            return false;
+        }
+        let end = self.lines.next_line(SearchType::Tag, line_id, &self.patterns,
+            DisplayMode::Normal, false);
+        self.expanded.push((line_id, end.unwrap_or(LineId::MAX)));
+        self.move_line_under_cursor(line_id, 0);
 
-        false
+        true
     }
 
-    // search string is collected, do the actual search
-    fn do_search(&mut self, search: String) {
-        lD5!(MA, "do_search: search: {}", search);
-        if let Some(id) = self.last_search {
-            self.remove_pattern(id);
-            self.last_search = None;
-        }
-        if search.is_empty() {
-            return;
+    // in follow mode, keep the end of the file on screen unless the user scrolled away
+    fn follow_tail(&mut self) -> bool {
+        if !self.follow || self.follow_paused {
+            return false;
         }
-        // TODO: check if the pattern is valid
-        let style = self.mark_style.get(MarkType::Search);
-        let match_type = self.search_match_type;
-        let id = self.add_pattern(&search, match_type, style, PatternMode::Search);
-        self.last_search = Some(id);
-
-        self.search_cont(Direction::Forward);
+        self.move_end()
     }
 
-    fn match_has_mode(&self, pline: &ProcessedLine, pos: usize, mode: PatternMode) -> bool
-    {
-        if let Some(ref matches) = pline.chars[pos].matches {
-            for &(id, _) in matches {
-                if self.patterns.get(id).mode == mode {
-                    return true;
-                }
+    // pick up lines appended to the file
+    fn check_growth(&mut self) -> bool {
+        match self.lines.grow() {
+            Ok(grown) => grown,
+            Err(e) => {
+                self.status_message = Some(format!("failed to read new lines: {}", e));
+                false
             }
         }
-
-        false
     }
 
-    fn match_get_search_ix(&self, pline: &ProcessedLine, pos: usize) -> Option<usize>
-    {
-        if let Some(ref matches) = pline.chars[pos].matches {
-            for &(id, ix) in matches {
-                if self.patterns.get(id).mode == PatternMode::Search {
-                    return Some(ix);
-                }
+    // read the file again. Appended lines are picked up, tags and hides stay. If the file
+    // was truncated or replaced, it is read from the top and the lines marked in it are gone
+    fn reload(&mut self) -> bool {
+        let filename = self.lines.filename();
+        let end = self.lines.last_line_id() + 1;
+        let same = match std::fs::metadata(&filename) {
+            Ok(meta) if meta.len() < end => Ok(false),
+            Ok(_) => self.fingerprint.as_ref().map_or(Ok(true), |fp| qhash::check(fp, &filename)),
+            Err(e) => Err(e),
+        };
+        let same = match same {
+            Ok(same) => same,
+            Err(e) => {
+                self.status_message = Some(format!("failed to reload: {}", e));
+                return false;
+            }
+        };
+        self.fingerprint = qhash::qhash(&filename).ok();
+        if same {
+            let grown = self.check_growth();
+            if self.status_message.is_none() {
+                self.status_message = Some(match grown {
+                    true => "read new lines".to_string(),
+                    false => "no new lines".to_string(),
+                });
             }
+            return grown;
         }
 
-        None
+        lD1!(MA, "reload: {:?} was truncated or replaced", filename);
+        if let Err(e) = self.lines.reset() {
+            self.status_message = Some(format!("failed to reload: {}", e));
+            return false;
+        }
+        // everything that points at lines of the old file
+        self.bookmarks.clear();
+        self.overlong_fold.clear();
+        self.expanded.clear();
+        self.pinned_line = None;
+        self.before_filter_pos.clear();
+        self.undo_stack.retain(|undo| matches!(undo, Undo::Pattern(_)));
+        self.redo_stack.retain(|undo| matches!(undo, Undo::Pattern(_)));
+        for mode in [PatternMode::Tagging, PatternMode::Hiding, PatternMode::Search] {
+            self.update_patterns(mode);
+        }
+        self.first_line = 0;
+        self.line_offset = 0;
+        self.horizontal_offset = 0;
+        self.set_cursor_x(0);
+        self.cursor_y = 0;
+        self.lines.set_current_line(0);
+        self.status_message =
+            Some("the file was truncated or replaced, reloaded from the top".to_string());
+
+        true
     }
 
-    fn match_has_search_ix(&self, pline: &ProcessedLine, pos: usize, wanted_ix: usize) -> bool {
-        if let Some(ref matches) = pline.chars[pos].matches {
-            for &(_, ix) in matches {
-                if ix == wanted_ix {
-                    return true;
+    // look for the alert pattern in lines appended after end
+    fn check_alerts(&mut self, end: LineId) {
+        let Some(alert) = self.alert else {
+            return;
+        };
+        let mut matches = 0;
+        let mut line_id = self.lines.next_line(SearchType::Tag, end, &self.patterns,
+            DisplayMode::All, false);
+        for _ in 0..MAX_ALERT_LINES {
+            let Some(id) = line_id else {
+                break;
+            };
+            if let Some((_, bytes)) = self.lines.get_raw(id, &self.patterns) {
+                let line = self.patterns.visible(&bytes);
+                if self.patterns.is_match(alert, &String::from_utf8_lossy(&line)) {
+                    matches += 1;
                 }
             }
+            line_id = self.lines.next_line(SearchType::Tag, id, &self.patterns,
+                DisplayMode::All, false);
         }
+        if matches == 0 {
+            return;
+        }
+        lD2!(MA, "alert: {} new matching lines", matches);
+        self.bell |= self.alert_bell;
+        self.alert_flash = Some(std::time::Instant::now());
+        self.status_message = Some(match matches {
+            1 => "alert: a new matching line".to_string(),
+            n => format!("alert: {} new matching lines", n),
+        });
+    }
 
-        false
+    fn toggle_follow(&mut self) -> bool {
+        // a log that is followed might get truncated by rotation, which a mapped file
+        // doesn't survive
+        if !self.follow && self.lines.is_mapped() {
+            self.status_message = Some("a mapped file can't be followed".to_string());
+            return false;
+        }
+        self.follow = !self.follow;
+        self.follow_paused = false;
+        if !self.follow {
+            self.status_message = Some("follow mode off".to_string());
+            return false;
+        }
+        self.status_message = Some("follow mode on".to_string());
+        self.check_growth();
+        self.move_end()
     }
 
-    // return the start position of the search match, if any
-    fn get_search_match_forward(&self, pline: &ProcessedLine, pos: usize, skip_current: bool)
-        -> Option<usize>
+    // a wheel tick scrolls like ^e/^y a few times, the cursor stays on its line if it can.
+    // A click on the text moves the cursor there, a click on the markers toggles the tag of
+    // the line like T
+    fn handle_mouse_event(&mut self, mouse_event: &MouseEvent, log_area: Rect,
+        marker_area: Rect) -> bool
     {
-        let mut pos = pos;
-        if skip_current {
-            let ix = self.match_get_search_ix(pline, pos);
-            if let Some(ix) = ix {
-                while pos < pline.chars.len() {
-                    if !self.match_has_search_ix(pline, pos, ix) {
-                        break;
-                    }
-                    pos += 1;
-                }
+        let position = Position::new(mouse_event.column, mouse_event.row);
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) if log_area.contains(position) => {
+                self.click(position.x - log_area.x, position.y - log_area.y);
+                false
             }
-        };
-        while pos < pline.chars.len() {
-            if self.match_has_mode(pline, pos, PatternMode::Search) {
-                return Some(pos);
+            MouseEventKind::Down(MouseButton::Left) if marker_area.contains(position) => {
+                if !self.click(0, position.y - marker_area.y) {
+                    return false;
+                }
+                self.tag_hide(false, PatternMode::Tagging)
             }
-            pos += 1;
-        }
-        None
-    }
-
-    // return the start position of the search match, if any
-    fn get_search_match_backward(&mut self, pline: &ProcessedLine, pos: usize, skip_current: bool)
-        -> Option<usize>
-    {
-        let mut pos = pos as isize;
-        if skip_current {
-            let ix = self.match_get_search_ix(pline, pos as usize);
-            if let Some(ix) = ix {
-                while pos >= 0 {
-                    if !self.match_has_search_ix(pline, pos as usize, ix) {
-                        break;
+            MouseEventKind::ScrollDown => {
+                for _ in 0..WHEEL_ROWS {
+                    if self.scroll_down() && self.cursor_y > 0 {
+                        self.move_cursor(0, -1);
                     }
-                    pos -= 1;
                 }
+                true
             }
-        };
-        while pos >= 0 {
-            if self.match_has_mode(pline, pos as usize, PatternMode::Search) {
-                // found a match, now find the start of the match
-                let Some(ix) = self.match_get_search_ix(pline, pos as usize) else {
-                    return None;
-                };
-                while pos > 0 && self.match_has_search_ix(pline, pos as usize - 1, ix) {
-                    pos -= 1;
+            MouseEventKind::ScrollUp => {
+                for _ in 0..WHEEL_ROWS {
+                    if self.scroll_up() && self.cursor_y < (self.area_height - 1) as i16 {
+                        self.move_cursor(0, 1);
+                    }
                 }
-                return Some(pos as usize);
+                true
             }
-            pos -= 1;
+            _ => false,
         }
-        None
     }
 
-    fn search_cont(&mut self, direction: Direction) -> bool {
-        let search_dir = self.search_direction;
-        if search_dir == direction {
-            self.search_next()
-        } else {
-            self.search_prev()
-        }
+    // move the cursor to a row and column of the log area. Returns false if there is no line
+    fn click(&mut self, x: u16, y: u16) -> bool {
+        let Some(last) = self.line_indexes.len().checked_sub(1) else {
+            return false;
+        };
+        self.set_cursor_x(x.min(self.area_width.saturating_sub(1)) as i16);
+        self.cursor_y = (y as usize).min(last) as i16;
+
+        y as usize <= last
     }
 
-    fn search_next(&mut self) -> bool {
-        let (pos, ix, part) = match self.resolve_cursor_position() {
-            Some(x) => x,
-            None => (None, 0, 0),
-        };
-        let pos = if let Some(pos) = pos {
-            pos
-        } else if part == 0 {
-            0
-        } else {
-            self.area_width as usize +
-                (part - 1) * (self.area_width as usize - self.indent_chars as usize)
+    fn scroll_down(&mut self) -> bool {
+        lD4!(MA, "scroll_down: self.line_offset: {} indexes {:?}",
+            self.line_offset, self.line_indexes);
+
+        /*
+         * don't scroll down if the bottom line is the last line
+         */
+        if !self.scroll_past_end && self.bottom_visible() {
+            return false;
+        }
+
+        let Some(index1) = self.line_indexes.get(1) else {
+            return false;
         };
-        let line_id = self.plines[ix].line_id;
-        // don't take line from cache, as the matches aren't up-to-date here
-        let pline = self.get_line(line_id).unwrap();
-        lD2!(MA, "search_next: pos: {} ix: {} part: {} line: {}", pos, ix, part, pline.line_id);
-        if let Some(match_pos) = self.get_search_match_forward(&pline, pos, true) {
-            lD2!(MA, "do_search: found match at {}", match_pos);
-            let (x, y) = self.cursor_from_pos_ix(match_pos, ix, self.area_width);
-            self.cursor_x = x as i16;
-            self.cursor_y = y as i16;
+        if index1.line_part > 0 && self.line_offset < index1.line_part {
+            self.line_offset += 1;
             return true;
         }
-        let mut res = self.lines.next_line(SearchType::Search, pline.line_id, &self.patterns,
-            DisplayMode::Normal, false);
-        lD2!(MA, "do_search: next_line: {:?}", res);
-        if res.is_none() {
-            self.lines.set_current_line(0);  // hint for FileSearch
-            res = self.lines.next_line(SearchType::Search, 0, &self.patterns,
-                DisplayMode::Normal, true);
-            lD2!(MA, "do_search: next_line from 0: {:?}", res);
-            if res.is_some() {
-                self.status_message = Some("Search wrapped".to_string());
-            }
-        }
-        let Some(line_id) = res else {
-            lD2!(MA, "do_search: nothing found");
-            self.status_message = Some("No matches".to_string());
+        self.line_offset = 0;
+
+        let Some(next_line_id) = self.next_shown(self.first_line) else {
             return false;
         };
 
-        let pline = self.get_line(line_id).unwrap();
-        lD10!(MA, "current line: {} {:?}", line_id, pline);
-        let match_pos = self.get_search_match_forward(&pline, 0, false).unwrap();
+        self.first_line = next_line_id;
+        self.lines.set_current_line(self.first_line);
+        return true;
+    }
 
-        // if line is on screen, do not scroll
-        let ix = self.line_indexes.iter().position(|x| self.plines[x.line_ix].line_id == line_id);
-        if let Some(ix) = ix {
-            let (x, y) = self.cursor_from_pos_len(match_pos, self.area_width);
-            let y = y + ix as u16;
-            if y < self.area_height {
-                self.cursor_x = x as i16;
-                self.cursor_y = y as i16;
-                return true;
-            }
-        }
+    fn scroll_up(&mut self) -> bool {
+        lD4!(MA, "scroll_up: self.line_offset: {} indexes {:?}",
+            self.line_offset, self.line_indexes);
 
-        lD2!(MA, "do_search: found match at {}", match_pos);
-        let (x, y) = self.cursor_from_pos_len(match_pos, self.area_width);
-        self.cursor_x = x as i16;
-        self.cursor_y = y as i16;
+        if self.line_offset > 0 {
+            self.line_offset -= 1;
+            return true;
+        }
 
+        let Some(line_id) = self.prev_shown(self.first_line) else {
+            return false;
+        };
         self.first_line = line_id;
         self.lines.set_current_line(self.first_line);
-        self.line_offset = 0;
 
-        true
+        // show the last row of the line
+        let pline = self.get_line(line_id).unwrap();
+        self.line_offset = self.line_parts(&pline, self.area_width) - 1;
+        return true;
     }
 
-    fn search_prev(&mut self) -> bool {
-        let (pos, ix, part) = match self.resolve_cursor_position() {
-            Some(x) => x,
-            None => (None, 0, 0),
-        };
-        let pos = if let Some(pos) = pos {
-            pos
-        } else if part == 0 {
-            0
-        } else {
-            self.area_width as usize +
-                (part - 1) * (self.area_width as usize - self.indent_chars as usize)
-        };
-        let line_id = self.plines[ix].line_id;
-        // don't take line from cache, as the matches aren't up-to-date here
-        let pline = self.get_line(line_id).unwrap();
-        lD2!(MA, "search_prev: pos: {} ix: {} part: {} line: {}", pos, ix, part, pline.line_id);
-        if let Some(match_pos) = self.get_search_match_backward(&pline, pos, true) {
-            lD2!(MA, "do_search: found match at {}", match_pos);
-            let (x, y) = self.cursor_from_pos_ix(match_pos, ix, self.area_width);
-            self.cursor_x = x as i16;
-            self.cursor_y = y as i16;
-            return true;
-        }
-        let mut res = self.lines.prev_line(SearchType::Search, pline.line_id, &self.patterns,
-            DisplayMode::Normal, false);
-        lD2!(MA, "do_search: next_line: {:?}", res);
-        if res.is_none() {
-            let last_line_id = self.lines.last_line_id();
-            self.lines.set_current_line(last_line_id); // hint for FileSearch
-            res = self.lines.prev_line(SearchType::Search, last_line_id, &self.patterns,
-                DisplayMode::Normal, true);
-            lD2!(MA, "do_search: next_line from 0: {:?}", res);
-        }
-        let Some(line_id) = res else {
-            lD2!(MA, "do_search: nothing found");
-            self.status_message = Some("No matches".to_string());
+    fn start_of_line(&mut self) -> bool {
+        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
             return false;
         };
+        let (x, y) = self.cursor_from_pos_ix(0, line_ix, self.area_width);
+        self.set_cursor_x(x as i16);
+        self.cursor_y = y as i16;
 
-        let pline = self.get_line(line_id).unwrap();
-        lD10!(MA, "current line: {} {:?}", line_id, pline);
-        let len = pline.chars.len();
-        let match_pos = self.get_search_match_backward(&pline, len - 1, false).unwrap();
-
-        // if line is on screen, do not scroll
-        let ix = self.line_indexes.iter().position(|x| self.plines[x.line_ix].line_id == line_id);
-        if let Some(ix) = ix {
-            let (x, y) = self.cursor_from_pos_len(match_pos, self.area_width);
-            let y = y + ix as u16;
-            if y < self.area_height {
-                self.cursor_x = x as i16;
-                self.cursor_y = y as i16;
-                return true;
-            }
-        }
+        false
+    }
 
-        lD2!(MA, "do_search: found match at {}", match_pos);
-        let (x, y) = self.cursor_from_pos_len(match_pos, self.area_width);
-        self.cursor_x = x as i16;
+    fn end_of_line(&mut self) -> bool {
+        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let (x, y) = self.cursor_from_pos_ix(self.plines[line_ix].chars.len() - 1,
+            line_ix, self.area_width);
+        self.set_cursor_x(x as i16);
         self.cursor_y = y as i16;
 
-        self.first_line = line_id;
-        self.lines.set_current_line(self.first_line);
-        self.line_offset = 0;
-
-        true
+        false
     }
 
-    fn help(&mut self) -> bool {
-        self.focus = Focus::Help;
+    fn fold_line(&mut self) -> bool {
+        if !self.wrap {
+            self.status_message = Some("truncated lines don't fold".to_string());
+            return false;
+        }
+        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let line_id = self.plines[line_ix].line_id;
+        if self.overlong_fold.get(&line_id).is_some() {
+            self.overlong_fold.remove(&line_id);
+        } else {
+            let parts = self.line_parts(&self.plines[line_ix], self.area_width) as usize;
+            self.overlong_fold.insert(line_id, (parts.min(self.area_height as usize / 2), 0));
+        }
+
         true
     }
 
-    fn calculate_layout(&self, area: Rect) -> [Rect; 5] {
-        /*
-         * calculate layout
-         */
-        let [main_area, bottom_area] =
-            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)])
-                .spacing(0)
-                .areas(area);
+    fn fold_more_less(&mut self, more: bool, count: usize) -> bool {
+        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let line_id = self.plines[line_ix].line_id;
+        let full_line = self.lines.get(line_id, &self.patterns, None).unwrap();
+        let parts = self.line_parts(&full_line, self.area_width) as usize;
+        if let Some((lines, _)) = self.overlong_fold.get_mut(&line_id) {
+            if more && *lines < parts {
+                *lines = (*lines + count).min(parts);
+            } else if !more && *lines > 2 {
+                *lines = lines.saturating_sub(count).max(2);
+            }
+            return true;
+        }
 
-        let [input_area, status_area] =
-            Layout::horizontal([Constraint::Fill(1), Constraint::Length(50)])
-                .spacing(0)
-                .areas(bottom_area);
+        false
+    }
 
-        let marker_len = if self.display_offset {
-            2 + self.display_offset_len as usize + 1
-        } else {
-            2
+    fn scroll_fold_up_down(&mut self, cnt: usize, direction: Direction) -> bool {
+        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
+            return false;
         };
-        let [marker_area, log_area] =
-            Layout::horizontal([Constraint::Length(marker_len as u16), Constraint::Fill(1)])
-                .spacing(0)
-                .areas(main_area);
+        let line_id = self.plines[line_ix].line_id;
+        let full_line = self.lines.get(line_id, &self.patterns, None).unwrap();
+        let parts = self.line_parts(&full_line, self.area_width) as usize;
+        if parts == 1 {
+            return false;
+        }
+        if let Some((lines, first)) = self.overlong_fold.get_mut(&line_id) {
+            if direction == Direction::Forward {
+                for _ in 0..cnt {
+                    if *first + *lines >= parts {
+                        break;
+                    }
+                    *first += 1;
+                }
+            } else {
+                *first = first.saturating_sub(cnt);
+            }
+            return true;
+        }
 
-        [main_area, log_area, marker_area, input_area, status_area]
+        false
     }
 
-    fn process_event(&mut self, area: Rect, event: Option<Event>) {
-        let [_, log_area, _, _, _] = self.calculate_layout(area);
+    fn set_indent(&mut self) -> bool {
+        // a screen column, so it lines up with what is displayed of the first row, after
+        // the hidden prefix
+        self.indent_chars = self.cursor_x as u16;
+
+        true
+    }
+
+    // return : None if cursor is not on a line
+    // return : Some(None, index, part) if cursor is on the whitespace part of the line
+    // return : Some(Some(position), index, part) if cursor is on the text part of the line
+    fn resolve_cursor_position(&self) -> Option<(Option<usize>, usize, usize)> {
+        let Some(index) = self.line_indexes.get(self.cursor_y as usize) else {
+            return None;
+        };
+        lD5!(MA, "cursor_y {} index {:?}", self.cursor_y, index);
+        let indent = self.wrap_indent(self.area_width);
+        let pos = if index.line_part > 0 {
+            if self.cursor_x < indent as i16 {
+                lD5!(MA, "returns None, line_ix: {} line_part: {}", index.line_ix, index.line_part);
+                return Some((None, index.line_ix, index.line_part));
+            }
+            index.char_index + self.cursor_x as usize - indent as usize
+        } else {
+            index.char_index + self.cursor_x as usize
+        };
+        if pos >= self.plines[index.line_ix].chars.len() {
+            lD5!(MA, "returns None, line_ix: {} line_part: {}", index.line_ix, index.line_part);
+            return Some((None, index.line_ix, index.line_part));
+        }
+
+        lD5!(MA, "returns Some({}), line_ix: {} line_part: {}", pos, index.line_ix,
+            index.line_part);
+        Some((Some(pos), index.line_ix, index.line_part))
+    }
+
+    // pos is an index into the pline, so tabs are already expanded to their width
+    fn cursor_from_pos_ix(&self, pos: usize, ix: usize, width: u16) -> (u16, u16) {
+        // find new y position
+        lD5!(MA, "cursor_from_pos_ix: pos: {} ix: {} width: {}", pos, ix, width);
+        let mut y = 0;
+        for i in 0..ix {
+            let mut parts = self.line_parts(&self.plines[i], width);
+            if i == 0 {
+                parts -= self.line_offset;
+            }
+            y += parts;
+            lD5!(MA, "line {} has {} parts new y {}", i, parts, y);
+        }
+        let mut chars_per_line = width as usize;
+        // a position in the hidden prefix is shown at the start of the line
+        let mut pos = pos.saturating_sub(self.hidden_prefix(&self.plines[ix]));
+        let mut x_off = 0;
+        while pos >= chars_per_line {
+            pos -= chars_per_line;
+            y += 1;
+            chars_per_line = (width - self.wrap_indent(width)) as usize;
+            x_off = self.wrap_indent(width) as usize;
+        }
+
+        lD5!(MA, "returns x: {} y: {}", pos + x_off, y);
+        ((pos + x_off) as u16, y as u16)
+    }
+
+    fn cursor_from_pos_len(&self, pline: &ProcessedLine, pos: usize, width: u16) -> (u16, u16) {
+        let pos = pos.saturating_sub(self.hidden_prefix(pline));
+        if pos < width as usize {
+            return (pos as u16, 0);
+        }
+        let indent = self.wrap_indent(width);
+        let y = (pos - width as usize) / (width - indent) as usize;
+        let x = (pos - width as usize) % (width - indent) as usize;
+
+        return (x as u16 + indent, (y + 1) as u16);
+    }
+
+    // the indent of wrapped lines, clamped so that some columns remain for the content
+    fn wrap_indent(&self, width: u16) -> u16 {
+        self.indent_chars.min(width.saturating_sub(MIN_WRAP_COLUMNS))
+    }
+
+    // number of chars at the start of the line that are not displayed, stripped or, when
+    // truncating, scrolled off to the left. At least one char is left, so there is something
+    // to put the cursor on
+    fn hidden_prefix(&self, pline: &ProcessedLine) -> usize {
+        if pline.hidden > 0 {
+            return 0;
+        }
+        let hidden = if self.wrap {
+            self.strip_chars
+        } else {
+            self.strip_chars + self.horizontal_offset
+        };
+        hidden.min(pline.chars.len().saturating_sub(1))
+    }
+
+    // shift truncated lines sideways, but not further than the longest line on screen needs
+    // to show its end
+    fn scroll_sideways(&mut self, columns: u16, direction: Direction) -> bool {
+        if self.wrap {
+            self.status_message = Some("wrapped lines don't scroll sideways, v truncates them"
+                .to_string());
+            return false;
+        }
+        let (columns, width) = (columns.max(1) as usize, self.area_width as usize);
+        let offset = match direction {
+            Direction::Forward => {
+                let crop = self.strip_chars + self.horizontal_offset + columns + width;
+                let longest = self.plines.iter()
+                    .filter(|pline| pline.hidden == 0)
+                    .filter_map(|pline| self.lines.get(pline.line_id, &self.patterns, Some(crop)))
+                    .map(|pline| pline.chars.len().saturating_sub(self.strip_chars))
+                    .max()
+                    .unwrap_or(0);
+                (self.horizontal_offset + columns).min(longest.saturating_sub(width))
+                    .max(self.horizontal_offset)
+            }
+            Direction::Backward => self.horizontal_offset.saturating_sub(columns),
+        };
+        lD3!(MA, "scroll_sideways: offset {} -> {}", self.horizontal_offset, offset);
+        if offset == self.horizontal_offset {
+            return false;
+        }
+        self.horizontal_offset = offset;
+
+        true
+    }
+
+    // wrap long lines or truncate them to one row each and scroll sideways
+    fn toggle_wrap(&mut self) -> bool {
+        let anchor = self.cursor_anchor();
+        self.wrap = !self.wrap;
+        self.horizontal_offset = 0;
+        self.set_cursor_x(0);
+        self.status_message = Some(match self.wrap {
+            true => "wrapping long lines".to_string(),
+            false => "truncating long lines, h/l scroll sideways at the edges".to_string(),
+        });
+        if let Some((line_id, _)) = anchor {
+            self.anchor_cursor(line_id, 0);
+        }
+
+        true
+    }
+
+    fn line_parts(&self, pline: &ProcessedLine, width: u16) -> usize {
+        let len = pline.chars.len() - self.hidden_prefix(pline);
+        if !self.wrap || len <= width as usize {
+            1
+        } else {
+            (len - width as usize - 1) / ((width - self.wrap_indent(width)) as usize) + 2
+        }
+    }
+
+    // turn the mark under the cursor into a pattern that hides all lines not matching it,
+    // or back into a mark
+    fn hide_others(&mut self) -> bool {
+        let Some((Some(pos), line_ix, line_part)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let line = &self.plines[line_ix];
+        let line_id = line.line_id;
+        let Some(ref matches) = line.chars[pos].matches else {
+            self.status_message = Some("no pattern under cursor".to_string());
+            return false;
+        };
+        if matches.len() != 1 {
+            self.status_message = Some("ambiguous selection".to_string());
+            return false;
+        }
+        let (id, _) = matches[0];
+        let (mode, negative) = {
+            let p = self.patterns.get(id);
+            (p.mode, p.negative)
+        };
+        let (new_mode, new_variant, new_negative) = match mode {
+            PatternMode::Hiding if negative => (PatternMode::Marking, MarkType::Mark, false),
+            PatternMode::Tagging => return false,
+            _ => (PatternMode::Hiding, MarkType::Hide, true),
+        };
+        if mode == PatternMode::Search {
+            self.last_search = None;
+        }
+
+        lD1!(MA, "hide others: set pattern {} to {:?} negative {}", id, new_mode, new_negative);
+        self.undo_push_pattern(mode);
+        self.patterns.with(id, |p| {
+            p.mode = new_mode;
+            p.style.variant = new_variant;
+            p.negative = new_negative;
+        });
+        self.update_patterns(mode);
+        self.update_patterns(new_mode);
+        self.anchor_cursor(line_id, line_part);
+
+        true
+    }
+
+    // tags or hides always act on the whole logical line, also from a continuation row of
+    // a wrapped line. The marker is shown on its first row
+    fn tag_hide(&mut self, all: bool, patmode: PatternMode) -> bool {
+        let Some((pos, line_ix, line_part)) = self.resolve_cursor_position() else {
+            return false;
+        };
+
+        let line = &self.plines[line_ix];
+        let line_id = line.line_id;
+
+        if all {
+            if let Some(pos) = pos {
+                if let Some(ref matches) = line.chars[pos].matches {
+                    if matches.len() != 1 {
+                        // don't do anything on more that one match, it might be confusing
+                        self.status_message = Some("ambiguous selection".to_string());
+                        return false;
+                    }
+                    let (id, _) = matches[0];
+                    let mode = self.patterns.get(id).mode;
+                    self.undo_push_pattern(mode);
+                    let (new_mode, new_variant) = if mode == PatternMode::Marking {
+                        if patmode == PatternMode::Tagging {
+                            (PatternMode::Tagging, MarkType::Tag)
+                        } else {
+                            (PatternMode::Hiding, MarkType::Hide)
+                        }
+                    } else if mode == patmode {
+                        (PatternMode::Marking, MarkType::Mark)
+                    } else if mode == PatternMode::Search {
+                        // convert search to tag
+                        self.last_search = None;
+                        (PatternMode::Tagging, MarkType::Tag)
+                    } else {
+                        return false;
+                    };
+
+                    lD1!(MA, "mark/hide: set pattern {} tagging to {:?}", id, new_mode);
+                    let mode = self.patterns.get(id).mode;
+                    self.patterns.with(id, |p| {
+                        p.mode = new_mode;
+                        p.style.variant = new_variant;
+                        p.negative = false;
+                    });
+                    self.update_patterns(mode);
+                    self.update_patterns(new_mode);
+
+                    self.anchor_cursor(line_id, line_part);
+
+                    return true;
+                }
+            }
+        }
+
+        lD3!(MA, "tag: line_ix: {} pos: {:?} id {}", line_ix, pos, line.line_id);
+        lD5!(MA, "line_indexes: {:?}", self.line_indexes);
+        let line_id = line.line_id;
+        match patmode {
+            PatternMode::Tagging => self.lines.toggle_tag(line_id),
+            PatternMode::Hiding => self.lines.toggle_hide(line_id),
+            _ => panic!("unexpected pattern mode {:?}", patmode),
+        }
+        self.undo_push(Undo::TagHide((line_id, patmode)));
+
+        self.anchor_cursor(line_id, line_part);
+
+        true
+    }
+
+    // find the pattern with the given mode that is closest to pos on the line
+    fn nearest_pattern(&self, pline: &ProcessedLine, pos: usize, mode: PatternMode)
+        -> Option<PatternId>
+    {
+        pline.chars.iter().enumerate()
+            .filter_map(|(i, sc)| {
+                sc.matches.as_ref()?.iter()
+                    .find(|&&(id, _)| self.patterns.get(id).mode == mode)
+                    .map(|&(id, _)| (i.abs_diff(pos), id))
+            })
+            .min_by_key(|&(dist, _)| dist)
+            .map(|(_, id)| id)
+    }
+
+    // promote the mark under or nearest to the cursor to a tagging pattern
+    fn tag_mark(&mut self) -> bool {
+        let Some((pos, line_ix, line_part)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let pos = pos.unwrap_or(self.line_indexes[self.cursor_y as usize].char_index);
+        let pline = &self.plines[line_ix];
+        let line_id = pline.line_id;
+        let Some(id) = self.nearest_pattern(pline, pos, PatternMode::Marking) else {
+            self.status_message = Some("no mark on this line".to_string());
+            return false;
+        };
+
+        lD1!(MA, "tag_mark: set pattern {} to tagging", id);
+        self.undo_push_pattern(PatternMode::Tagging);
+        self.patterns.with(id, |p| {
+            p.mode = PatternMode::Tagging;
+            p.style.variant = MarkType::Tag;
+        });
+        self.update_patterns(PatternMode::Tagging);
+
+        self.anchor_cursor(line_id, line_part);
+
+        true
+    }
+
+    // the line under the cursor and the row of it the cursor is on
+    fn cursor_anchor(&self) -> Option<(LineId, usize)> {
+        let (_, line_ix, line_part) = self.resolve_cursor_position()?;
+        Some((self.plines[line_ix].line_id, line_part))
+    }
+
+    // keep the line under the cursor in its row after a change that may alter the layout.
+    // If the line got filtered, the nearest visible line takes its place
+    fn anchor_cursor(&mut self, line_id: LineId, line_part: usize) {
+        let mode = self.display_mode;
+        let next = self.lines.next_line(SearchType::Tag, line_id, &self.patterns, mode, true);
+        // with collapsing, a hidden line still has a row, alone or for its run
+        let hidden = self.collapsing() && next != Some(line_id);
+        if next == Some(line_id) || hidden {
+            self.move_line_under_cursor(line_id, line_part);
+            return;
+        }
+        let nearest = next.or_else(|| self.lines.prev_line(SearchType::Tag, line_id,
+            &self.patterns, mode, false));
+        if let Some(id) = nearest {
+            lD2!(MA, "anchor: line {} got filtered, using {}", line_id, id);
+            self.move_line_under_cursor(id, 0);
+        }
+    }
+
+    // a new action invalidates everything that was undone before
+    fn undo_push(&mut self, undo: Undo) {
+        self.undo_stack.push(undo);
+        self.redo_stack.clear();
+    }
+
+    // revert to the state saved in undo, returns what reverts it again
+    fn apply_undo(&mut self, undo: Undo) -> Undo {
+        let anchor = self.cursor_anchor();
+        let reverse = match undo {
+            Undo::Pattern((mode, p)) => {
+                lD3!(MA, "undo pattern: {:?}", p);
+                let current = std::mem::replace(&mut self.patterns, p);
+                self.update_patterns(mode);
+                // a pattern that changed its mode is in a second set
+                for other in [PatternMode::Tagging, PatternMode::Hiding, PatternMode::Search] {
+                    if other != mode && !self.patterns.same_re(&current, other) {
+                        self.update_patterns(other);
+                    }
+                }
+                Undo::Pattern((mode, current))
+            }
+            Undo::TagHide((line_id, mode)) => {
+                lD3!(MA, "undo tag/hide: line_id: {} mode: {:?}", line_id, mode);
+                match mode {
+                    PatternMode::Tagging => self.lines.toggle_tag(line_id),
+                    PatternMode::Hiding => self.lines.toggle_hide(line_id),
+                    _ => panic!("unexpected pattern mode {:?}", mode),
+                }
+                Undo::TagHide((line_id, mode))
+            }
+            Undo::Reset(marks) => {
+                lD3!(MA, "undo reset: {:?}", marks.0);
+                Undo::Reset(self.reset_to(marks))
+            }
+        };
+        if let Some((line_id, line_part)) = anchor {
+            self.anchor_cursor(line_id, line_part);
+        }
+
+        reverse
+    }
+
+    // replace all patterns, manual tags and hides and the last search, returns the old ones
+    fn reset_to(&mut self, marks: Marks) -> Marks {
+        let (patterns, tagged, hidden, last_search) = marks;
+        let old = (
+            std::mem::replace(&mut self.patterns, patterns),
+            self.lines.tagged_lines().clone(),
+            self.lines.hidden_lines().clone(),
+            std::mem::replace(&mut self.last_search, last_search),
+        );
+        self.lines.restore(tagged, hidden);
+        for mode in [PatternMode::Tagging, PatternMode::Hiding, PatternMode::Search] {
+            self.update_patterns(mode);
+        }
+
+        old
+    }
+
+    // forget all marks, tags, hides and the search in one step that can be undone. The
+    // alert pattern stays, display mode and bookmarks, too
+    fn clear_all(&mut self) -> bool {
+        let anchor = self.cursor_anchor();
+        let mut patterns = self.patterns.clone();
+        let ids = patterns.iter_ids()
+            .map(|(id, _)| id)
+            .filter(|&id| Some(id) != self.alert)
+            .collect::<Vec<_>>();
+        for id in ids {
+            patterns.remove(id);
+        }
+        let old = self.reset_to((patterns, BTreeSet::new(), BTreeSet::new(), None));
+        self.undo_push(Undo::Reset(old));
+        if let Some((line_id, line_part)) = anchor {
+            self.anchor_cursor(line_id, line_part);
+        }
+        self.status_message = Some("cleared marks, tags and hides, u to undo".to_string());
+
+        true
+    }
+
+    fn undo(&mut self) -> bool {
+        let Some(undo) = self.undo_stack.pop() else {
+            lD3!(MA, "undo stack empty");
+            self.status_message = Some("nothing to undo".to_string());
+            return false;
+        };
+        let redo = self.apply_undo(undo);
+        self.redo_stack.push(redo);
+
+        true
+    }
+
+    fn redo(&mut self) -> bool {
+        let Some(redo) = self.redo_stack.pop() else {
+            lD3!(MA, "redo stack empty");
+            self.status_message = Some("nothing to redo".to_string());
+            return false;
+        };
+        let undo = self.apply_undo(redo);
+        self.undo_stack.push(undo);
+
+        true
+    }
+
+    fn mark(&mut self, match_type: MatchType) -> bool {
+        let Some((pos, line_ix, line_part)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let Some(pos) = pos else {
+            return false;
+        };
+        lD1!(MA, "mark: line: {} pos: {} char: {}", line_ix, pos,
+            self.plines[line_ix].chars[pos].c);
+        let line_id = self.plines[line_ix].line_id;
+        if let Some(ref matches) = self.plines[line_ix].chars[pos].matches {
+            let matches = matches.clone();
+            // only act on last match
+            if let Some(&(id, _)) = matches.last() {
+                // convert search to mark
+                if self.patterns.get(id).mode == PatternMode::Search {
+                    self.undo_push_pattern(PatternMode::Search);
+                    // give it a new color
+                    let match_index = self.mark_style.index;
+                    self.mark_style.cycle_forward();
+                    self.patterns.with(id, |p| {
+                        p.mode = PatternMode::Marking;
+                        p.style.variant = MarkType::Mark;
+                        p.style.index = match_index;
+                    });
+                    self.update_patterns(PatternMode::Search);
+                    if Some(id) == self.last_search {
+                        self.last_search = None;
+                    }
+                    // search matches are always shown, a mark isn't
+                    self.anchor_cursor(line_id, line_part);
+                    return true;
+                }
+                self.undo_push_pattern(self.patterns.get(id).mode);
+                self.remove_pattern(id);
+                self.anchor_cursor(line_id, line_part);
+
+                return true;
+            }
+
+            return false;
+        }
+
+        // a text mark takes all between whitespace, like id=42 in key=value logs
+        let deliminator = match match_type {
+            MatchType::Text => " \t",
+            _ => self.patterns.delimiter(match_type),
+        };
+
+        let Some(pattern) = self.word_at(line_ix, pos, deliminator) else {
+            return false;
+        };
+        lD1!(MA, "mark: pattern: {}", pattern);
+        let style = self.mark_style.get(MarkType::Mark);
+        self.mark_style.cycle_forward();
+        self.undo_push_pattern(PatternMode::Marking);
+        self.add_pattern(&pattern, match_type, Anchor::default(), false, style,
+            PatternMode::Marking);
+        self.anchor_cursor(line_id, line_part);
+
+        true
+    }
+
+    // the word at pos, between delimiters. None on a delimiter
+    fn word_at(&self, line_ix: usize, pos: usize, deliminator: &str) -> Option<String> {
+        let chars = &self.plines[line_ix].chars;
+        let is_delimiter = |i: usize| deliminator.contains(chars[i].c);
+        if is_delimiter(pos) {
+            return None;
+        }
+        let start = run_start(0, pos + 1, is_delimiter);
+        let end = run_end(chars.len(), pos, is_delimiter);
+
+        Some(source_text(chars, start..end))
+    }
+
+    // where the word is on screen as a whole word, for each char of the lines
+    fn word_occurrences(&self, word: &str) -> Vec<Vec<bool>> {
+        let deliminator = self.patterns.delimiter(MatchType::SmallWord);
+        self.plines.iter().map(|pline| {
+            let len = pline.chars.len();
+            let is_delimiter = |i: usize| deliminator.contains(pline.chars[i].c);
+            let mut found = vec![false; len];
+            let mut pos = 0;
+            while pos < len {
+                let end = run_end(len, pos, is_delimiter);
+                let chars = pline.chars[pos..end].iter().map(|c| c.c);
+                if !is_delimiter(pos) && chars.eq(word.chars()) {
+                    found[pos..end].fill(true);
+                }
+                pos = end;
+            }
+            found
+        }).collect()
+    }
+
+    // unlike a mark, the highlight follows the cursor and is gone with Esc
+    fn toggle_highlight_word(&mut self) -> bool {
+        self.highlight_word = !self.highlight_word;
+        self.status_message = Some(match self.highlight_word {
+            true => "highlighting the word under the cursor, Esc to stop".to_string(),
+            false => "word under the cursor not highlighted".to_string(),
+        });
+
+        true
+    }
+
+    // toggle mark of the WORD in the cursor column, for columns of tabular data. Says so
+    // if the line has nothing there, instead of silently doing nothing like m/M
+    fn mark_field(&mut self) -> bool {
+        let Some((pos, line_ix, _)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let on_field = pos.is_some_and(|pos| {
+            let c = self.plines[line_ix].chars[pos].c;
+            c != '\n' && !self.patterns.delimiter(MatchType::BigWord).contains(c)
+        });
+        if !on_field {
+            self.status_message = Some("no field here".to_string());
+            return false;
+        }
+
+        self.mark(MatchType::BigWord)
+    }
+
+    fn cycle_color(&mut self, direction: Direction) -> bool {
+        let Some((pos, line_ix, _)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let Some(pos) = pos else {
+            return false;
+        };
+        let line = &self.plines[line_ix];
+        lD1!(MA, "mark: line: {} pos: {} char: {}", line_ix, pos, line.chars[pos].c);
+        if let Some(ref matches) = line.chars[pos].matches {
+            if matches.len() != 1 {
+                // we don't know what to do when the char has multiple matches
+                return false;
+            }
+            let (id, _) = matches[0];
+            self.patterns.with(id, |p| {
+                if direction == Direction::Forward {
+                    p.style.cycle_forward();
+                } else {
+                    p.style.cycle_backward();
+                }
+            });
+            self.redo_stack.clear();
+
+            return true;
+        }
+
+        return false;
+    }
+
+    fn mark_extend(&mut self, extend: bool, direction: Direction) -> bool {
+        let Some((pos, line_ix, line_part)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let pline = &self.plines[line_ix];
+        let line_id = pline.line_id;
+        let Some(mut pos) = pos else {
+            return false;
+        };
+        lD1!(MA, "line: {} pos: {} char: {}", line_ix, pos, pline.chars[pos].c);
+        if let Some(ref matches) = pline.chars[pos].matches {
+            if matches.len() != 1 {
+                // we don't know what to do when the char has multiple matches
+                return false;
+            }
+            let idm = matches[0];
+            let (id, _) = idm;
+            loop {
+                if extend {
+                    if pline.chars[pos].matches.is_some() &&
+                       pline.chars[pos].matches.as_ref().unwrap().contains(&idm)
+                    {
+                        if direction == Direction::Forward {
+                            if pos == pline.chars.len() - 1 {
+                                break;
+                            }
+                            pos += 1;
+                        } else {
+                            if pos == 0 {
+                                break;
+                            }
+                            pos -= 1;
+                        }
+                        continue;
+                    }
+                    // extend the match
+                    let c = source_char(&pline.chars, pos);
+                    self.patterns.with(id, |p| {
+                        if direction == Direction::Forward {
+                            p.pattern.push(c);
+                        } else {
+                            p.pattern.insert(0, c);
+                        }
+                        p.match_type = MatchType::Text;
+                        lD1!(MA, "mark: pattern: {}", p.pattern);
+                    });
+                    break;
+                } else {
+                    self.patterns.with(id, |p| {
+                        if p.pattern.len() > 1 {
+                            if direction == Direction::Forward {
+                                p.pattern.pop();
+                            } else {
+                                p.pattern.remove(0);
+                            }
+                        }
+                        p.match_type = MatchType::Text;
+                        lD1!(MA, "mark: pattern: {}", p.pattern);
+                    });
+                }
+                break;
+            }
+            let mode = self.patterns.get(id).mode;
+            self.update_patterns(mode);
+            self.redo_stack.clear();
+        } else if extend {
+            let c = source_char(&pline.chars, pos);
+            let style = self.mark_style.get(MarkType::Mark);
+            self.mark_style.cycle_forward();
+            self.add_pattern(&c.to_string(), MatchType::Text, Anchor::default(), false, style,
+                PatternMode::Marking);
+        }
+        self.anchor_cursor(line_id, line_part);
+
+        return true;
+    }
+
+    // like mark_extend, but by a word or a run of delimiters between words at a time
+    fn mark_extend_word(&mut self, extend: bool, direction: Direction) -> bool {
+        let Some((Some(pos), line_ix, line_part)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let pline = &self.plines[line_ix];
+        let line_id = pline.line_id;
+        let Some(&[idm]) = pline.chars[pos].matches.as_deref() else {
+            self.status_message = Some("no single mark under the cursor".to_string());
+            return false;
+        };
+        let (id, _) = idm;
+        // owned, the patterns are changed while it is used
+        let delimiter = self.patterns.delimiter(MatchType::SmallWord).to_string();
+        if extend {
+            let linelen = pline.chars.len();
+            let in_mark = |i: usize| pline.chars[i].matches.as_ref()
+                .is_some_and(|matches| matches.contains(&idm));
+            let is_delimiter = |i: usize| delimiter.contains(pline.chars[i].c);
+            // the run of chars next to the mark
+            let run = if direction == Direction::Forward {
+                (pos..linelen).find(|&i| !in_mark(i))
+                    .map(|end| end..run_end(linelen, end, is_delimiter))
+            } else {
+                let start = self.hidden_prefix(pline);
+                (start..pos).rev().find(|&i| !in_mark(i))
+                    .map(|end| run_start(start, end + 1, is_delimiter)..end + 1)
+            };
+            let Some(run) = run else {
+                self.status_message = Some("the mark reaches the end of the line".to_string());
+                return false;
+            };
+            let text = source_text(&pline.chars, run);
+            self.patterns.with(id, |p| {
+                if direction == Direction::Forward {
+                    p.pattern.push_str(&text);
+                } else {
+                    p.pattern.insert_str(0, &text);
+                }
+                // with delimiters in it, it is no word anymore
+                p.match_type = MatchType::Text;
+                lD1!(MA, "mark: pattern: {}", p.pattern);
+            });
+        } else {
+            self.patterns.with(id, |p| {
+                let chars = p.pattern.chars().collect::<Vec<_>>();
+                let is_delimiter = |i: usize| delimiter.contains(chars[i]);
+                // the last run stays
+                if direction == Direction::Forward {
+                    let cut = run_start(0, chars.len(), is_delimiter);
+                    if cut > 0 {
+                        p.pattern = chars[..cut].iter().collect();
+                    }
+                } else {
+                    let cut = run_end(chars.len(), 0, is_delimiter);
+                    if cut < chars.len() {
+                        p.pattern = chars[cut..].iter().collect();
+                    }
+                }
+                p.match_type = MatchType::Text;
+                lD1!(MA, "mark: pattern: {}", p.pattern);
+            });
+        }
+        let mode = self.patterns.get(id).mode;
+        self.update_patterns(mode);
+        self.redo_stack.clear();
+        self.anchor_cursor(line_id, line_part);
+
+        true
+    }
+
+    // the mark under the cursor, or else the whole line under it, and what it is
+    fn yank_text(&self) -> Option<(String, &'static str)> {
+        let (pos, line_ix, _) = self.resolve_cursor_position()?;
+        let pline = &self.plines[line_ix];
+        let mark = pos.and_then(|pos| {
+            let (id, _) = pline.chars[pos].matches.as_ref()?.iter()
+                .find(|&&(id, _)| self.patterns.get(id).mode == PatternMode::Marking)?;
+            let marked = |p: &usize| pline.chars[*p].matches.as_ref()
+                .is_some_and(|m| m.iter().any(|(i, _)| i == id));
+            let start = (0..pos).rev().take_while(marked).last().unwrap_or(pos);
+            let end = (pos..pline.chars.len()).take_while(marked).last().unwrap();
+            Some(source_text(&pline.chars, start..end + 1))
+        });
+        if let Some(text) = mark {
+            return Some((text, "mark"));
+        }
+        // the displayed line might be cropped or folded
+        let line = self.lines.get(pline.line_id, &self.patterns, None)?;
+
+        Some((source_text(&line.chars, 0..line.chars.len()), "1 line"))
+    }
+
+    fn yank(&mut self) -> bool {
+        let Some((text, what)) = self.yank_text() else {
+            return false;
+        };
+        self.status_message = Some(match clipboard::copy(&text) {
+            Ok(()) => format!("yanked {}", what),
+            Err(e) => format!("yank failed: {}", e),
+        });
+
+        false
+    }
+
+    // a reference to the cursor line for --goto-anchor, that survives edits of the file
+    fn show_anchor(&mut self) -> bool {
+        let Some((line_id, _)) = self.cursor_anchor() else {
+            return false;
+        };
+        let anchor = LineAnchor::new(&self.lines, line_id, &self.patterns);
+        self.status_message = Some(format!("anchor {}", anchor));
+
+        false
+    }
+
+    // start at the line of the anchor, or where it used to be if it can't be found
+    fn goto_anchor(&mut self, anchor: &LineAnchor) {
+        let line_id = match anchor.resolve(&self.lines, &self.patterns) {
+            Some(line_id) => line_id,
+            None => {
+                self.status_message = Some(format!("anchor {} not found", anchor));
+                anchor.offset().min(self.lines.last_line_id())
+            }
+        };
+        self.first_line = line_id;
+        self.line_offset = 0;
+        self.lines.set_current_line(line_id);
+        if let Some(id) = self.adjust_to_unfiltered_line(line_id) {
+            self.first_line = id;
+        }
+    }
+
+    fn offsets(&mut self) -> bool {
+        self.display_offset = match self.display_offset {
+            OffsetDisplay::Off => OffsetDisplay::Offset,
+            OffsetDisplay::Offset => OffsetDisplay::Line,
+            OffsetDisplay::Line => OffsetDisplay::Relative,
+            OffsetDisplay::Relative => OffsetDisplay::Off,
+        };
+        self.update_offset_len();
+
+        return true;
+    }
+
+    // pin the line under the cursor to the top of the screen, or unpin it again
+    fn toggle_pin(&mut self) -> bool {
+        if self.pinned_line.take().is_some() {
+            self.status_message = Some("line unpinned".to_string());
+            return true;
+        }
+        let Some((line_id, _)) = self.cursor_anchor() else {
+            return false;
+        };
+        self.pinned_line = Some(line_id);
+        self.status_message = Some("line pinned, P to unpin".to_string());
+
+        true
+    }
+
+    fn toggle_center_match(&mut self) -> bool {
+        self.center_match = !self.center_match;
+        self.status_message = Some(match self.center_match {
+            true => "centering search matches".to_string(),
+            false => "scrolling only to matches off screen".to_string(),
+        });
+
+        true
+    }
+
+    fn toggle_diff_above(&mut self) -> bool {
+        self.diff_above = !self.diff_above;
+        self.status_message = Some(match self.diff_above {
+            true => "highlighting changes to the line above".to_string(),
+            false => "change highlighting off".to_string(),
+        });
+
+        true
+    }
+
+    // make room for the widest number the current offset display can show. Lines are
+    // counted in the background, until they are all counted the width grows with the count
+    fn update_offset_len(&mut self) {
+        self.counting_lines = false;
+        self.display_offset_len = match self.display_offset {
+            OffsetDisplay::Off => 0,
+            OffsetDisplay::Offset => self.lines.last_line_id().to_string().len(),
+            // the cursor line shows the absolute line number in relative mode
+            OffsetDisplay::Line |
+            OffsetDisplay::Relative => {
+                let (count, complete) = self.lines.line_count();
+                self.counting_lines = !complete;
+                count.to_string().len()
+            }
+        };
+    }
+
+    fn adjust_to_unfiltered_line(&mut self, line_id: LineId) -> Option<LineId> {
+        lD2!(MA, "filter: current line {} is filtered", line_id);
+        let mut res = self.lines.next_line(SearchType::Tag, line_id, &self.patterns,
+            self.display_mode, true);
+        if res.is_none() {
+            lD2!(MA, "filter: trying next line backwards");
+            res = self.lines.prev_line(SearchType::Tag, line_id, &self.patterns,
+                self.display_mode, true);
+        }
+        let Some(id) = res else {
+            lD2!(MA, "filter: nothing found, staying in normal mode");
+            self.status_message = Some("nothing to display".to_string());
+            self.display_mode = DisplayMode::Normal;
+            return None;
+        };
+
+        Some(id)
+    }
+
+    fn get_line(&self, line_id: LineId) -> Option<ProcessedLine> {
+        if self.is_collapsed(line_id) {
+            return Some(self.collapsed_line(line_id));
+        }
+        if !self.wrap {
+            // get one more char to find out whether the line is longer than the screen
+            let width = self.strip_chars + self.horizontal_offset + self.area_width as usize;
+            let mut line = self.lines.get(line_id, &self.patterns, Some(width + 1))?;
+            line.cropped = line.chars.len() > width;
+            line.chars.truncate(width);
+            return Some(line);
+        }
+        let Some(&(lines, mut first)) = self.overlong_fold.get(&line_id) else {
+            return self.lines.get(line_id, &self.patterns, None);
+        };
+        assert!(lines >= 1);
+        // the first row starts after the hidden prefix
+        let width = self.strip_chars + self.area_width as usize;
+        let indented = (self.area_width - self.wrap_indent(self.area_width)) as usize;
+
+        let crop_chars = Some(width + (lines + first - 1) * indented);
+        let mut line = self.lines.get(line_id, &self.patterns, crop_chars)?;
+        if first == 0 {
+            return Some(line);
+        }
+        // lines - total number of lines to show, including the first line
+        // first - number of lines to skip after the first line
+        let parts = self.line_parts(&line, self.area_width) as usize;
+        if first + lines > parts {
+            first = parts - lines;
+        }
+        // cut out the first /first/ indented lines
+        let cut_size = first * indented;
+        let width = self.hidden_prefix(&line) + self.area_width as usize;
+        line.chars.drain(width .. width + cut_size);
+
+        Some(line)
+    }
+
+    fn move_line_under_cursor(&mut self, line_id: LineId, line_part: usize) {
+        // we want line_id in display line line_ix. find lines backwards to find a suitable
+        // first_line and offset
+        let mut first_line = line_id;
+        let mut lines_to_go_back = self.cursor_y as isize - line_part as isize;
+        lD2!(MA, "move_under_cursor: line_id {} line_part: {} lines_to_go_back: {} first_line {}",
+            line_id, line_part, lines_to_go_back, first_line);
+        lD2!(MA, "cursor_x {} cursor_y: {}", self.cursor_x, self.cursor_y);
+        let mut first_parts = None;
+        while lines_to_go_back > 0 {
+            let Some(prev_line_id) = self.prev_shown(first_line) else {
+                lD2!(MA, "can't get back any further");
+                break;
+            };
+            let pline = self.get_line(prev_line_id).unwrap();
+            first_line = prev_line_id;
+            let parts = self.line_parts(&pline, self.area_width) as isize;
+            lines_to_go_back -= parts;
+            lD2!(MA, "line {} has {} parts, lines_to_go_back {}", prev_line_id, parts,
+                lines_to_go_back);
+            first_parts = Some(parts);
+        }
+        lD2!(MA, "first_line: {} lines_to_go_back: {} first_parts {:?}", first_line,
+            lines_to_go_back, first_parts);
+        self.first_line = first_line;
+        self.lines.set_current_line(self.first_line);
+        if lines_to_go_back > 0 {
+            self.cursor_y -= lines_to_go_back as i16;
+        }
+        self.line_offset = 0;
+        if let Some(first_parts) = first_parts {
+            if lines_to_go_back < 0 && -lines_to_go_back < first_parts {
+                self.line_offset = -lines_to_go_back as usize;
+                lD2!(MA, "filter: line_offset: {}", self.line_offset);
+            }
+        }
+    }
+
+    fn display(&mut self, direction: Direction) -> bool {
+        let old_mode = self.display_mode;
+
+        if direction == Direction::Forward {
+            match self.display_mode {
+                DisplayMode::All => self.display_mode = DisplayMode::Normal,
+                DisplayMode::Normal => self.display_mode = DisplayMode::Tagged,
+                DisplayMode::Tagged => self.display_mode = DisplayMode::Manual,
+                DisplayMode::Manual => return false,
+            }
+        } else {
+            match self.display_mode {
+                DisplayMode::Manual => self.display_mode = DisplayMode::Tagged,
+                DisplayMode::Tagged => self.display_mode = DisplayMode::Normal,
+                DisplayMode::Normal => self.display_mode = DisplayMode::All,
+                DisplayMode::All => return false,
+            }
+        }
+
+        // before the first render there is no cursor line to keep, the mode change stands
+        let Some(line_index) = self.line_indexes.get(self.cursor_y as usize) else {
+            return true;
+        };
+        let line_ix = line_index.line_ix;
+        let line_part = line_index.line_part;
+        let line_id = self.plines[line_ix].line_id;
+        let y = self.cursor_y;
+        self.before_filter_pos.insert(old_mode as usize, (line_id, line_part, y));
+
+        // back to where the cursor was when this mode was left, unless a pattern change or
+        // reload took that line away from it
+        let remembered = self.before_filter_pos.get(&(self.display_mode as usize)).copied()
+            .filter(|&(id, _, _)| self.lines.next_line(SearchType::Tag, id, &self.patterns,
+                self.display_mode, true) == Some(id));
+        // otherwise move cursor to next unfiltered line
+        let (line_id, line_part) = if let Some((id, part, y)) = remembered {
+            // the line might wrap differently by now
+            let parts = self.line_parts(&self.get_line(id).unwrap(), self.area_width);
+            self.cursor_y = y.min(self.area_height as i16 - 1);
+            (id, part.min(parts - 1))
+        } else if let Some(new) = self.adjust_to_unfiltered_line(line_id) {
+            if new != line_id {
+                (new, 0)
+            } else {
+                (line_id, line_part)
+            }
+        } else {
+            // if we can't find a line, stay in previous mode
+            self.display_mode = old_mode;
+            self.undo_stack.pop();
+            return true;
+        };
+
+        self.move_line_under_cursor(line_id, line_part);
+
+        true
+    }
+
+    fn search(&mut self, direction: Direction, match_type: MatchType) -> bool {
+        self.focus = Focus::Search;
+        self.current_search = String::new();
+        self.history_pos = None;
+        self.search_direction = direction;
+        self.search_match_type = match_type;
+        self.search_origin = None;
+
+        false
+    }
+
+    fn restore_search_origin(&mut self) {
+        let Some(origin) = self.search_origin.clone() else {
+            return;
+        };
+        self.first_line = origin.first_line;
+        self.lines.set_current_line(self.first_line);
+        self.line_offset = origin.line_offset;
+        (self.cursor_x, self.cursor_y) = origin.cursor;
+        self.plines = origin.plines;
+        self.line_indexes = origin.line_indexes;
+    }
+
+    // drop the pattern of the preview and switch the last search back on
+    fn clear_preview(&mut self) {
+        let Some(id) = self.search_preview.take() else {
+            return;
+        };
+        self.patterns.remove(id);
+        if let Some(old) = self.paused_search.take() {
+            self.patterns.set_enabled(old, true);
+        }
+        self.update_patterns(PatternMode::Search);
+    }
+
+    // show where the search being typed would go, without making it the search yet
+    fn preview_search(&mut self) -> bool {
+        if self.search_origin.is_none() {
+            self.search_origin = Some(SearchOrigin {
+                first_line: self.first_line,
+                line_offset: self.line_offset,
+                cursor: (self.cursor_x, self.cursor_y),
+                plines: self.plines.clone(),
+                line_indexes: self.line_indexes.clone(),
+            });
+        }
+        self.clear_preview();
+        self.restore_search_origin();
+        if self.current_search.is_empty() {
+            return true;
+        }
+        let (search, anchor) = self.search_pattern(&self.current_search);
+        let style = self.mark_style.get(MarkType::Search);
+        let Ok(id) = self.patterns.add(&search, self.search_match_type, anchor,
+            self.search_ignore_case, style, PatternMode::Search) else {
+            // Enter tells what's wrong with it
+            return true;
+        };
+        self.search_preview = Some(id);
+        if let Some(old) = self.last_search.filter(|&old| self.patterns.get(old).enabled) {
+            self.patterns.set_enabled(old, false);
+            self.paused_search = Some(old);
+        }
+        self.update_patterns(PatternMode::Search);
+        // only as far as the background search got, scanning the file would stall the input
+        match self.search_direction {
+            Direction::Forward => self.search_next(false),
+            Direction::Backward => self.search_prev(false),
+        };
+
+        true
+    }
+
+    // leave the input without a new search, the last one stays
+    fn cancel_search(&mut self) -> bool {
+        self.clear_preview();
+        self.restore_search_origin();
+        self.search_origin = None;
+        self.current_search.clear();
+        self.focus = Focus::Main;
+
+        true
+    }
+
+    // replace the input with an older or newer search. Past the newest one, the input
+    // typed before comes back
+    fn recall_search(&mut self, direction: Direction) {
+        let len = self.search_history.len();
+        let pos = match (direction, self.history_pos) {
+            (Direction::Backward, None) if len > 0 => {
+                self.history_input = self.current_search.clone();
+                Some(len - 1)
+            }
+            (Direction::Backward, Some(pos)) => Some(pos.saturating_sub(1)),
+            (Direction::Forward, Some(pos)) if pos + 1 < len => Some(pos + 1),
+            (Direction::Forward, Some(_)) => None,
+            _ => return,
+        };
+        self.current_search = match pos {
+            Some(pos) => self.search_history[pos].clone(),
+            None => std::mem::take(&mut self.history_input),
+        };
+        self.history_pos = pos;
+    }
+
+    fn add_search_history(&mut self, search: &str) {
+        if search.is_empty() || self.search_history.last().is_some_and(|last| last == search) {
+            return;
+        }
+        if self.search_history.len() == MAX_SEARCH_HISTORY {
+            self.search_history.remove(0);
+        }
+        self.search_history.push(search.to_string());
+    }
+
+    // write the lines of the current view with their highlighting, "-" is stdout. An
+    // existing file is only replaced with overwrite
+    fn export_to(&self, format: ExportFormat, path: &OsStr, overwrite: bool)
+        -> io::Result<u64>
+    {
+        let mut out: Box<dyn Write> = if path == "-" {
+            Box::new(io::BufWriter::new(io::stdout()))
+        } else {
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .create_new(!overwrite)
+                .open(path)?;
+            Box::new(io::BufWriter::new(file))
+        };
+        let name = std::path::Path::new(&self.filename).file_name().unwrap_or_default();
+        let count = match format {
+            ExportFormat::Html => export::export_html(&mut out, &name.to_string_lossy(),
+                &self.lines, &self.patterns, self.display_mode),
+            ExportFormat::Ansi => export::export_ansi(&mut out, &self.lines, &self.patterns,
+                self.display_mode),
+            ExportFormat::Text => {
+                // the whole view of a big file takes a while
+                let total = (self.lines.last_line_id() + 1) as f32;
+                let mut written = 0;
+                export::export_text(&mut out, &self.lines, &self.patterns, self.display_mode,
+                    |line_id| {
+                        if written % PROGRESS_INTERVAL == 0 {
+                            self.progress.set("Exporting", line_id as f32 / total);
+                        }
+                        written += 1;
+                    })
+            }
+        };
+        self.progress.clear();
+        let count = count?;
+        out.flush()?;
+
+        Ok(count)
+    }
+
+    // save the current view next to the log, in the current directory. Earlier exports are
+    // kept, the next one is numbered like app.log.1.html
+    fn export(&mut self, format: ExportFormat) -> bool {
+        let name = std::path::Path::new(&self.filename).file_name().unwrap_or_default();
+        let path = (0..).map(|i| {
+                let mut path = name.to_os_string();
+                if i > 0 {
+                    path.push(format!(".{}", i));
+                }
+                path.push(".");
+                path.push(format.extension());
+                path
+            })
+            .find(|path| !std::path::Path::new(path).exists())
+            .unwrap();
+        self.export_path(format, &path, false)
+    }
+
+    fn export_path(&mut self, format: ExportFormat, path: &OsStr, overwrite: bool) -> bool {
+        let result = self.export_to(format, path, overwrite);
+        let name = path.to_string_lossy();
+        self.status_message = Some(match result {
+            Ok(count) => format!("exported {} lines to {}", count, name),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists =>
+                format!("{} exists, :w! {} to overwrite it", name, name),
+            Err(e) => format!("export to {} failed: {}", name, e),
+        });
+
+        false
+    }
+
+    // drop the search highlight, leaving marks, tags and hides alone
+    fn clear_search(&mut self) -> bool {
+        let Some(id) = self.last_search.take() else {
+            self.status_message = Some("no search to clear".to_string());
+            return false;
+        };
+        let anchor = self.cursor_anchor();
+        self.remove_pattern(id);
+        if let Some((line_id, line_part)) = anchor {
+            self.anchor_cursor(line_id, line_part);
+        }
+        self.status_message = Some("search cleared".to_string());
+
+        true
+    }
+
+    // search string is collected, do the actual search. Returns false if the pattern is
+    // invalid, the previous search is kept then
+    // regexes bring their own anchors
+    fn search_pattern(&self, input: &str) -> (String, Anchor) {
+        if self.search_match_type == MatchType::Regex {
+            (input.to_string(), Anchor::default())
+        } else {
+            Anchor::parse(input)
+        }
+    }
+
+    fn do_search(&mut self, search: String) -> bool {
+        lD5!(MA, "do_search: search: {}", search);
+        let style = self.mark_style.get(MarkType::Search);
+        let match_type = self.search_match_type;
+        let (pattern, anchor) = self.search_pattern(&search);
+        let id = if pattern.is_empty() {
+            None
+        } else {
+            let Some(id) = self.add_pattern(&pattern, match_type, anchor, self.search_ignore_case,
+                style, PatternMode::Search) else {
+                return false;
+            };
+            Some(id)
+        };
+        self.add_search_history(&search);
+        if let Some(old) = std::mem::replace(&mut self.last_search, id) {
+            self.remove_pattern(old);
+        }
+        if id.is_some() {
+            self.search_cont(Direction::Forward);
+        }
+
+        true
+    }
+
+    fn match_has_mode(&self, pline: &ProcessedLine, pos: usize, mode: PatternMode) -> bool
+    {
+        if let Some(ref matches) = pline.chars[pos].matches {
+            for &(id, _) in matches {
+                if self.patterns.get(id).mode == mode {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn match_get_search_ix(&self, pline: &ProcessedLine, pos: usize) -> Option<usize>
+    {
+        if let Some(ref matches) = pline.chars[pos].matches {
+            for &(id, ix) in matches {
+                if self.patterns.get(id).mode == PatternMode::Search {
+                    return Some(ix);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn match_has_search_ix(&self, pline: &ProcessedLine, pos: usize, wanted_ix: usize) -> bool {
+        if let Some(ref matches) = pline.chars[pos].matches {
+            for &(_, ix) in matches {
+                if ix == wanted_ix {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    // return the start position of the search match, if any
+    fn get_search_match_forward(&self, pline: &ProcessedLine, pos: usize, skip_current: bool)
+        -> Option<usize>
+    {
+        let mut pos = pos;
+        if skip_current {
+            let ix = self.match_get_search_ix(pline, pos);
+            if let Some(ix) = ix {
+                while pos < pline.chars.len() {
+                    if !self.match_has_search_ix(pline, pos, ix) {
+                        break;
+                    }
+                    pos += 1;
+                }
+            }
+        };
+        while pos < pline.chars.len() {
+            if self.match_has_mode(pline, pos, PatternMode::Search) {
+                return Some(pos);
+            }
+            pos += 1;
+        }
+        None
+    }
+
+    // return the start position of the search match, if any
+    fn get_search_match_backward(&mut self, pline: &ProcessedLine, pos: usize, skip_current: bool)
+        -> Option<usize>
+    {
+        let mut pos = pos as isize;
+        if skip_current {
+            let ix = self.match_get_search_ix(pline, pos as usize);
+            if let Some(ix) = ix {
+                while pos >= 0 {
+                    if !self.match_has_search_ix(pline, pos as usize, ix) {
+                        break;
+                    }
+                    pos -= 1;
+                }
+            }
+        };
+        while pos >= 0 {
+            if self.match_has_mode(pline, pos as usize, PatternMode::Search) {
+                // found a match, now find the start of the match
+                let Some(ix) = self.match_get_search_ix(pline, pos as usize) else {
+                    return None;
+                };
+                while pos > 0 && self.match_has_search_ix(pline, pos as usize - 1, ix) {
+                    pos -= 1;
+                }
+                return Some(pos as usize);
+            }
+            pos -= 1;
+        }
+        None
+    }
+
+    // the row of the match in the middle of the screen, as far as the file allows
+    fn center_on_match(&mut self, pline: &ProcessedLine, match_pos: usize) -> bool {
+        let (x, line_part) = self.cursor_from_pos_len(pline, match_pos, self.area_width);
+        let rows = self.area_height as usize;
+        let middle = rows / 2;
+        // near the end the screen stays filled, so the match ends up further down
+        let mut below = self.line_parts(pline, self.area_width) - 1 - line_part as usize;
+        let mut line_id = pline.line_id;
+        while !self.scroll_past_end && below < rows - 1 - middle {
+            let Some(next) = self.next_shown(line_id) else {
+                break;
+            };
+            below += self.line_parts(&self.get_line(next).unwrap(), self.area_width);
+            line_id = next;
+        }
+        self.set_cursor_x(x as i16);
+        self.cursor_y = match self.scroll_past_end {
+            true => middle,
+            false => middle.max(rows.saturating_sub(below + 1)),
+        } as i16;
+        self.move_line_under_cursor(pline.line_id, line_part as usize);
+
+        true
+    }
+
+    fn search_cont(&mut self, direction: Direction) -> bool {
+        let search_dir = self.search_direction;
+        if search_dir == direction {
+            self.search_next(true)
+        } else {
+            self.search_prev(true)
+        }
+    }
+
+    // without wait, only in the splits the background search is done with
+    fn search_next(&mut self, wait: bool) -> bool {
+        let (pos, ix, part) = match self.resolve_cursor_position() {
+            Some(x) => x,
+            None => (None, 0, 0),
+        };
+        let pos = if let Some(pos) = pos {
+            pos
+        } else if part == 0 {
+            0
+        } else {
+            self.hidden_prefix(&self.plines[ix]) + self.area_width as usize +
+                (part - 1) * (self.area_width - self.wrap_indent(self.area_width)) as usize
+        };
+        let line_id = self.plines[ix].line_id;
+        // don't take line from cache, as the matches aren't up-to-date here
+        let pline = self.get_line(line_id).unwrap();
+        lD2!(MA, "search_next: pos: {} ix: {} part: {} line: {}", pos, ix, part, pline.line_id);
+        if let Some(match_pos) = self.get_search_match_forward(&pline, pos, true) {
+            lD2!(MA, "do_search: found match at {}", match_pos);
+            if self.center_match {
+                return self.center_on_match(&pline, match_pos);
+            }
+            let (x, y) = self.cursor_from_pos_ix(match_pos, ix, self.area_width);
+            self.set_cursor_x(x as i16);
+            self.cursor_y = y as i16;
+            return true;
+        }
+        let next = |inner: &Self, line_id, inclusive| match wait {
+            true => inner.lines.next_line(SearchType::Search, line_id, &inner.patterns,
+                DisplayMode::Normal, inclusive),
+            false => inner.lines.next_searched_line(line_id, &inner.patterns, inclusive),
+        };
+        let mut res = next(self, pline.line_id, false);
+        lD2!(MA, "do_search: next_line: {:?}", res);
+        if res.is_none() {
+            // from the start of the file, this includes matches before the cursor on the
+            // current line
+            self.lines.set_current_line(0);  // hint for FileSearch
+            res = next(self, 0, true);
+            lD2!(MA, "do_search: next_line from 0: {:?}", res);
+            if res.is_some() {
+                self.status_message = Some("Search wrapped".to_string());
+            }
+        }
+        let Some(line_id) = res else {
+            lD2!(MA, "do_search: nothing found");
+            if wait {
+                self.status_message = Some("No matches".to_string());
+            }
+            return false;
+        };
+
+        let pline = self.get_line(line_id).unwrap();
+        lD10!(MA, "current line: {} {:?}", line_id, pline);
+        let match_pos = self.get_search_match_forward(&pline, 0, false).unwrap();
+        if self.center_match {
+            return self.center_on_match(&pline, match_pos);
+        }
+
+        // if line is on screen, do not scroll
+        let ix = self.line_indexes.iter().position(|x| self.plines[x.line_ix].line_id == line_id);
+        if let Some(ix) = ix {
+            let (x, y) = self.cursor_from_pos_len(&pline, match_pos, self.area_width);
+            let y = y + ix as u16;
+            if y < self.area_height {
+                self.set_cursor_x(x as i16);
+                self.cursor_y = y as i16;
+                return true;
+            }
+        }
+
+        lD2!(MA, "do_search: found match at {}", match_pos);
+        let (x, y) = self.cursor_from_pos_len(&pline, match_pos, self.area_width);
+        self.set_cursor_x(x as i16);
+        self.cursor_y = y as i16;
+
+        self.first_line = line_id;
+        self.lines.set_current_line(self.first_line);
+        self.line_offset = 0;
+
+        true
+    }
+
+    fn search_prev(&mut self, wait: bool) -> bool {
+        let (pos, ix, part) = match self.resolve_cursor_position() {
+            Some(x) => x,
+            None => (None, 0, 0),
+        };
+        let pos = if let Some(pos) = pos {
+            pos
+        } else if part == 0 {
+            0
+        } else {
+            self.hidden_prefix(&self.plines[ix]) + self.area_width as usize +
+                (part - 1) * (self.area_width - self.wrap_indent(self.area_width)) as usize
+        };
+        let line_id = self.plines[ix].line_id;
+        // don't take line from cache, as the matches aren't up-to-date here
+        let pline = self.get_line(line_id).unwrap();
+        lD2!(MA, "search_prev: pos: {} ix: {} part: {} line: {}", pos, ix, part, pline.line_id);
+        // a match in the hidden prefix is shown at the start of the line, where the cursor
+        // might be already
+        let start = self.hidden_prefix(&pline);
+        let found = self.get_search_match_backward(&pline, pos, true)
+            .filter(|&match_pos| match_pos >= start || pos > start);
+        if let Some(match_pos) = found {
+            lD2!(MA, "do_search: found match at {}", match_pos);
+            if self.center_match {
+                return self.center_on_match(&pline, match_pos);
+            }
+            let (x, y) = self.cursor_from_pos_ix(match_pos, ix, self.area_width);
+            self.set_cursor_x(x as i16);
+            self.cursor_y = y as i16;
+            return true;
+        }
+        let prev = |inner: &Self, line_id, inclusive| match wait {
+            true => inner.lines.prev_line(SearchType::Search, line_id, &inner.patterns,
+                DisplayMode::Normal, inclusive),
+            false => inner.lines.prev_searched_line(line_id, &inner.patterns, inclusive),
+        };
+        let mut res = prev(self, pline.line_id, false);
+        lD2!(MA, "do_search: next_line: {:?}", res);
+        if res.is_none() {
+            let last_line_id = self.lines.last_line_id();
+            self.lines.set_current_line(last_line_id); // hint for FileSearch
+            res = prev(self, last_line_id, true);
+            lD2!(MA, "do_search: next_line from 0: {:?}", res);
+            if res.is_some() {
+                self.status_message = Some("Search wrapped".to_string());
+            }
+        }
+        let Some(line_id) = res else {
+            lD2!(MA, "do_search: nothing found");
+            if wait {
+                self.status_message = Some("No matches".to_string());
+            }
+            return false;
+        };
+
+        let pline = self.get_line(line_id).unwrap();
+        lD10!(MA, "current line: {} {:?}", line_id, pline);
+        let len = pline.chars.len();
+        let match_pos = self.get_search_match_backward(&pline, len - 1, false).unwrap();
+        if self.center_match {
+            return self.center_on_match(&pline, match_pos);
+        }
+
+        // if line is on screen, do not scroll
+        let ix = self.line_indexes.iter().position(|x| self.plines[x.line_ix].line_id == line_id);
+        if let Some(ix) = ix {
+            let (x, y) = self.cursor_from_pos_len(&pline, match_pos, self.area_width);
+            let y = y + ix as u16;
+            if y < self.area_height {
+                self.set_cursor_x(x as i16);
+                self.cursor_y = y as i16;
+                return true;
+            }
+        }
+
+        lD2!(MA, "do_search: found match at {}", match_pos);
+        let (x, y) = self.cursor_from_pos_len(&pline, match_pos, self.area_width);
+        self.set_cursor_x(x as i16);
+        self.cursor_y = y as i16;
+
+        self.first_line = line_id;
+        self.lines.set_current_line(self.first_line);
+        self.line_offset = 0;
+
+        true
+    }
+
+    fn hexdump(&mut self) -> bool {
+        let Some((_, line_ix, _)) = self.resolve_cursor_position() else {
+            return false;
+        };
+        let line_id = self.plines[line_ix].line_id;
+        let Some((line_id, bytes)) = self.lines.get_raw(line_id, &self.patterns) else {
+            return false;
+        };
+        self.show_overlay(build_hexdump(line_id, &bytes));
+
+        true
+    }
+
+    // the last messages, for those that were gone too soon
+    fn message_log(&mut self) -> bool {
+        if self.message_log.is_empty() {
+            self.status_message = Some("no messages".to_string());
+            return false;
+        }
+        let text = self.message_log.iter().map(|m| Line::from(m.clone())).collect();
+        self.show_overlay(build_text_overlay(text));
+
+        true
+    }
+
+    // how big the file is and what is going on with it. Lines are counted in the background,
+    // until they are all counted only part of them is known
+    fn info(&mut self) -> bool {
+        let num_splits = self.lines.num_splits();
+        let size = num_splits.checked_sub(1)
+            .and_then(|last| self.lines.get_split(last))
+            .map_or(0, |(_, end)| end);
+        let lines = match self.lines.line_count() {
+            (count, true) => count.to_string(),
+            (count, false) => format!("{} so far, still counting", count),
+        };
+        let active = |mode| self.patterns.iter().filter(|p| p.enabled && p.mode == mode).count();
+        let patterns = format!("{} tagging, {} hiding, {} marking", active(PatternMode::Tagging),
+            active(PatternMode::Hiding), active(PatternMode::Marking));
+        let key = Style::default().bold();
+        let text = [
+            ("file", self.filename.to_string_lossy().into_owned()),
+            ("size", format!("{} bytes", size)),
+            ("splits", num_splits.to_string()),
+            ("lines", lines),
+            ("display", format!("{:?}", self.display_mode)),
+            ("patterns", patterns),
+        ].into_iter().map(|(name, value)| Line::from(vec![
+            Span::styled(format!("{:<10}", name), key),
+            Span::raw(value),
+        ])).collect();
+        self.show_overlay(build_text_overlay(text));
+
+        true
+    }
+
+    // list all tagged lines to jump between them
+    fn tag_list(&mut self) -> bool {
+        let mut tag_list = Vec::new();
+        let mut text = Vec::new();
+        let mut next = self.lines.next_line(SearchType::Tag, 0, &self.patterns,
+            DisplayMode::Tagged, true);
+        let mut scanned = 0;
+        while let Some(line_id) = next {
+            if scanned == MAX_TAG_LIST_SCAN {
+                self.status_message = Some(format!("only showing the tagged lines of the first \
+                    {} lines of tagged mode", MAX_TAG_LIST_SCAN));
+                break;
+            }
+            scanned += 1;
+            let Some(pline) = self.lines.get(line_id, &self.patterns, Some(TAG_LIST_WIDTH)) else {
+                self.status_message = Some(format!("line {} can't be read", line_id));
+                break;
+            };
+            // search results are also shown in tagged mode
+            if self.lines.is_tagged(line_id) ||
+                pline.matches.iter().any(|&id| self.patterns.is_tagging(id))
+            {
+                let content = pline.chars.iter().map(|c| c.c).collect::<String>();
+                text.push(Line::from(vec![
+                    Span::styled(format!("{:>12} ", line_id), Style::default().fg(Color::DarkGray)),
+                    Span::raw(content),
+                ]));
+                tag_list.push(line_id);
+                // no need to look for more than are shown
+                if tag_list.len() == MAX_TAG_LIST {
+                    self.status_message = Some(format!("only showing the first {} tagged lines",
+                        MAX_TAG_LIST));
+                    break;
+                }
+            }
+            next = self.lines.next_line(SearchType::Tag, line_id, &self.patterns,
+                DisplayMode::Tagged, false);
+        }
+        if tag_list.is_empty() {
+            // a read error says more
+            self.status_message.get_or_insert_with(|| "no tagged lines".to_string());
+            return false;
+        }
+
+        let key = Style::default().bold();
+        let bottom = Line::from(vec![
+                Span::styled("j", key), Span::raw("/"),
+                Span::styled("k", key),
+                Span::raw(": select "),
+                Span::styled("Enter", key),
+                Span::raw(": jump "),
+                Span::styled("q", key),
+                Span::raw(": close"),
+        ]).alignment(Alignment::Center);
+        let mut overlay = Overlay::new(text, bottom);
+        overlay.selected = Some(0);
+        self.tag_list = tag_list;
+        self.show_overlay(overlay);
+        self.focus = Focus::TagList;
+
+        true
+    }
+
+    // the patterns with their mode, disabled ones muted
+    fn pattern_list_text(&self) -> Vec<Line<'static>> {
+        self.pattern_list.iter().map(|&id| {
+            let p = self.patterns.get(id);
+            let muted = Style::default().fg(Color::DarkGray);
+            let style = if p.enabled { p.style.style() } else { muted.crossed_out() };
+            let kind = if p.negative {
+                format!("Hiding others {:?}", p.match_type)
+            } else {
+                format!("{:?} {:?}", p.mode, p.match_type)
+            };
+            Line::from(vec![
+                Span::styled("  ", p.style.style()),
+                Span::styled(format!(" {:<18} ", kind), muted),
+                Span::styled(p.pattern.clone(), style),
+                Span::styled(if p.enabled { "" } else { " (off)" }, muted),
+            ])
+        }).collect()
+    }
+
+    // list all patterns to manage them in one place
+    fn pattern_list(&mut self) -> bool {
+        self.pattern_list = self.patterns.iter_ids().map(|(id, _)| id).collect();
+        if self.pattern_list.is_empty() {
+            self.status_message = Some("no patterns".to_string());
+            return false;
+        }
+
+        let key = Style::default().bold();
+        let bottom = Line::from(vec![
+                Span::styled("j", key), Span::raw("/"),
+                Span::styled("k", key),
+                Span::raw(": select "),
+                Span::styled("Space", key),
+                Span::raw(": on/off "),
+                Span::styled("c", key), Span::raw("/"),
+                Span::styled("C", key),
+                Span::raw(": color "),
+                Span::styled("m", key),
+                Span::raw(": mode "),
+                Span::styled("d", key),
+                Span::raw(": delete "),
+                Span::styled("q", key),
+                Span::raw(": close"),
+        ]).alignment(Alignment::Center);
+        let mut overlay = Overlay::new(self.pattern_list_text(), bottom);
+        overlay.selected = Some(0);
+        self.show_overlay(overlay);
+        self.focus = Focus::PatternList;
+
+        true
+    }
+
+    fn help(&mut self) -> bool {
+        self.focus = Focus::Help;
+        true
+    }
+
+    fn calculate_layout(&self, area: Rect) -> [Rect; 8] {
+        /*
+         * calculate layout
+         */
+        let [main_area, separator_area, bottom_area] = Layout::vertical([
+                Constraint::Fill(1),
+                Constraint::Length(self.separator as u16),
+                Constraint::Length(1),
+            ])
+                .spacing(0)
+                .areas(area);
+
+        let [pin_area, main_area] =
+            Layout::vertical([Constraint::Length(self.pinned_line.is_some() as u16),
+                Constraint::Fill(1)])
+                .spacing(0)
+                .areas(main_area);
+
+        // room for the follow indicator on top of the usual fields
+        let status_width = self.status_width + if self.follow { FOLLOW_STATUS_WIDTH } else { 0 };
+        let [input_area, status_area] =
+            Layout::horizontal([Constraint::Min(MIN_INPUT_WIDTH), Constraint::Length(status_width)])
+                .spacing(0)
+                .areas(bottom_area);
+
+        let marker_len = if self.display_offset != OffsetDisplay::Off {
+            self.marker_width as usize + self.display_offset_len as usize + 1
+        } else {
+            self.marker_width as usize
+        };
+        // the scrollbar only takes a column that isn't needed for the text
+        let scrollbar_len = self.scrollbar &&
+            main_area.width.saturating_sub(marker_len as u16) > MIN_WRAP_COLUMNS;
+        let [marker_area, log_area, scrollbar_area] =
+            Layout::horizontal([Constraint::Length(marker_len as u16), Constraint::Fill(1),
+                Constraint::Length(scrollbar_len as u16)])
+                .spacing(0)
+                .areas(main_area);
+
+        [main_area, log_area, marker_area, input_area, status_area, separator_area, pin_area,
+            scrollbar_area]
+    }
+
+    fn process_event(&mut self, area: Rect, event: Option<Event>) {
+        // the message of the last event is kept a while longer, unless a new one replaces it
+        if let Some(message) = self.status_message.take() {
+            let at = self.message_at.take().unwrap_or_else(std::time::Instant::now);
+            self.old_message = Some((message, at));
+        }
+
+        // in follow mode, the file is checked for new lines on every event and periodically
+        let end = self.lines.last_line_id();
+        let grown = self.follow && self.check_growth();
+        if grown {
+            self.check_alerts(end);
+        }
+        if self.counting_lines {
+            self.update_offset_len();
+        }
 
         /*
          * Handle key events part 1
@@ -1504,13 +4079,16 @@ impl LogrokInner {
                     Focus::Main => self.handle_event_before_layout(&key_event),
                     Focus::Search => self.handle_search_event_before_layout(&key_event),
                     Focus::Help => self.handle_help_event_before_layout(&key_event),
+                    Focus::Overlay | Focus::TagList | Focus::PatternList | Focus::RegexTest |
+                        Focus::LogLevel | Focus::Command => false,
                 })
             } else {
                 (None, false)
             }
         } else {
-            (None, false)
+            (None, grown)
         };
+        let [_, log_area, marker_area, _, _, _, _, _] = self.calculate_layout(area);
 
         /*
          * calculate cursor position on area change
@@ -1524,7 +4102,7 @@ impl LogrokInner {
                     let (x, y) = self.cursor_from_pos_ix(pos, ix, log_area.width);
                     self.cursor_x = x as i16;
                     self.cursor_y = y as i16;
-                } else if self.cursor_x < self.indent_chars as i16 {
+                } else if self.cursor_x < self.wrap_indent(self.area_width) as i16 {
                     // when in indent whitespace, keep it in the same column and part
                     let parts = self.line_parts(&self.plines[ix], log_area.width);
                     let (_, y) = self.cursor_from_pos_ix(0, ix, log_area.width);
@@ -1555,14 +4133,32 @@ impl LogrokInner {
                 Focus::Main => self.handle_event_after_layout(&key_event),
                 Focus::Search => self.handle_search_event_after_layout(&key_event),
                 Focus::Help => self.handle_help_event_after_layout(&key_event),
+                Focus::Overlay => self.handle_overlay_event_after_layout(&key_event),
+                Focus::TagList => self.handle_tag_list_event_after_layout(&key_event),
+                Focus::PatternList => self.handle_pattern_list_event_after_layout(&key_event),
+                Focus::RegexTest => self.handle_regex_test_event_after_layout(&key_event),
+                Focus::LogLevel => self.handle_log_level_event_after_layout(&key_event),
+                Focus::Command => self.handle_command_event_after_layout(&key_event),
             };
         }
+        if let Some(Event::Mouse(mouse_event)) = event {
+            if focus == Focus::Main {
+                recalc_lines |= self.handle_mouse_event(&mouse_event, log_area, marker_area);
+            }
+        }
 
         /*
          * build lines
          */
         lD5!(MA, "render: recalc_lines: {}", recalc_lines);
-        recalc_lines |= self.plines.is_empty();
+        if grown {
+            recalc_lines |= self.follow_tail();
+        }
+        if self.plines.is_empty() {
+            // initial layout
+            self.follow_tail();
+            recalc_lines = true;
+        }
         while recalc_lines {
             recalc_lines = false;
             let mut state_lines = Vec::new();
@@ -1572,10 +4168,8 @@ impl LogrokInner {
             loop {
                 lD5!(MA, "render: curr_line_id: {} num_lines {} skip {}",
                     curr_line_id, num_lines, skip);
-                let mode = self.display_mode;
                 let pline = self.get_line(curr_line_id).unwrap();
-                let next_line_id = self.lines.next_line(SearchType::Tag, curr_line_id,
-                    &self.patterns, mode, false);
+                let next_line_id = self.next_shown(curr_line_id);
                 state_lines.push(pline.clone());
                 let parts = self.line_parts(&pline, log_area.width);
                 num_lines += parts;
@@ -1592,36 +4186,86 @@ impl LogrokInner {
                 }
             }
             self.plines = state_lines;
+            if self.status_message.is_none() && self.plines.iter().any(|l| l.broad) {
+                self.status_message = Some("pattern too broad".to_string());
+            }
 
-            while num_lines < log_area.height as usize {
+            // fill the screen, unless the user explicitly scrolled past the end
+            while num_lines < log_area.height as usize && !self.scroll_past_end {
                 let scrolled = self.scroll_up();
                 if scrolled {
                     num_lines += 1;
                     recalc_lines = true;
+                    // the cursor stays on its line, which moved down a row
+                    self.cursor_y = (self.cursor_y + 1).min(log_area.height as i16 - 1);
                 } else {
                     break;
                 }
             }
         }
+        if let Some(e) = self.lines.take_read_error() {
+            self.status_message = Some(format!("failed to read the file: {}", e));
+        }
+    }
+
+    // the position of the rows on screen in the file. In filtered modes the lines shown span
+    // more of the file than they fill, so it's only approximate
+    fn render_scrollbar(&self, area: Rect, buf: &mut Buffer) {
+        let line_id = |index: &LineIndex| self.plines[index.line_ix].line_id as usize;
+        let (Some(first), Some(last)) = (self.line_indexes.first(), self.line_indexes.last())
+        else {
+            return;
+        };
+        let (first, last) = (line_id(first), line_id(last));
+        let total = self.lines.last_line_id() as usize + 1;
+        let viewport = last - first + 1;
+        // ratatui counts the positions to scroll to, not the length of the content
+        let mut state = ScrollbarState::new(total.saturating_sub(viewport) + 1)
+            .position(first)
+            .viewport_content_length(viewport);
+        Scrollbar::new(ScrollbarOrientation::VerticalRight)
+            .begin_symbol(None)
+            .end_symbol(None)
+            .track_symbol(Some(self.glyphs.scroll_track))
+            .thumb_symbol(self.glyphs.scroll_thumb)
+            .track_style(Style::new().dark_gray())
+            .render(area, buf, &mut state);
+    }
+
+    // the first row of the pinned line, with the marker column like the log below it
+    fn render_pinned(&self, line_id: LineId, pin_area: Rect, log_area: Rect, buf: &mut Buffer) {
+        let Some(pline) = self.get_line(line_id) else {
+            return;
+        };
+        let text = pline.chars.iter()
+            .skip(self.hidden_prefix(&pline))
+            .take(log_area.width as usize)
+            .map(|sc| Span::styled(sc.c.to_string(), sc.display_style()))
+            .collect::<Vec<_>>();
+        Paragraph::new(Span::raw(self.glyphs.pinned).bold())
+            .render(Rect { width: log_area.x - pin_area.x, ..pin_area }, buf);
+        Paragraph::new(Line::from(text).underlined())
+            .render(Rect { x: log_area.x, width: log_area.width, ..pin_area }, buf);
     }
 
     fn render(&mut self, area: Rect, buf: &mut Buffer) {
         // ignore everything if the area is too small
         lD3!(MA, "render: area: {}x{} indent_chars {}", area.width, area.height, self.indent_chars);
-        if area.width < self.indent_chars + 3 {
+        // the wrap indent is clamped, it doesn't need room of its own
+        if area.width < MARKER_WIDTH + MIN_WRAP_COLUMNS {
             Paragraph::new(Text::raw("Window not wide enough"))
                 .alignment(Alignment::Center)
                 .render(area, buf);
             return;
         }
-        if area.height < 3 {
+        if area.height < 3 + self.separator as u16 + self.pinned_line.is_some() as u16 {
             Paragraph::new(Text::raw("Window not high enough"))
                 .render(area, buf);
             return;
         }
 
-        let [main_area, log_area, marker_area, input_area, status_area] =
-            self.calculate_layout(area);
+        let [main_area, log_area, marker_area, input_area, status_area, separator_area, pin_area,
+            scrollbar_area] = self.calculate_layout(area);
 
         /*
          * render lines and build index array
@@ -1629,14 +4273,32 @@ impl LogrokInner {
         let mut lines = Vec::new();
         let mut line_indexes = Vec::new();
         let mut skip = self.line_offset;
+        let regex_test = if self.focus == Focus::RegexTest {
+            Some(self.regex_test_matches())
+        } else {
+            None
+        };
+        let preview = Style::new().reversed().underlined();
+        let current = Style::new().reversed();
+        let occurrence = Style::new().bg(Color::DarkGray);
+        // added on top, so marks and tags keep their colors
+        let changed = Style::new().underlined();
+        let indent = self.wrap_indent(log_area.width);
         'a: for (i, pline) in self.plines.iter().enumerate() {
-            let mut ix = 0;
+            let pline_matches = match regex_test {
+                Some(Some((ref matched, _))) => Some(&matched[i]),
+                _ => None,
+            };
+            // the topmost line has nothing above it to compare with
+            let above = i.checked_sub(1).filter(|_| self.diff_above).map(|j| &self.plines[j]);
+            let mut ix = self.hidden_prefix(pline);
             let mut broken_into = 0;
-            while ix < pline.chars.len() {
-                let max_len = if ix == 0 {
+            // an empty line still takes a row
+            while ix < pline.chars.len() || broken_into == 0 {
+                let max_len = if broken_into == 0 {
                     log_area.width as usize
                 } else {
-                    (log_area.width - self.indent_chars) as usize
+                    (log_area.width - indent) as usize
                 };
                 let len = max_len.min(pline.chars.len() - ix);
                 if skip > 0 {
@@ -1644,11 +4306,25 @@ impl LogrokInner {
                 } else {
                     let mut l = Line::default();
                     if broken_into != 0 {
-                        l.spans.push(Span::raw(self.indent.clone()));
+                        l.spans.push(Span::raw(" ".repeat(indent as usize)));
+                    }
+                    for i in ix..ix + len {
+                        let sc = &pline.chars[i];
+                        let mut style = sc.display_style();
+                        if pline_matches.is_some_and(|m| m[i]) {
+                            style = style.patch(preview);
+                        }
+                        if above.is_some_and(|a| a.chars.get(i).map(|c| c.c) != Some(sc.c)) {
+                            style = style.patch(changed);
+                        }
+                        if pline.hidden > 0 {
+                            style = style.fg(Color::DarkGray);
+                        }
+                        l.spans.push(Span::styled(sc.c.to_string(), style));
                     }
-                    for i in ix..ix + len {
-                        let sc = &pline.chars[i];
-                        l.spans.push(Span::styled(sc.c.to_string(), sc.style.style()));
+                    if !self.wrap && pline.cropped {
+                        l.spans.pop();
+                        l.spans.push(Span::raw(self.glyphs.truncated).dark_gray());
                     }
                     lines.push(l);
                     line_indexes.push(LineIndex {
@@ -1665,6 +4341,10 @@ impl LogrokInner {
             }
         }
         self.line_indexes = line_indexes;
+        if self.follow {
+            // scrolling away from the end pauses follow mode, coming back resumes it
+            self.follow_paused = !self.bottom_visible();
+        }
 
         /*
          * adjust cursor position if we don't have enough lines
@@ -1673,6 +4353,60 @@ impl LogrokInner {
             self.cursor_y = self.cursor_y.min(self.line_indexes.len() as i16 - 1);
             lD5!(MA, "adjusting cursor_y to {}", self.cursor_y);
         }
+        // after j/k, the cursor stays on the text of a shorter row
+        if let Some((want, _)) = self.desired_x {
+            if let Some(index) = self.line_indexes.get(self.cursor_y as usize) {
+                let (start, first) = match index.line_part {
+                    0 => (0, 0),
+                    _ => (indent as i16, 1),
+                };
+                let chars = lines[self.cursor_y as usize].spans.len() as i16 - first;
+                self.cursor_x = want.min(start + chars - 1).max(0);
+                self.desired_x = Some((want, self.cursor_x));
+            }
+        }
+
+        /*
+         * set the search match under the cursor apart from the others
+         */
+        if let Some((Some(pos), line_ix, _)) = self.resolve_cursor_position() {
+            let pline = &self.plines[line_ix];
+            if let Some(match_ix) = self.match_get_search_ix(pline, pos) {
+                for (row, index) in self.line_indexes.iter().enumerate() {
+                    if index.line_ix != line_ix {
+                        continue;
+                    }
+                    // continuation rows start with the indent
+                    let first = if index.line_part > 0 { 1 } else { 0 };
+                    for (k, span) in lines[row].spans.iter_mut().skip(first).enumerate() {
+                        let i = index.char_index + k;
+                        if i < pline.chars.len() && self.match_has_search_ix(pline, i, match_ix) {
+                            span.style = span.style.patch(current);
+                        }
+                    }
+                }
+            }
+        }
+
+        /*
+         * highlight the word under the cursor wherever it is on screen
+         */
+        let word = match self.resolve_cursor_position() {
+            Some((Some(pos), line_ix, _)) if self.highlight_word =>
+                self.word_at(line_ix, pos, self.patterns.delimiter(MatchType::SmallWord)),
+            _ => None,
+        };
+        if let Some(word) = word {
+            let found = self.word_occurrences(&word);
+            for (row, index) in self.line_indexes.iter().enumerate() {
+                let first = if index.line_part > 0 { 1 } else { 0 };
+                for (k, span) in lines[row].spans.iter_mut().skip(first).enumerate() {
+                    if found[index.line_ix].get(index.char_index + k) == Some(&true) {
+                        span.style = span.style.patch(occurrence);
+                    }
+                }
+            }
+        }
 
         lD3!(MA, "render: patterns: {:?}", self.patterns);
 
@@ -1683,65 +4417,144 @@ impl LogrokInner {
         for index in &self.line_indexes {
             let line = &self.plines[index.line_ix];
             let mut spans = Vec::new();
-            if index.line_part == 0 && self.lines.is_hidden(line.line_id) {
-                spans.push(Span::raw("H "));
-            } else if index.line_part == 0 &&
-                line.matches.iter().any(|&id| self.patterns.is_hiding(id))
-            {
-                spans.push(Span::raw("- "));
+            let glyphs = &self.glyphs;
+            if index.line_part == 0 && line.hidden > 0 {
+                spans.push(Span::raw(glyphs.collapsed).dark_gray());
+            } else if index.line_part == 0 && self.lines.is_hidden(line.line_id) {
+                spans.push(Span::raw(glyphs.hidden));
+            } else if index.line_part == 0 && self.patterns.hides(&line.matches) {
+                spans.push(Span::raw(glyphs.hidden_match));
             } else if index.line_part == 0 && self.lines.is_tagged(line.line_id) {
-                spans.push(Span::raw("T "));
+                spans.push(Span::raw(glyphs.tagged));
             } else if index.line_part == 0 &&
                 line.matches.iter().any(|&id| self.patterns.is_tagging(id))
             {
-                spans.push(Span::raw("* "));
+                spans.push(Span::raw(glyphs.tagged_match));
+            } else if index.line_part == 0 &&
+                self.lines.is_context_line(line.line_id, self.display_mode, &self.patterns)
+            {
+                // the line above on screen isn't the one above in the file
+                let above = index.line_ix.checked_sub(1).map(|i| self.plines[i].line_id);
+                let gap = above.is_some() && above != self.lines.prev_line(SearchType::Tag,
+                    line.line_id, &self.patterns, DisplayMode::All, false);
+                let glyph = if gap { glyphs.context_break } else { glyphs.context };
+                spans.push(Span::raw(glyph).dark_gray());
+            } else if index.line_part == 0 &&
+                self.file_starts.binary_search(&line.line_id).is_ok()
+            {
+                spans.push(Span::raw(glyphs.file_start));
             } else if index.line_part > 0 && self.overlong_fold.contains_key(&line.line_id) {
                 let (lines, first) = self.overlong_fold.get(&line.line_id).unwrap();
                 if index.line_part == 1 && *first > 0 {
-                    spans.push(Span::raw("F-"));
+                    spans.push(Span::raw(glyphs.fold_before));
                 } else if index.line_part == *lines - 1 && line.cropped {
-                    spans.push(Span::raw("F+"));
+                    spans.push(Span::raw(glyphs.fold_after));
                 } else {
-                    spans.push(Span::raw("F "));
+                    spans.push(Span::raw(glyphs.fold));
                 }
+            } else {
+                spans.push(Span::raw("  "));
             };
-            if self.display_offset && index.line_part == 0 {
-                let line_id_len = self.display_offset_len;
-                spans.push(Span::raw(format!("{:line_id_len$} ", line.line_id)).green());
+            if self.marker_width > MARKER_WIDTH {
+                spans.push(Span::raw(" ".repeat((self.marker_width - MARKER_WIDTH) as usize)));
+            }
+            if self.display_offset != OffsetDisplay::Off && index.line_part == 0 {
+                let len = self.display_offset_len;
+                let cursor_ix = self.line_indexes[self.cursor_y as usize].line_ix;
+                let offset = match self.display_offset {
+                    OffsetDisplay::Offset => Some(line.line_id),
+                    OffsetDisplay::Relative if index.line_ix != cursor_ix =>
+                        Some(index.line_ix.abs_diff(cursor_ix) as u64),
+                    // not waiting for the lines before to be counted, the next render
+                    // shows the number
+                    _ => self.lines.line_number(line.line_id, &self.patterns),
+                };
+                let offset = offset.map_or("?".to_string(), |o| o.to_string());
+                spans.push(Span::raw(format!("{:>len$} ", offset)).green());
             }
             markers.push(Line::from(spans));
         }
         while markers.len() < marker_area.height as usize {
-            markers.push(Line::from("~ "));
+            markers.push(Line::from(Span::raw(self.glyphs.filler).dim()));
+        }
+        // like vi, the rows after the end of the file are marked in the log area, too
+        while lines.len() < log_area.height as usize {
+            lines.push(Line::from(Span::raw(self.glyphs.filler.trim_end()).dim()));
         }
 
         /*
          * render input area
          */
+        if self.status_message.is_some() && self.message_at.is_none() {
+            self.message_at = Some(std::time::Instant::now());
+            if self.message_log.len() == MAX_MESSAGE_LOG {
+                self.message_log.pop_front();
+            }
+            self.message_log.extend(self.status_message.clone());
+        }
+        let message = self.status_message.clone().or_else(|| self.old_message.as_ref()
+            .filter(|(_, at)| at.elapsed() < MESSAGE_TIME)
+            .map(|(message, _)| message.clone()));
         let mut spans = Vec::new();
-        if self.focus == Focus::Search {
+        if self.focus == Focus::RegexTest {
+            spans.push(Span::raw("%"));
+            spans.push(Span::raw(self.regex_test.clone()));
+            spans.push(match regex_test {
+                Some(Some((_, count))) => Span::raw(format!("  ({} matches on screen)", count))
+                    .dark_gray(),
+                _ => Span::raw("  (invalid regex)").red(),
+            });
+        } else if self.focus == Focus::LogLevel {
+            spans.push(Span::raw("!"));
+            spans.push(Span::raw(self.log_spec.clone()));
+            spans.push(Span::raw("  (log levels like -l)").dark_gray());
+        } else if self.focus == Focus::Command {
+            spans.push(Span::raw(":"));
+            spans.push(Span::raw(self.command.clone()));
+        } else if self.focus == Focus::Search {
             if self.search_match_type == MatchType::Regex {
-                spans.push(Span::raw("&"));
+                if self.search_direction == Direction::Forward {
+                    spans.push(Span::raw("&"));
+                } else {
+                    spans.push(Span::raw("#"));
+                }
             } else if self.search_direction == Direction::Forward {
                 spans.push(Span::raw("/"));
             } else {
                 spans.push(Span::raw("?"));
             }
+            if self.search_ignore_case {
+                spans.push(Span::raw("i").dark_gray().bold());
+            }
             spans.push(Span::raw(self.current_search.clone()));
-        } else if let Some(ref message) = self.status_message {
-            spans.push(Span::raw(message.clone()).blue().bold());
+            if let Some(ref message) = message {
+                spans.push(Span::raw(format!("  ({})", message)).red());
+            }
+        } else if let Some(message) = message {
+            spans.push(Span::raw(message).blue().bold());
+        } else if let Some(info) = self.split_info().filter(|_| self.debug_splits) {
+            spans.push(Span::raw(info).dark_gray());
         } else {
             spans.push(Span::raw("^H").red().bold());
             spans.push(Span::raw(" Help "));
         }   
         let input = Line::from(spans);
-        self.status_message = None;
 
         /*
          * render status area
          */
         // current cursor position
         let cursor_pos = format!("{:3}:{:2} ", self.cursor_x, self.cursor_y);
+        // the char of the line under the cursor, counted in the whole line and not per row.
+        // A cropped line is longer than known
+        let column = match self.resolve_cursor_position() {
+            Some((Some(pos), line_ix, _)) => {
+                let pline = &self.plines[line_ix];
+                let more = if pline.cropped { "+" } else { "" };
+                format!(" col {}/{}{}", pos + 1, pline.chars.len(), more)
+            }
+            _ => String::new(),
+        };
 
         // current position
         let line_id = self.plines[self.line_indexes[self.cursor_y as usize].line_ix].line_id;
@@ -1754,12 +4567,37 @@ impl LogrokInner {
             DisplayMode::All    => "All   ",
             DisplayMode::Manual => "Manual",
         };
-        let status = vec![Line::from(vec![
+        let mut status = vec![
             Span::raw(cursor_pos),
             Span::raw(position),
             " Show ".into(),
             display_mode.red().bold(),
-        ])];
+        ];
+        self.counting = false;
+        if self.last_search.is_some() {
+            if let Some((count, complete)) = self.lines.match_count() {
+                let more = if complete { "" } else { "+" };
+                status.push(format!(" {}{} matches", count, more).into());
+                self.counting = !complete;
+            }
+        }
+        if self.follow && self.follow_paused {
+            status.push(" Paused".yellow().bold());
+        } else if self.follow {
+            status.push(" Follow".green().bold());
+        }
+        // last and only if there is room, so it never pushes out the fields before it
+        let width = status.iter().map(|span| span.width()).sum::<usize>();
+        if width + column.len() <= status_area.width as usize {
+            status.push(Span::raw(column));
+        }
+        let status = vec![Line::from(status)];
+        let flashing = self.alert_flash.is_some_and(|at| at.elapsed() < ALERT_FLASH);
+        let status_style = if flashing {
+            Style::default().fg(Color::White).bg(Color::Red)
+        } else {
+            Style::default().fg(Color::Black).bg(Color::Gray)
+        };
 
         Paragraph::new(lines)
             .render(log_area, buf);
@@ -1767,19 +4605,43 @@ impl LogrokInner {
         Paragraph::new(markers)
             .render(marker_area, buf);
 
+        if let Some(line_id) = self.pinned_line {
+            self.render_pinned(line_id, pin_area, log_area, buf);
+        }
+
+        if scrollbar_area.width > 0 {
+            self.render_scrollbar(scrollbar_area, buf);
+        }
+
+        if self.separator {
+            let rule = self.glyphs.separator.repeat(separator_area.width as usize);
+            Paragraph::new(Span::raw(rule).dark_gray())
+                .render(separator_area, buf);
+        }
+
         Paragraph::new(input)
             .style(Style::default().fg(Color::Black).bg(Color::Gray))
             .alignment(Alignment::Left)
             .render(input_area, buf);
 
         Paragraph::new(status)
-            .style(Style::default().fg(Color::Black).bg(Color::Gray))
+            .style(status_style)
             .alignment(Alignment::Right)
             .render(status_area, buf);
 
         if self.focus == Focus::Search {
+            let prefix = if self.search_ignore_case { 2 } else { 1 };
+            self.render_cursor =
+                (input_area.x + self.current_search.len() as u16 + prefix, input_area.y);
+        } else if self.focus == Focus::RegexTest {
+            self.render_cursor =
+                (input_area.x + self.regex_test.chars().count() as u16 + 1, input_area.y);
+        } else if self.focus == Focus::LogLevel {
             self.render_cursor =
-                (input_area.x + self.current_search.len() as u16 + 1, input_area.y);
+                (input_area.x + self.log_spec.chars().count() as u16 + 1, input_area.y);
+        } else if self.focus == Focus::Command {
+            self.render_cursor =
+                (input_area.x + self.command.chars().count() as u16 + 1, input_area.y);
         } else {
             self.render_cursor =
                 (log_area.x + self.cursor_x as u16, log_area.y + self.cursor_y as u16);
@@ -1793,46 +4655,16 @@ impl LogrokInner {
         self.input_area = input_area;
         self.input_content = input_content;
 
-        if self.focus == Focus::Help && main_area.height > 4 {
-            let max_area = Rect::new(2, 2, main_area.width - 4, main_area.height - 4);
-            let _max_width = max_area.width as usize;
-            let max_height = max_area.height as usize;
-            /*
-            let cols = if self.help.lines <= max_height {
-                1
-            } else if self.help.columns * 2 + 2 <= max_width {
-                2
-            } else {
-                1
-            };
-            */
-            let cols = 1;
-            let (width, height) = if cols == 1 {
-                (self.help.columns + 2, self.help.lines.min(max_height - 2) + 2)
-            } else {
-                (2 * self.help.columns + 4, (self.help.lines + 1) / 2 + 2)
-            };
-            let vertical = Layout::vertical(
-                [Constraint::Fill(1), Constraint::Length(height as u16), Constraint::Fill(1)]);
-            let [_, help_vertical, _] = vertical.areas(max_area);
-            let horizontal = Layout::horizontal(
-                [Constraint::Fill(1), Constraint::Length(width as u16), Constraint::Fill(1)]);
-            let [_, help_area, _] = horizontal.areas(help_vertical);
-            Clear::default()
-                .render(help_area, buf);
-            let block = Block::default()
-                .padding(Padding::uniform(1))
-                .style(Style::default().fg(Color::Black).bg(Color::LightGreen))
-                .title_bottom(self.help.bottom.clone());
-            let block_inner = block.inner(help_area);
-            block.render(help_area, buf);
-            let mut lines = self.help.help.iter().map(|x| x.clone()).collect::<Vec<_>>();
-            if lines.len() - self.help_first_line < block_inner.height as usize {
-                self.help_first_line = lines.len() - block_inner.height as usize;
-            }
-            lines.drain(0..self.help_first_line);
-            Paragraph::new(lines)
-                .render(block_inner, buf);
+        if main_area.height > 4 {
+            match self.focus {
+                Focus::Help => self.help.render(&mut self.help_first_line, main_area, buf),
+                Focus::Overlay | Focus::TagList | Focus::PatternList =>
+                    if let Some(ref overlay) = self.overlay
+                {
+                    overlay.render(&mut self.overlay_first_line, main_area, buf);
+                }
+                _ => (),
+            }
         }
     }
 }
@@ -1844,35 +4676,57 @@ impl Logrok {
     }
 
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
-        let (tx_req, rx_req) = std::sync::mpsc::channel();
-        let (tx_rsp, rx_rsp) = std::sync::mpsc::channel();
+        // only one request is in flight at a time. Events that queue up meanwhile are
+        // coalesced in poll_events, so the worker doesn't lag behind the input
+        let (tx_req, rx_req) = std::sync::mpsc::sync_channel(1);
+        let (tx_rsp, rx_rsp) = std::sync::mpsc::sync_channel(1);
         let s = self.clone();
         let jh = std::thread::spawn(move || {
             loop {
-                let Ok((event, area)) = rx_req.recv() else {
+                let Ok((event, area, accel_rows)) = rx_req.recv() else {
                     break;
                 };
                 let mut inner = s.inner.lock().unwrap();
-                inner.process_event(area, Some(event));
+                inner.accel_rows = accel_rows;
+                inner.process_event(area, event);
                 tx_rsp.send(()).unwrap();
             }
         });
         let mut inner = self.inner.lock().unwrap();
         let filesearch = inner.lines.get_file_search();
+        let progress = inner.progress.clone();
         inner.process_event(Self::area(terminal)?, None);
         while !inner.exit {
             let input_area = inner.input_area; // XXX progress hack
+            // in follow mode, wake up regularly to look for new lines. Same while matches
+            // are counted, to show the progress
+            let timeout = (inner.follow || inner.counting || inner.counting_lines)
+                .then_some(FOLLOW_INTERVAL);
+            // and once more when an old message goes away
+            let expires = inner.old_message.as_ref()
+                .and_then(|(_, at)| MESSAGE_TIME.checked_sub(at.elapsed()));
+            let timeout = match (timeout, expires) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
             drop(inner);
             terminal.draw(|frame| self.draw(frame))?;
-            let event = self.poll_events()?;
+            let event = self.poll_events(timeout)?;
             let area = Self::area(terminal)?;
-            tx_req.send((event, area)).unwrap();
+            // timed here, as the worker might lag behind
+            let accel_rows = self.accel.rows(event.as_ref());
+            tx_req.send((event, area, accel_rows)).unwrap();
             let mut need_restore = false;
             loop {
                 match rx_rsp.recv_timeout(std::time::Duration::from_millis(200)) {
                     Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
-                        let progress = filesearch.get_progress();
-                        draw_progress(progress, input_area, terminal)?;
+                        let message = match progress.get() {
+                            Some((what, fraction)) =>
+                                format!("{}... {:.2}%", what, fraction * 100.0),
+                            None => format!("Processing... {:.2}%",
+                                filesearch.get_progress() * 100.0),
+                        };
+                        draw_progress(&message, input_area, terminal)?;
                         need_restore = true;
                     },
                     Err(e) => return Err(e.into()),
@@ -1883,12 +4737,25 @@ impl Logrok {
             if need_restore {
                 restore_progress(terminal, input_area, &inner.input_content)?;
             }
+            if std::mem::take(&mut inner.bell) {
+                let b = terminal.backend_mut();
+                b.write_all(b"\x07")?;
+                std::io::Write::flush(b)?;
+            }
             if inner.exit {
                 break;
             }
         }
+        let interrupted = inner.interrupted;
+        if !interrupted && inner.keep_state {
+            inner.save_state()?;
+        }
+        drop(inner);
         drop(tx_req);
         jh.join().unwrap();
+        if interrupted {
+            return Err(Exit::Interrupted.into());
+        }
         Ok(())
     }
 
@@ -1898,19 +4765,60 @@ impl Logrok {
         frame.set_cursor_position(cursor);
     }
 
-    fn poll_events(&mut self) -> io::Result<Event> {
-        let event = loop {
-            let event = event::read()?;
-            lD1!(MA, "event: {:?}", event);
-            match event {
-                // it's important to check that the event is a key press event as
-                // crossterm also emits key release and repeat events on Windows.
-                Event::Key(_) |
-                Event::Resize(_, _) => break event,
-                _ => (),
-            };
+    // wait for the next event. Returns None if there was none within the timeout
+    fn poll_events(&mut self, timeout: Option<std::time::Duration>)
+        -> io::Result<Option<Event>>
+    {
+        let mut event = match self.pending_event.take() {
+            Some(event) => event,
+            None => loop {
+                if let Some(timeout) = timeout {
+                    if !event::poll(timeout)? {
+                        return Ok(None);
+                    }
+                }
+                let event = event::read()?;
+                if Self::relevant(&event) {
+                    break event;
+                }
+            },
         };
-        Ok(event)
+        // collapse held movement keys and resizes that piled up while we were busy. Terminals
+        // mostly send a held key as presses, only some as repeats. A resize is never dropped,
+        // only superseded by a later one
+        while event::poll(std::time::Duration::ZERO)? {
+            let next = event::read()?;
+            if !Self::relevant(&next) {
+                continue;
+            }
+            let coalesce = match (&event, &next) {
+                (Event::Resize(_, _), Event::Resize(_, _)) => true,
+                (Event::Key(a), Event::Key(b)) => a == b && (a.kind == KeyEventKind::Repeat
+                    || self.inner.lock().unwrap().repeats_movement(a)),
+                _ => false,
+            };
+            if !coalesce {
+                self.pending_event = Some(next);
+                break;
+            }
+            lD3!(MA, "coalescing event: {:?}", next);
+            event = next;
+        }
+        lD1!(MA, "event: {:?}", event);
+        Ok(Some(event))
+    }
+
+    fn relevant(event: &Event) -> bool {
+        // it's important to check that the event is a key press event as
+        // crossterm also emits key release and repeat events on Windows.
+        // Of the mouse, only the wheel and left clicks are used
+        match event {
+            Event::Key(_) | Event::Resize(_, _) => true,
+            Event::Mouse(mouse_event) => matches!(mouse_event.kind,
+                MouseEventKind::ScrollUp | MouseEventKind::ScrollDown |
+                MouseEventKind::Down(MouseButton::Left)),
+            _ => false,
+        }
     }
 }
 
@@ -1921,10 +4829,10 @@ impl Widget for &Logrok {
     }
 }
 
-fn draw_progress(progress: f32, area: Rect, terminal: &mut DefaultTerminal) -> Result<()> {
+fn draw_progress(message: &str, area: Rect, terminal: &mut DefaultTerminal) -> Result<()> {
     terminal.hide_cursor()?;
     let b = terminal.backend_mut();
-    let message = format!("Processing... {:.2}%", progress * 100.0);
+    let message = message.to_string();
     let mut spans = Vec::new();
         spans.push(Span::raw(message).blue().bold());
     let input = Line::from(spans);
@@ -1947,6 +4855,82 @@ fn draw_progress(progress: f32, area: Rect, terminal: &mut DefaultTerminal) -> R
     Ok(())
 }
 
+// a huge file takes a while and a lot of memory to index, so ask first. Without a terminal
+// to ask on, it's opened anyway
+fn confirm_open(filename: &OsStr, confirm_size: u64, chunk_size: u64) -> Result<bool> {
+    let len = std::fs::metadata(filename)?.len();
+    if len <= confirm_size || !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Ok(true);
+    }
+    eprint!("{} is {:.1} GiB, about {} splits to index. Open it anyway? [y/N] ",
+        filename.to_string_lossy(), len as f64 / (1u64 << 30) as f64,
+        len.div_ceil(chunk_size));
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+// byte range and number of lines of each split, one per line
+fn dump_splits<W: Write>(filename: &OsStr, chunk_size: u64, out: &mut W) -> Result<()> {
+    let split_cache = SplitCache::new(filename, NonZeroUsize::MIN, chunk_size)?;
+    let patterns = PatternSet::new(MarkStyle::new());
+    let num_splits = split_cache.num_splits();
+    for split_id in 0..num_splits {
+        let Some((start, end)) = split_cache.get_split(split_id) else {
+            return Err(anyhow::anyhow!("split {} not found", split_id));
+        };
+        let split = split_cache.get(split_id, &patterns)?;
+        writeln!(out, "split {}/{} {}..{} {} bytes {} lines", split_id, num_splits, start, end,
+            end - start, split.line_ends.len())?;
+    }
+
+    Ok(())
+}
+
+// mouse capture is turned off again however the terminal is left, early errors included.
+// The panic hook exits without unwinding, so it turns it off itself
+struct MouseCapture;
+
+impl MouseCapture {
+    fn enable() -> Result<Self> {
+        crossterm::execute!(io::stdout(), EnableMouseCapture)?;
+        Ok(MouseCapture)
+    }
+}
+
+impl Drop for MouseCapture {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(io::stdout(), DisableMouseCapture);
+    }
+}
+
+// show how much of the input has been read until it's complete. ^C aborts
+fn wait_for_spool(spool: &Spool, what: &str, terminal: &mut DefaultTerminal) -> Result<()> {
+    loop {
+        if let Some(result) = spool.result() {
+            result?;
+            break;
+        }
+        let size = terminal.size()?;
+        let message = format!("Reading {}... {:.1} MiB", what,
+            spool.bytes() as f64 / (1024.0 * 1024.0));
+        draw_progress(&message, Rect::new(0, size.height - 1, size.width, 1), terminal)?;
+        if event::poll(std::time::Duration::from_millis(200))? {
+            if let Event::Key(key) = event::read()? {
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    return Err(Exit::Interrupted.into());
+                }
+            }
+        }
+    }
+    if spool.bytes() == 0 {
+        return Err(anyhow::anyhow!("No input from {}", what));
+    }
+
+    Ok(())
+}
+
 fn restore_progress(terminal: &mut DefaultTerminal, area: Rect, contents: &Vec<Cell>) -> Result<()>
 {
     let b = terminal.backend_mut();
@@ -1961,55 +4945,211 @@ fn restore_progress(terminal: &mut DefaultTerminal, area: Rect, contents: &Vec<C
     Ok(())
 }
 
+// a scrollable popup over the main area, like the help text
 #[derive(Debug)]
-struct Help {
-    help: Vec<Line<'static>>,
+struct Overlay {
+    text: Vec<Line<'static>>,
     lines: usize,
     columns: usize,
     bottom: Line<'static>,
+    selected: Option<usize>,    // highlighted line in selection lists
+}
+
+impl Overlay {
+    fn new(text: Vec<Line<'static>>, bottom: Line<'static>) -> Self {
+        let mut columns = 0;
+        for line in &text {
+            let mut len = 0;
+            for span in line {
+                len += span.content.chars().count();
+            }
+            columns = columns.max(len);
+        }
+        Overlay {
+            lines: text.len(),
+            text,
+            columns,
+            bottom,
+            selected: None,
+        }
+    }
+
+    fn render(&self, first_line: &mut usize, main_area: Rect, buf: &mut Buffer) {
+        let max_area = Rect::new(2, 2, main_area.width - 4, main_area.height - 4);
+        let _max_width = max_area.width as usize;
+        let max_height = max_area.height as usize;
+        /*
+        let cols = if self.lines <= max_height {
+            1
+        } else if self.columns * 2 + 2 <= max_width {
+            2
+        } else {
+            1
+        };
+        */
+        let cols = 1;
+        let (width, height) = if cols == 1 {
+            (self.columns + 2, self.lines.min(max_height - 2) + 2)
+        } else {
+            (2 * self.columns + 4, (self.lines + 1) / 2 + 2)
+        };
+        let vertical = Layout::vertical(
+            [Constraint::Fill(1), Constraint::Length(height as u16), Constraint::Fill(1)]);
+        let [_, overlay_vertical, _] = vertical.areas(max_area);
+        let horizontal = Layout::horizontal(
+            [Constraint::Fill(1), Constraint::Length(width as u16), Constraint::Fill(1)]);
+        let [_, overlay_area, _] = horizontal.areas(overlay_vertical);
+        Clear::default()
+            .render(overlay_area, buf);
+        let block = Block::default()
+            .padding(Padding::uniform(1))
+            .style(Style::default().fg(Color::Black).bg(Color::LightGreen))
+            .title_bottom(self.bottom.clone());
+        let block_inner = block.inner(overlay_area);
+        block.render(overlay_area, buf);
+        let mut lines = self.text.iter().map(|x| x.clone()).collect::<Vec<_>>();
+        let height = block_inner.height as usize;
+        if let Some(selected) = self.selected {
+            // keep the selection on screen
+            if selected < *first_line {
+                *first_line = selected;
+            } else if selected >= *first_line + height {
+                *first_line = selected + 1 - height;
+            }
+            lines[selected] = lines[selected].clone().patch_style(Style::default().reversed());
+        }
+        if lines.len().saturating_sub(*first_line) < height {
+            *first_line = lines.len().saturating_sub(height);
+        }
+        lines.drain(0..*first_line);
+        Paragraph::new(lines)
+            .render(block_inner, buf);
+    }
+}
+
+fn build_text_overlay(text: Vec<Line<'static>>) -> Overlay {
+    let key = Style::default().bold();
+    let bottom = Line::from(vec![
+            Span::styled("j", key), Span::raw("/"),
+            Span::styled("k", key),
+            Span::raw(": scroll "),
+            Span::styled("q", key),
+            Span::raw(": close"),
+    ]).alignment(Alignment::Center);
+
+    Overlay::new(text, bottom)
+}
+
+fn build_hexdump(offset: LineId, bytes: &[u8]) -> Overlay {
+    let offset_style = Style::default().fg(Color::DarkGray);
+    let mut text = Vec::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let mut hex = String::new();
+        let mut ascii = String::new();
+        for j in 0..16 {
+            if j == 8 {
+                hex.push(' ');
+            }
+            if let Some(&b) = chunk.get(j) {
+                hex.push_str(&format!(" {:02x}", b));
+                ascii.push(if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' });
+            } else {
+                hex.push_str("   ");
+            }
+        }
+        text.push(Line::from(vec![
+            Span::styled(format!("{:08x}", offset + i as LineId * 16), offset_style),
+            Span::raw(hex),
+            Span::raw(format!("  |{}|", ascii)),
+        ]));
+    }
+
+    build_text_overlay(text)
 }
 
-fn build_help() -> Help {
+fn build_help() -> Overlay {
         /*
            Movement
            h/j/k/l: left/down/up/right
            cursor keys: left/down/up/right
            H/J/K/L: left/down/up/right (faster)
+           alt-l/alt-h: scroll truncated lines half a screen right/left, alt-L/alt-H a screen
            w/W/b/B: next/previous word/WORD
+           e/E: end of word/WORD, ge/gE: end of previous word/WORD
+             word_end in the config makes w/W/b/B go to ends
+             a count like 10j repeats a movement
+             holding j/k speeds up, see accel in the config
            ^e/^y: scroll up/down one line
+             with --mouse, the wheel scrolls and a click moves the cursor,
+             a click on the markers tags the line
            ^d/^u: scroll up/down half a page
            ^b/^f: scroll up/down a page
-           g/G: go to start/end of file
+           gg/G: go to start/end of file, Ngg/NG to line N
            0/$: go to start/end of line
-           alt-e/y/d/u/b/f: scroll folded lines
+           ]l/[l: next/previous line longer than the screen
+           `a/'a: set bookmark a (a-z)/go to bookmark a
+           alt-e/y/d/u/b/f: scroll folded lines, N times with a count
 
            Marking
            m/M: toggle mark word/WORD under cursor
+           *: toggle mark of the text between spaces, also inside words
            >/<: extend marking to right/left
+           alt-./alt-<: extend marking to right/left by a word, alt-,/alt-> to shrink it
+           O: highlight the word under the cursor on screen while moving, Esc to stop
+           _: toggle mark of the field (WORD) in the cursor column
+           |: lock the column, j/k then move by lines and keep it
 
            Tagging/Hiding
-           t/x: toggle tag/hide match under cursor
-                or full line if not on a match
-           T/X: toggle tag/hide full line only
-           c: cycle color of mark
+           t/x: toggle tag/hide match under cursor, or full line if not on a match
+           T/X: toggle tag/hide full line
+           ~: hide all lines not matching the mark under cursor. With more
+             of them, lines matching any are shown unless hidden otherwise
+           p: tag mark under or nearest to cursor
+           ^T: list tagged lines
+           ]t/[t: next/previous tagged line, in any display mode
+           ^G: show the last messages
+           ^P: manage patterns: switch off and on, recolor, change mode, delete
+           c/C: cycle color of mark
 
            Searching
-           //?: search forward/backward
-           &: regex search (forward)
+           //?: search forward/backward, shown while typing, Esc cancels
+             a leading ^ or trailing $ anchors to line start/end
+             Tab toggles ignoring case, shown as i
+             Up/Down recall earlier searches
+           &/#: regex search forward/backward
+           %: try a regex on the screen, Enter searches, ^T tags
            n/N: next/previous search match
+           \: clear search, keep marks
 
            Display
-           f: show All->Normal->Tagged->Manual
-           d: show Manual->Tagged->Normal->All
-           @: toggle display of line offsets
+           f/d: show All->Normal->Tagged->Manual/the other way round
+           @: cycle offsets/line numbers/relative numbers
            F: fold current (overlong) line
-           +/-: increase/decrease fold size
+           +/-: in-/decrease fold size, by N rows with a count (N+)
+           v: toggle wrapping of long lines, truncated they scroll sideways
            i: set indent column
+           ^X: show hex dump of current line
+           I: show size, lines and active patterns of the file
+           ^W: toggle following the end of a growing file
+           R: reload the file, from the top if it was truncated or replaced
+           P: pin current line to the top, again to unpin
+           D: toggle highlighting of changes to the line above
+           {/}: fewer/more context lines around tagged lines (N} for N more)
+           Z: toggle collapsing runs of hidden lines into one row
+           z: show the lines of the collapsed run under the cursor
 
            Various
            u/^R: undo/redo
+           y: copy mark under cursor or current line to the clipboard
+           =: show an anchor of the current line for --goto-anchor
+           S/A: save current view as html/with ansi colors
+           Y: save the lines of the current view as they are in the file
            q: quit
+           ^C: quit with exit code 130
            ^H: toggle display of this help
+           !: change log levels, like -l, with --output
+           :: command, :q :w[!] FILE :N :set indent=N :set [no]wrap :set [no]center :nohl
+             :clear :e
         */
 
     let text = Style::default();
@@ -2035,16 +5175,52 @@ fn build_help() -> Help {
             Span::styled("K", key), sep.clone(),
             Span::styled("L", key),
             Span::styled(": left/down/up/right (faster)", text)]),
+        Line::from(vec![
+            Span::styled("alt-l", key), sep.clone(),
+            Span::styled("alt-h", key),
+            Span::styled(": scroll truncated lines half a screen right/left, ", text),
+            Span::styled("alt-L", key), sep.clone(),
+            Span::styled("alt-H", key),
+            Span::styled(" a screen", text)]),
         Line::from(vec![
             Span::styled("w", key), sep.clone(),
             Span::styled("W", key), sep.clone(),
             Span::styled("b", key), sep.clone(),
             Span::styled("B", key),
             Span::styled(": next/previous word/WORD", text)]),
+        Line::from(vec![
+            Span::styled("e", key), sep.clone(),
+            Span::styled("E", key),
+            Span::styled(": end of word/WORD, ", text),
+            Span::styled("ge", key), sep.clone(),
+            Span::styled("gE", key),
+            Span::styled(": end of previous word/WORD", text)]),
+        Line::from(vec![
+            Span::styled("  word_end in the config makes ", text),
+            Span::styled("w", key), sep.clone(),
+            Span::styled("W", key), sep.clone(),
+            Span::styled("b", key), sep.clone(),
+            Span::styled("B", key),
+            Span::styled(" go to ends", text)]),
+        Line::from(vec![
+            Span::styled("  a count like ", text),
+            Span::styled("10j", key),
+            Span::styled(" repeats a movement", text)]),
+        Line::from(vec![
+            Span::styled("  holding ", text),
+            Span::styled("j", key), sep.clone(),
+            Span::styled("k", key),
+            Span::styled(" speeds up, see accel in the config", text)]),
         Line::from(vec![
             Span::styled("^e", key), sep.clone(),
             Span::styled("^y", key),
             Span::styled(": scroll up/down one line", text)]),
+        Line::from(vec![
+            Span::styled("  with ", text),
+            Span::styled("--mouse", key),
+            Span::styled(", the wheel scrolls and a click moves the cursor,", text)]),
+        Line::from(vec![
+            Span::styled("  a click on the markers tags the line", text)]),
         Line::from(vec![
             Span::styled("^d", key), sep.clone(),
             Span::styled("^u", key),
@@ -2054,13 +5230,24 @@ fn build_help() -> Help {
             Span::styled("^f", key),
             Span::styled(": scroll up/down a page", text)]),
         Line::from(vec![
-            Span::styled("g", key), sep.clone(),
+            Span::styled("gg", key), sep.clone(),
             Span::styled("G", key),
-            Span::styled(": go to start/end of file", text)]),
+            Span::styled(": go to start/end of file, ", text),
+            Span::styled("Ngg", key), sep.clone(),
+            Span::styled("NG", key),
+            Span::styled(" to line N", text)]),
         Line::from(vec![
             Span::styled("0", key), sep.clone(),
             Span::styled("$", key),
             Span::styled(": go to start/end of line", text)]),
+        Line::from(vec![
+            Span::styled("]l", key), sep.clone(),
+            Span::styled("[l", key),
+            Span::styled(": next/previous line longer than the screen", text)]),
+        Line::from(vec![
+            Span::styled("`a", key), sep.clone(),
+            Span::styled("'a", key),
+            Span::styled(": set bookmark a (a-z)/go to bookmark a", text)]),
             Line::from(vec![
             Span::styled("alt-e", key), sep.clone(),
             Span::styled("y", key), sep.clone(),
@@ -2068,27 +5255,72 @@ fn build_help() -> Help {
             Span::styled("u", key), sep.clone(),
             Span::styled("b", key), sep.clone(),
             Span::styled("f", key),
-            Span::styled(": scroll folded lines", text)]),
+            Span::styled(": scroll folded lines, N times with a count", text)]),
         Line::from(vec![]),
         Line::from(vec![Span::styled("Marking", heading)]).alignment(Alignment::Center),
         Line::from(vec![
             Span::styled("m", key), sep.clone(),
             Span::styled("M", key),
             Span::styled(": toggle mark word/WORD under cursor", text)]),
+        Line::from(vec![
+            Span::styled("*", key),
+            Span::styled(": toggle mark of the text between spaces, also inside words", text)]),
         Line::from(vec![
             Span::styled(">", key), sep.clone(),
             Span::styled("<", key),
             Span::styled(": extend marking to right/left", text)]),
+        Line::from(vec![
+            Span::styled("alt-.", key), sep.clone(),
+            Span::styled("alt-<", key),
+            Span::styled(": extend marking to right/left by a word, ", text),
+            Span::styled("alt-,", key), sep.clone(),
+            Span::styled("alt->", key),
+            Span::styled(" to shrink it", text)]),
+        Line::from(vec![
+            Span::styled("O", key),
+            Span::styled(": highlight the word under the cursor on screen while moving, ", text),
+            Span::styled("Esc", key),
+            Span::styled(" to stop", text)]),
+        Line::from(vec![
+            Span::styled("_", key),
+            Span::styled(": toggle mark of the field (WORD) in the cursor column", text)]),
+        Line::from(vec![
+            Span::styled("|", key),
+            Span::styled(": lock the column, j/k then move by lines and keep it", text)]),
         Line::from(vec![]),
         Line::from(vec![Span::styled("Tagging/Hiding", heading)]).alignment(Alignment::Center),
         Line::from(vec![
             Span::styled("t", key), sep.clone(),
             Span::styled("x", key),
-            Span::styled(": toggle tag/hide match under cursor", text)]),
+            Span::styled(": toggle tag/hide match under cursor, or full line if not on a match",
+                text)]),
         Line::from(vec![
-            Span::styled("t", key), sep.clone(),
-            Span::styled("x", key),
+            Span::styled("T", key), sep.clone(),
+            Span::styled("X", key),
             Span::styled(": toggle tag/hide full line", text)]),
+        Line::from(vec![
+            Span::styled("~", key),
+            Span::styled(": hide all lines not matching the mark under cursor. With more", text)]),
+        Line::from(vec![
+            Span::styled("  ", key),
+            Span::styled("of them, lines matching any are shown unless hidden otherwise", text)]),
+        Line::from(vec![
+            Span::styled("p", key),
+            Span::styled(": tag mark under or nearest to cursor", text)]),
+        Line::from(vec![
+            Span::styled("^T", key),
+            Span::styled(": list tagged lines", text)]),
+        Line::from(vec![
+            Span::styled("]t", key), sep.clone(),
+            Span::styled("[t", key),
+            Span::styled(": next/previous tagged line, in any display mode", text)]),
+        Line::from(vec![
+            Span::styled("^G", key),
+            Span::styled(": show the last messages", text)]),
+        Line::from(vec![
+            Span::styled("^P", key),
+            Span::styled(": manage patterns: switch off and on, recolor, change mode, delete",
+                text)]),
         Line::from(vec![
             Span::styled("c", key), sep.clone(),
             Span::styled("C", key),
@@ -2098,46 +5330,133 @@ fn build_help() -> Help {
         Line::from(vec![
             Span::styled("/", key), sep.clone(),
             Span::styled("?", key),
-            Span::styled(": search forward/backward", text)]),
+            Span::styled(": search forward/backward, shown while typing, ", text),
+            Span::styled("Esc", key),
+            Span::styled(" cancels", text)]),
+        Line::from(vec![
+            Span::styled("  a leading ", text),
+            Span::styled("^", key),
+            Span::styled(" or trailing ", text),
+            Span::styled("$", key),
+            Span::styled(" anchors to line start/end", text)]),
+        Line::from(vec![
+            Span::styled("  Tab", key),
+            Span::styled(" toggles ignoring case, shown as ", text),
+            Span::styled("i", key)]),
+        Line::from(vec![
+            Span::styled("  Up", key), Span::styled("/", text),
+            Span::styled("Down", key),
+            Span::styled(" recall earlier searches", text)]),
+        Line::from(vec![
+            Span::styled("&", key), sep.clone(),
+            Span::styled("#", key),
+            Span::styled(": regex search forward/backward", text)]),
         Line::from(vec![
-            Span::styled("&", key),
-            Span::styled(": regex search (forward)", text)]),
+            Span::styled("%", key),
+            Span::styled(": try a regex on the screen, ", text),
+            Span::styled("Enter", key),
+            Span::styled(" searches, ", text),
+            Span::styled("^T", key),
+            Span::styled(" tags", text)]),
         Line::from(vec![
             Span::styled("n", key), sep.clone(),
             Span::styled("N", key),
             Span::styled(": next/previous search match", text)]),
+        Line::from(vec![
+            Span::styled("\\", key),
+            Span::styled(": clear search, keep marks", text)]),
         Line::from(vec![]),
         Line::from(vec![Span::styled("Display", heading)]).alignment(Alignment::Center),
         Line::from(vec![
-            Span::styled("f", key),
-            Span::styled(": toggle display of only tagged lines", text)]),
-        Line::from(vec![
-            Span::styled("F", key),
-            Span::styled(": toggle display of hidden lines", text)]),
+            Span::styled("f", key), sep.clone(),
+            Span::styled("d", key),
+            Span::styled(": show All->Normal->Tagged->Manual/the other way round", text)]),
         Line::from(vec![
             Span::styled("@", key),
-            Span::styled(": toggle display of line offsets", text)]),
+            Span::styled(": cycle offsets/line numbers/relative numbers", text)]),
         Line::from(vec![
-            Span::styled("o", key),
+            Span::styled("F", key),
             Span::styled(": fold current (overlong) line", text)]),
         Line::from(vec![
             Span::styled("+", key), sep.clone(),
             Span::styled("-", key),
-            Span::styled(": in-/decrease fold size", text)]),
+            Span::styled(": in-/decrease fold size, by N rows with a count (N+)", text)]),
+        Line::from(vec![
+            Span::styled("v", key),
+            Span::styled(": toggle wrapping of long lines, truncated they scroll sideways", text)]),
         Line::from(vec![
             Span::styled("i", key),
             Span::styled(": set indent column", text)]),
+        Line::from(vec![
+            Span::styled("^X", key),
+            Span::styled(": show hex dump of current line", text)]),
+        Line::from(vec![
+            Span::styled("I", key),
+            Span::styled(": show size, lines and active patterns of the file", text)]),
+        Line::from(vec![
+            Span::styled("^W", key),
+            Span::styled(": toggle following the end of a growing file", text)]),
+        Line::from(vec![
+            Span::styled("R", key),
+            Span::styled(": reload the file, from the top if it was truncated or replaced", text)]),
+        Line::from(vec![
+            Span::styled("P", key),
+            Span::styled(": pin current line to the top, again to unpin", text)]),
+        Line::from(vec![
+            Span::styled("D", key),
+            Span::styled(": toggle highlighting of changes to the line above", text)]),
+        Line::from(vec![
+            Span::styled("{", key), sep.clone(),
+            Span::styled("}", key),
+            Span::styled(": fewer/more context lines around tagged lines (", text),
+            Span::styled("N}", key),
+            Span::styled(" for N more)", text)]),
+        Line::from(vec![
+            Span::styled("Z", key),
+            Span::styled(": toggle collapsing runs of hidden lines into one row", text)]),
+        Line::from(vec![
+            Span::styled("z", key),
+            Span::styled(": show the lines of the collapsed run under the cursor", text)]),
         Line::from(vec![]),
         Line::from(vec![Span::styled("Various", heading)]).alignment(Alignment::Center),
         Line::from(vec![
-            Span::styled("u", key),
-            Span::styled(": undo", text)]),
+            Span::styled("u", key), sep.clone(),
+            Span::styled("^R", key),
+            Span::styled(": undo/redo", text)]),
+        Line::from(vec![
+            Span::styled("y", key),
+            Span::styled(": copy mark under cursor or current line to the clipboard", text)]),
+        Line::from(vec![
+            Span::styled("=", key),
+            Span::styled(": show an anchor of the current line for ", text),
+            Span::styled("--goto-anchor", key)]),
+        Line::from(vec![
+            Span::styled("S", key), sep.clone(),
+            Span::styled("A", key),
+            Span::styled(": save current view as html/with ansi colors", text)]),
+        Line::from(vec![
+            Span::styled("Y", key),
+            Span::styled(": save the lines of the current view as they are in the file", text)]),
         Line::from(vec![
             Span::styled("q", key),
             Span::styled(": quit", text)]),
+        Line::from(vec![
+            Span::styled("^C", key),
+            Span::styled(": quit with exit code 130", text)]),
         Line::from(vec![
             Span::styled("^H", key),
             Span::styled(": toggle display of this help", text)]),
+        Line::from(vec![
+            Span::styled("!", key),
+            Span::styled(": change log levels, like ", text),
+            Span::styled("-l", key),
+            Span::styled(", with ", text),
+            Span::styled("--output", key)]),
+        Line::from(vec![
+            Span::styled(":", key),
+            Span::styled(": command, ", text),
+            Span::styled(":q :w[!] FILE :N :set indent=N :set [no]wrap :set [no]center ", key),
+            Span::styled(":nohl :clear :e", key)]),
     ];
     let bottom = Line::from(vec![
             Span::styled("j", key), sep.clone(),
@@ -2146,41 +5465,309 @@ fn build_help() -> Help {
             Span::styled("q", key),
             Span::styled(": close help", text),
     ]).alignment(Alignment::Center);
-    let mut columns = 0;
-    for line in &help {
-        let mut len = 0;
-        for span in line {
-            len += span.content.chars().count();
+    Overlay::new(help, bottom)
+}
+
+/// Reasons to leave with a specific exit code instead of the generic error code
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Exit {
+    NotFound,
+    NoMatches,
+    Interrupted,
+}
+
+impl Exit {
+    // keep in sync with EXIT_CODES_HELP
+    fn code(&self) -> u8 {
+        match self {
+            Exit::NotFound => 3,
+            Exit::NoMatches => 4,
+            Exit::Interrupted => 130,
         }
-        columns = columns.max(len);
     }
-    Help {
-        lines: help.len(),
-        help,
-        columns,
-        bottom,
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Exit::NotFound => write!(f, "file not found"),
+            Exit::NoMatches => write!(f, "no matches"),
+            Exit::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl std::error::Error for Exit {}
+
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+    0    normal quit
+    1    error
+    2    invalid command line
+    3    file not found
+    4    no matches, --export-ansi had no line to write
+    130  interrupted (^C)";
+
+// like grep, no matches and ^C are told by the exit code alone
+fn quiet_exit(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<Exit>(), Some(Exit::NoMatches | Exit::Interrupted))
+}
+
+fn exit_code(err: &anyhow::Error) -> u8 {
+    match err.downcast_ref::<Exit>() {
+        Some(exit) => exit.code(),
+        None => 1,
+    }
+}
+
+// only a missing log file is "file not found", not a missing config or key file
+fn check_log_file(name: &str) -> Result<()> {
+    match std::fs::metadata(name) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound =>
+            Err(anyhow::Error::new(e).context(name.to_string()).context(Exit::NotFound)),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Parser)]
+#[command(version, about, long_about = None, after_help = EXIT_CODES_HELP)]
+struct Cli {
+    /// Logging configuration, can be given multiple times. '!' changes it at runtime
+    #[arg(short='l', long)]
+    log: Vec<String>,
+
+    #[arg(short='o', long)]
+    output: Option<String>,
+
+    /// Follow the end of the file, pausing while scrolled away from it
+    #[arg(short='f', long)]
+    follow: bool,
+
+    /// Show N lines before and after each tagged line when only tagged lines are shown
+    #[arg(short='C', long, value_name = "N")]
+    context: Option<u16>,
+
+    /// Tag lines matching REGEX. In follow mode, new matching lines ring the bell and flash
+    /// the status row
+    #[arg(long, value_name = "REGEX")]
+    alert: Option<String>,
+
+    /// Tag lines matching REGEX, can be given multiple times
+    #[arg(short='t', long, value_name = "REGEX", value_parser = parse_regex)]
+    tag: Vec<String>,
+
+    /// Hide lines matching REGEX, can be given multiple times
+    #[arg(short='x', long, value_name = "REGEX", value_parser = parse_regex)]
+    hide: Vec<String>,
+
+    /// Mark matches of REGEX, each in a color of its own. Can be given multiple times
+    #[arg(short='m', long, value_name = "REGEX", value_parser = parse_regex)]
+    mark: Vec<String>,
+
+    /// Start showing only the tagged lines. --export-ansi then writes only them
+    #[arg(long)]
+    tagged: bool,
+
+    /// Additional characters that separate WORDs (W/B/M), besides space and tab
+    #[arg(long, value_name = "CHARS", default_value = "")]
+    bigword_delimiters: String,
+
+    /// Show the colors of ANSI escape sequences in the log instead of the sequences. Other
+    /// escape sequences are removed
+    #[arg(long)]
+    ansi: bool,
+
+    /// Expand tabs to the next multiple of N columns
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_TAB_WIDTH as u16)]
+    tab_width: u16,
+
+    /// Wrap long lines (default)
+    #[arg(long, overrides_with = "no_wrap")]
+    wrap: bool,
+
+    /// Truncate long lines at the screen width instead of wrapping them
+    #[arg(long, overrides_with = "wrap")]
+    no_wrap: bool,
+
+    /// Allow scrolling until the last line is at the top of the screen
+    #[arg(long)]
+    scroll_past_end: bool,
+
+    /// What to show in front of each line, '@' cycles through the modes
+    #[arg(long, value_enum)]
+    offsets: Option<OffsetDisplay>,
+
+    /// Indent of continuation lines of wrapped lines, 'i' sets it at runtime
+    #[arg(long, value_name = "COLUMNS")]
+    indent: Option<u16>,
+
+    /// Read preferences from FILE instead of ~/.config/logrok/config
+    #[arg(long, value_name = "FILE")]
+    config: Option<OsString>,
+
+    /// Don't restore tags, hides and marks from the last session, and don't save them on quit
+    #[arg(long)]
+    no_state: bool,
+
+    /// Write the file with ANSI colors to FILE ("-" for stdout) instead of displaying it.
+    /// Tags, hides and marks come from the last session and --tag, --hide and --mark
+    #[arg(long, value_name = "FILE")]
+    export_ansi: Option<OsString>,
+
+    /// Use only ASCII characters for UI glyphs (default: derived from the locale)
+    #[arg(long)]
+    ascii: bool,
+
+    /// Draw a rule between the log and the status row
+    #[arg(long)]
+    separator: bool,
+
+    /// Show a scrollbar right of the log, unless the window is too narrow for it
+    #[arg(long)]
+    scrollbar: bool,
+
+    /// Don't display the first CHARS characters of each line, like a timestamp. They are
+    /// still searched and matched
+    #[arg(long, value_name = "CHARS")]
+    strip: Option<u16>,
+
+    /// Show the split of the cursor line in the status row, ]s/[s jump to the next/previous
+    /// split. For debugging the split cache
+    #[arg(long, hide = true)]
+    debug_splits: bool,
+
+    /// Print the byte range and line count of each split of the file and exit. For debugging
+    /// the split cache
+    #[arg(long, hide = true)]
+    dump_splits: bool,
+
+    /// Show the rotated predecessors of the file (FILE.1, FILE.2.gz, ...) before it, as one
+    /// timeline
+    #[arg(long)]
+    rotated: bool,
+
+    /// Read the colors of marks, tags, hides and searches from FILE
+    #[arg(long, value_name = "FILE")]
+    theme: Option<OsString>,
+
+    /// Read key bindings from FILE, one `key = action` per line. Keys that aren't in it
+    /// keep their default action
+    #[arg(long, value_name = "FILE")]
+    keys: Option<OsString>,
+
+    /// Start at the line of an anchor shown with '=', even if the file changed a bit since
+    #[arg(long, value_name = "ANCHOR")]
+    goto_anchor: Option<String>,
+
+    /// Map the file into memory instead of reading it, which is faster for big files.
+    /// Truncating the file while it is shown kills logrok with SIGBUS then, so it can't be
+    /// combined with --follow or input copied to a temporary file
+    #[arg(long, conflicts_with = "follow")]
+    mmap: bool,
+
+    /// Split the file into chunks of about SIZE bytes (K, M and G suffixes), between 4K and 1G.
+    /// Each split is searched and read as a whole, so bigger ones need fewer and larger reads
+    #[arg(long, value_name = "SIZE", value_parser = parse_chunk_size,
+        default_value_t = search::SPLIT_CHUNK_SIZE)]
+    chunk_size: u64,
+
+    /// Keep N splits in memory, at least enough for a screen. Memory use is about N times
+    /// the chunk size, fewer splits are read again more often while moving around
+    #[arg(long, value_name = "N")]
+    cache_splits: Option<usize>,
+
+    /// Use the mouse: the wheel scrolls, a click moves the cursor or tags the line. Most
+    /// terminals then need shift held to select text
+    #[arg(long)]
+    mouse: bool,
+
+    #[arg(trailing_var_arg = true, allow_hyphen_values = false, hide = true)]
+    files: Vec<String>,
+}
+
+impl Cli {
+    // "-" or no file at all reads from stdin
+    fn reads_stdin(&self) -> bool {
+        self.files.first().is_none_or(|f| f == "-")
+    }
+
+    // the input is copied to a temporary file first
+    fn spooled(&self) -> bool {
+        self.reads_stdin() || self.rotated
+    }
+
+    // every row of the screen might show a line of another split, so a screen full of
+    // splits has to fit into the cache
+    fn cache_splits(&self) -> Result<NonZeroUsize> {
+        let rows = crossterm::terminal::size().map_or(1, |(_, rows)| rows as usize).max(1);
+        let splits = match self.cache_splits {
+            Some(splits) if splits < rows => {
+                return Err(anyhow::anyhow!(
+                    "--cache-splits {} is too small, the screen needs at least {}", splits, rows));
+            }
+            Some(splits) => splits,
+            None => CACHE_SPLITS.max(rows),
+        };
+
+        Ok(NonZeroUsize::new(splits).unwrap())
+    }
+
+    fn open(&self, filename: &OsStr) -> Result<Lines> {
+        Lines::with_splits(filename, self.chunk_size, self.cache_splits()?)
+    }
+}
+
+// checked here, so a typo is an invalid command line and not a silently missing pattern
+fn parse_regex(value: &str) -> Result<String> {
+    regex::Regex::new(value)?;
+
+    Ok(value.to_string())
+}
+
+fn parse_chunk_size(value: &str) -> Result<u64> {
+    let size = config::parse_size(value)?;
+    if size < search::MIN_CHUNK_SIZE {
+        return Err(anyhow::anyhow!("at least {} bytes", search::MIN_CHUNK_SIZE));
+    }
+    // also no "off", a split is read as a whole
+    if size > search::MAX_CHUNK_SIZE {
+        return Err(anyhow::anyhow!("at most {} bytes", search::MAX_CHUNK_SIZE));
     }
+
+    Ok(size)
 }
 
-#[derive(Parser)]
-#[command(version, about, long_about = None)]
-struct Cli {
-    /// Logging configuration, can be given multiple times
-    #[arg(short='l', long)]
-    log: Vec<String>,
+// write the view of --export-ansi, without a terminal. Nothing to write is an exit code of
+// its own
+fn export_batch(cli: &Cli, lines: Lines, config: &Config, path: &OsStr) -> Result<()> {
+    let inner = LogrokInner::new(lines, cli, config);
+    if inner.export_to(ExportFormat::Ansi, path, true)? == 0 {
+        return Err(Exit::NoMatches.into());
+    }
 
-    #[arg(short='o', long)]
-    output: Option<String>,
+    Ok(())
+}
 
-    #[arg(trailing_var_arg = true, allow_hyphen_values = false, hide = true)]
-    files: Vec<String>,
+fn main() -> process::ExitCode {
+    match logrok() {
+        Ok(()) => process::ExitCode::SUCCESS,
+        Err(e) => {
+            if !quiet_exit(&e) {
+                eprintln!("Error: {:?}", e);
+            }
+            process::ExitCode::from(exit_code(&e))
+        }
+    }
 }
 
-fn main() -> Result<()> {
+fn logrok() -> Result<()> {
     let cli = Cli::parse();
-    if cli.files.len() != 1 {
+    if cli.files.len() > 1 || (cli.files.is_empty() && io::stdin().is_terminal()) {
         return Err(anyhow::anyhow!("Expected exactly one file"));
     }
+    if !cli.reads_stdin() {
+        check_log_file(&cli.files[0])?;
+    }
 
     let mut facade = None;
     let mut level = None;
@@ -2195,58 +5782,1891 @@ fn main() -> Result<()> {
     CLog::set_mod_level(v)?;
 
     let orig_hook = panic::take_hook();
+    let mouse = cli.mouse;
     panic::set_hook(Box::new(move |panic_info| {
+        if mouse {
+            let _ = crossterm::execute!(io::stdout(), DisableMouseCapture);
+        }
         orig_hook(panic_info);
         let _ = std::io::stderr().flush();
         process::exit(1);
     }));
 
-    let filename = OsString::from(&cli.files[0]);
+    let config = Config::load(cli.config.as_deref())?;
+    let goto_anchor = cli.goto_anchor.as_deref().map(LineAnchor::parse).transpose()?;
+
+    // stdin, rotated logs and compressed files are copied to a temporary file, which is then
+    // read like any other
+    let (spool, what) = if cli.reads_stdin() {
+        (Some(Spool::stdin()?), "stdin")
+    } else if cli.rotated {
+        let sources = rotate::discover(OsStr::new(&cli.files[0]))?.iter()
+            .map(|path| rotate::open(path).with_context(|| format!("{}", path.display())))
+            .collect::<Result<Vec<_>>>()?;
+        (Some(Spool::new("rotated", sources)?), "rotated logs")
+    } else if rotate::is_gzip(Path::new(&cli.files[0])).with_context(|| cli.files[0].clone())? {
+        let source = rotate::open(Path::new(&cli.files[0])).with_context(|| cli.files[0].clone())?;
+        (Some(Spool::new("gunzip", vec![source])?), "compressed file")
+    } else {
+        (None, "")
+    };
+    if cli.mmap && spool.is_some() {
+        return Err(anyhow::anyhow!("--mmap can't be used with {}", what));
+    }
+    let filename = match spool {
+        Some(ref spool) => spool.path().to_os_string(),
+        None => OsString::from(&cli.files[0]),
+    };
+
+    if cli.dump_splits {
+        if let Some(ref spool) = spool {
+            spool.wait()?;
+        }
+        return dump_splits(&filename, cli.chunk_size, &mut io::stdout().lock());
+    }
+
+    if let Some(ref path) = cli.export_ansi {
+        if let Some(ref spool) = spool {
+            spool.wait()?;
+        }
+        return export_batch(&cli, cli.open(&filename)?, &config, path);
+    }
+
+    let confirm_size = config.confirm_size.unwrap_or(CONFIRM_SIZE);
+    if spool.is_none() && !confirm_open(&filename, confirm_size, cli.chunk_size)? {
+        return Ok(());
+    }
+
+    // open the file before taking over the terminal, so errors are reported cleanly
+    let lines = match spool {
+        Some(_) => None,
+        None => Some(cli.open(&filename)?),
+    };
 
     let mut terminal = ratatui::init();
+    let mouse_capture = cli.mouse.then(MouseCapture::enable).transpose()?;
     terminal.clear()?;
-    let indent = vec![" "; 79].join("");
-    let mark_style = MarkStyle::new();
-    let app_result = Logrok {
-        inner: Arc::new(Mutex::new(LogrokInner {
-            exit: false,
-            cursor_x: 0,
-            cursor_y: 0,
-            area_width: 1,
-            area_height: 1,
-            first_line: 0,
-            line_offset: 0,
-            patterns: PatternSet::new(mark_style.clone()),
-            lines: Lines::new(&filename)?,
-            display_mode: DisplayMode::Normal,
-            mark_style,
-            display_offset: false,
-            display_offset_len: 0,
-            focus: Focus::Main,
-            before_filter_pos: HashMap::new(),
-            current_search: String::new(),
-            search_direction: Direction::Forward,
-            search_match_type: MatchType::Text,
-            last_search: None,
-            status_message: None,
-            plines: Vec::new(),
-            line_indexes: Vec::new(),
-            render_cursor: (0, 0),
-            indent_chars: indent.chars().count() as u16,
-            indent,
-            overlong_fold: HashMap::new(),
-            help_first_line: 0,
-            help: build_help(),
-            undo_stack: Vec::new(),
-            input_area: Rect::default(),
-            input_content: Vec::new(),
-        })),
-    }.run(&mut terminal);
+    let lines = match lines {
+        Some(lines) => Ok(lines),
+        // with the terminal already set up, to show the progress of reading
+        None => wait_for_spool(spool.as_ref().unwrap(), what, &mut terminal)
+            .and_then(|()| cli.open(&filename)),
+    };
+    let app_result = lines.and_then(|lines| {
+        let mut inner = LogrokInner::new(lines, &cli, &config);
+        if let Some(ref anchor) = goto_anchor {
+            inner.goto_anchor(anchor);
+        }
+        if let Some(ref spool) = spool {
+            if cli.rotated && spool.starts().len() > 1 {
+                inner.file_starts = spool.starts();
+            }
+        }
+        Logrok {
+            inner: Arc::new(Mutex::new(inner)),
+            pending_event: None,
+            accel: Accel::new(config.accel.clone().unwrap_or_default()),
+        }.run(&mut terminal)
+    });
     // move to sane position in case the terminal does not have an altscreen
     let size = terminal.size()?;
     terminal.set_cursor_position((0, size.height - 1))?;
     terminal.show_cursor()?;
     println!("");
+    drop(mouse_capture);
     ratatui::restore();
     app_result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testutil::TempPath;
+    use std::ops::{Deref, DerefMut};
+
+    fn test_file(name: &str, content: &str) -> TempPath {
+        TempPath::file(&format!("test-{}", name), content)
+    }
+
+    // the log file goes away with the view of it
+    struct TestLogrok {
+        inner: LogrokInner,
+        _file: TempPath,
+    }
+
+    impl Deref for TestLogrok {
+        type Target = LogrokInner;
+
+        fn deref(&self) -> &LogrokInner {
+            &self.inner
+        }
+    }
+
+    impl DerefMut for TestLogrok {
+        fn deref_mut(&mut self) -> &mut LogrokInner {
+            &mut self.inner
+        }
+    }
+
+    fn test_logrok(name: &str, content: &str, args: &[&str]) -> TestLogrok {
+        let file = test_file(name, content);
+        let mut argv = vec!["logrok", "--no-state"];
+        argv.extend_from_slice(args);
+        argv.push(file.to_str().unwrap());
+        let cli = Cli::parse_from(argv);
+        let inner = LogrokInner::new(Lines::new(file.as_os_str()).unwrap(), &cli,
+            &Config::default());
+        TestLogrok { inner, _file: file }
+    }
+
+    // most tests start from the first frame, laid out for area
+    fn rendered_logrok(name: &str, content: &str, args: &[&str], area: Rect) -> TestLogrok {
+        let mut inner = test_logrok(name, content, args);
+        inner.process_event(area, None);
+        render_rows(&mut inner, area);
+        inner
+    }
+
+    fn render_rows(inner: &mut LogrokInner, area: Rect) -> Vec<String> {
+        let mut buf = Buffer::empty(area);
+        inner.render(area, &mut buf);
+        (0..area.height).map(|y| {
+            (0..area.width).map(|x| buf.cell((x, y)).unwrap().symbol().to_string())
+                .collect::<String>()
+        }).collect()
+    }
+
+    fn send_key(inner: &mut LogrokInner, area: Rect, c: char) -> Vec<String> {
+        let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        inner.process_event(area, Some(Event::Key(key)));
+        render_rows(inner, area)
+    }
+
+    #[test]
+    fn test_repeats_movement() {
+        let content = (0..40).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("repeats_movement", &content, &[], area);
+        let press = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert_eq!(press('j').kind, KeyEventKind::Press);
+        assert!(inner.repeats_movement(&press('j')));
+        assert!(inner.repeats_movement(&KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL)));
+        // gg and tagging twice are two keys, not a held one
+        assert!(!inner.repeats_movement(&press('g')));
+        assert!(!inner.repeats_movement(&press('t')));
+        // 5jj isn't 5j
+        send_key(&mut inner, area, '5');
+        assert!(!inner.repeats_movement(&press('j')));
+        send_key(&mut inner, area, 'j');
+        assert!(inner.repeats_movement(&press('j')));
+        // the key after a prefix is its argument
+        send_key(&mut inner, area, '`');
+        assert!(!inner.repeats_movement(&press('j')));
+        send_key(&mut inner, area, 'j');
+        // typed text
+        send_key(&mut inner, area, '/');
+        assert!(!inner.repeats_movement(&press('j')));
+    }
+
+    #[test]
+    fn test_indent_clamp() {
+        let content = "x".repeat(100) + "\n" + &"y".repeat(10) + "\n";
+        // 2 columns marker area, 40 columns log area
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("indent_clamp", &content, &[], area);
+        assert_eq!(inner.area_width, 40);
+
+        inner.cursor_x = inner.area_width as i16 - 1;
+        inner.set_indent();
+        assert_eq!(inner.indent_chars, 39);
+        assert_eq!(inner.wrap_indent(inner.area_width), 40 - MIN_WRAP_COLUMNS);
+
+        // 40 chars on the first row, the remaining 60 in rows of 8
+        let pline = inner.get_line(0).unwrap();
+        assert_eq!(inner.line_parts(&pline, inner.area_width), 9);
+        assert_eq!(inner.cursor_from_pos_ix(40, 0, inner.area_width), (32, 1));
+        assert_eq!(inner.cursor_from_pos_ix(99, 0, inner.area_width), (35, 8));
+
+        inner.plines.clear();
+        inner.process_event(area, None);
+        let rows = render_rows(&mut inner, area);
+        assert_eq!(rows[1], format!("  {}{}", " ".repeat(32), "x".repeat(8)));
+        assert_eq!(rows[8], format!("  {}{}{}", " ".repeat(32), "xxxx", " ".repeat(4)));
+        assert!(rows[9].starts_with("  yyyyyyyyyy"));
+    }
+
+    #[test]
+    fn test_default_indent() {
+        let content = "x".repeat(100) + "\n";
+        let area = Rect::new(0, 0, 80, 24);
+        let mut inner = rendered_logrok("default_indent", &content, &[], area);
+        let rows = render_rows(&mut inner, area);
+        assert_eq!(inner.indent_chars, 0);
+        assert_eq!(rows[0], format!("  {}", "x".repeat(78)));
+        assert!(rows[1].starts_with(&format!("  {} ", "x".repeat(22))));
+
+        // even a big indent leaves the narrow window usable
+        inner.indent_chars = 79;
+        let area = Rect::new(0, 0, 20, 24);
+        inner.process_event(area, None);
+        let rows = render_rows(&mut inner, area);
+        assert_eq!(rows[1], format!("  {}{}", " ".repeat(10), "x".repeat(8)));
+    }
+
+    #[test]
+    fn test_first_frame_indent() {
+        // the first frame of run, with a configured indent as wide as the terminal
+        let content = "y".repeat(10) + "\n" + &"x".repeat(100) + "\n";
+        let area = Rect::new(0, 0, 80, 24);
+        let mut inner = rendered_logrok("first_frame_indent", &content, &["--indent", "79"], area);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[0].starts_with("  yyyyyyyyyy "));
+        assert_eq!(rows[1], format!("  {}", "x".repeat(78)));
+        assert_eq!(rows[2], format!("  {}{}", " ".repeat(70), "x".repeat(8)));
+        assert_eq!(rows[4], format!("  {}{}{}", " ".repeat(70), "xxxxxx", "  "));
+        // the indent itself is kept for wider windows
+        assert_eq!(inner.indent_chars, 79);
+    }
+
+    #[test]
+    fn test_no_wrap() {
+        let content = "x".repeat(200) + "\n" + &"y".repeat(10) + "\n";
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("no_wrap", &content, &["--no-wrap"], area);
+        let rows = render_rows(&mut inner, area);
+        assert_eq!(rows[0], format!("  {}{}", "x".repeat(99), inner.glyphs.truncated));
+        assert!(rows[1].starts_with("  yyyyyyyyyy "));
+    }
+
+    #[test]
+    fn test_toggle_wrap() {
+        let content = (0..60).map(|i| (b'a' + i % 26) as char).collect::<String>() + "\nshort\n";
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("toggle_wrap", &content, &[], area);
+        let rows = send_key(&mut inner, area, 'v');
+        assert!(!inner.wrap);
+        assert!(rows[1].starts_with("  short"));
+        assert_eq!(inner.line_parts(&inner.plines[0], inner.area_width), 1);
+
+        // past the right edge, the view scrolls
+        send_key(&mut inner, area, '$');
+        let rows = send_key(&mut inner, area, 'l');
+        assert_eq!(inner.horizontal_offset, 1);
+        assert_eq!(inner.cursor_x, 39);
+        assert!(rows[0].starts_with("  bcdef"));
+        assert_eq!(inner.resolve_cursor_position(), Some((Some(40), 0, 0)));
+        send_key(&mut inner, area, '0');
+        let rows = send_key(&mut inner, area, 'h');
+        assert_eq!(inner.horizontal_offset, 0);
+        assert!(rows[0].starts_with("  abcdef"));
+
+        // not folded while truncated, wrapped again on the next v
+        send_key(&mut inner, area, 'F');
+        assert!(inner.overlong_fold.is_empty());
+        let rows = send_key(&mut inner, area, 'v');
+        assert!(inner.wrap);
+        assert!(rows[2].starts_with("  short"));
+    }
+
+    #[test]
+    fn test_status_column() {
+        let content = format!("hello world foo\n{}\n", "x".repeat(200));
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("status_column", &content, &[], area);
+        // w stops on the space after the word
+        let rows = send_key(&mut inner, area, 'w');
+        assert!(rows[11].ends_with(" Normal col 6/15"));
+
+        // a truncated line is longer than shown, v puts the cursor back to the start
+        send_key(&mut inner, area, 'v');
+        let rows = send_key(&mut inner, area, 'j');
+        assert!(rows[11].contains("col 1/"));
+        assert!(rows[11].ends_with("+"));
+    }
+
+    #[test]
+    fn test_mark_keeps_viewport() {
+        let content = (0..60).map(|i| format!("line {} foo bar\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("mark_viewport", &content, &[], area);
+        send_key(&mut inner, area, 'G');
+        let mut rows = Vec::new();
+        for _ in 0..4 {
+            rows = send_key(&mut inner, area, 'k');
+        }
+        assert!(rows[0].starts_with("  line 49 "));
+        let first_line = inner.first_line;
+        let cursor_y = inner.cursor_y;
+        inner.cursor_x = 8;
+
+        // mark "foo", then unmark it again
+        for i in 0..2 {
+            // everything but the status line, which shows the cursor column
+            assert_eq!(send_key(&mut inner, area, 'm')[..11], rows[..11]);
+            assert_eq!(inner.undo_stack.len(), i + 1);
+            assert_eq!(inner.first_line, first_line);
+            assert_eq!(inner.cursor_y, cursor_y);
+        }
+    }
+
+    #[test]
+    fn test_hide_anchors_next_line() {
+        let content = (0..60).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("hide_anchor", &content, &[], area);
+        send_key(&mut inner, area, 'j');
+        send_key(&mut inner, area, 'j');
+        let rows = send_key(&mut inner, area, 'j');
+        assert!(rows[3].starts_with("  line 3 "));
+
+        // the hidden line vanishes, the next one takes its row
+        let rows = send_key(&mut inner, area, 'x');
+        assert_eq!(inner.cursor_y, 3);
+        assert!(rows[2].starts_with("  line 2 "));
+        assert!(rows[3].starts_with("  line 4 "));
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let content = (0..20).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("undo_redo", &content, &[], area);
+        let redo = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+
+        send_key(&mut inner, area, 'T');
+        assert!(inner.lines.is_tagged(0));
+        send_key(&mut inner, area, 'u');
+        assert!(!inner.lines.is_tagged(0));
+        inner.process_event(area, Some(Event::Key(redo)));
+        assert!(inner.lines.is_tagged(0));
+
+        // a mark is undone and redone as a whole pattern set
+        render_rows(&mut inner, area);
+        send_key(&mut inner, area, 'm');
+        assert_eq!(inner.patterns.iter().count(), 1);
+        send_key(&mut inner, area, 'u');
+        send_key(&mut inner, area, 'u');
+        assert_eq!(inner.patterns.iter().count(), 0);
+        assert!(!inner.lines.is_tagged(0));
+        inner.process_event(area, Some(Event::Key(redo)));
+        assert!(inner.lines.is_tagged(0));
+
+        // a new action drops what's left to redo
+        render_rows(&mut inner, area);
+        send_key(&mut inner, area, 'j');
+        send_key(&mut inner, area, 'T');
+        inner.process_event(area, Some(Event::Key(redo)));
+        assert_eq!(inner.patterns.iter().count(), 0);
+        assert!(inner.lines.is_tagged(0));
+    }
+
+    #[test]
+    fn test_search_ignore_case() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("ignore_case", "info\nERROR one\nerror two\n", &[], area);
+
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        for c in "/error".chars() {
+            send_key(&mut inner, area, c);
+        }
+        inner.process_event(area, Some(Event::Key(enter)));
+        assert_eq!(inner.cursor_y, 2);
+
+        // Tab while typing makes the search ignore case
+        send_key(&mut inner, area, 'g');
+        send_key(&mut inner, area, 'g');
+        send_key(&mut inner, area, '/');
+        inner.process_event(area, Some(Event::Key(KeyEvent::new(KeyCode::Tab,
+            KeyModifiers::NONE))));
+        let rows = "error".chars().map(|c| send_key(&mut inner, area, c)).last().unwrap();
+        assert!(rows[11].starts_with("/ierror"));
+        inner.process_event(area, Some(Event::Key(enter)));
+        assert_eq!(inner.cursor_y, 1);
+        let id = inner.last_search.unwrap();
+        assert!(inner.patterns.get(id).ignore_case);
+    }
+
+    #[test]
+    fn test_search_history() {
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("search_history", "a\nb\nc\n", &[], area);
+        let key = |code| Some(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+        for search in ["/a", "/b", "/b", "/c"] {
+            search.chars().for_each(|c| { send_key(&mut inner, area, c); });
+            inner.process_event(area, key(KeyCode::Enter));
+        }
+        assert_eq!(inner.search_history, vec!["a", "b", "c"]);
+
+        // the partial input comes back after the newest entry
+        send_key(&mut inner, area, '/');
+        send_key(&mut inner, area, 'x');
+        inner.process_event(area, key(KeyCode::Up));
+        assert_eq!(inner.current_search, "c");
+        inner.process_event(area, key(KeyCode::Up));
+        inner.process_event(area, key(KeyCode::Up));
+        inner.process_event(area, key(KeyCode::Up));
+        assert_eq!(inner.current_search, "a");
+        inner.process_event(area, key(KeyCode::Down));
+        assert_eq!(inner.current_search, "b");
+        inner.process_event(area, key(KeyCode::Down));
+        inner.process_event(area, key(KeyCode::Down));
+        assert_eq!(inner.current_search, "x");
+        inner.process_event(area, key(KeyCode::Down));
+        assert_eq!(inner.current_search, "x");
+    }
+
+    #[test]
+    fn test_search_preview() {
+        let content = (0..40).map(|i| match i {
+            30 => "match\n".to_string(),
+            _ => format!("line {}\n", i),
+        }).collect::<String>();
+        let offset = content.find("match").unwrap() as LineId;
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("search_preview", &content, &[], area);
+        let key = |code| Some(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+
+        // the view follows the input, but it is no search yet. The file is a single split, the
+        // preview scans it right away instead of waiting for the background search
+        "/mat".chars().for_each(|c| { send_key(&mut inner, area, c); });
+        assert_eq!(inner.cursor_anchor(), Some((offset, 0)));
+        assert_eq!(inner.last_search, None);
+        assert!(inner.search_preview.is_some());
+
+        // back where it started with the input gone, and when cancelled
+        for _ in 0..3 {
+            inner.process_event(area, key(KeyCode::Backspace));
+            render_rows(&mut inner, area);
+        }
+        assert_eq!(inner.cursor_anchor(), Some((0, 0)));
+        send_key(&mut inner, area, 'm');
+        inner.process_event(area, key(KeyCode::Esc));
+        render_rows(&mut inner, area);
+        assert_eq!(inner.focus, Focus::Main);
+        assert_eq!(inner.cursor_anchor(), Some((0, 0)));
+        assert_eq!(inner.patterns.iter().count(), 0);
+
+        // Enter makes it the search
+        "/mat".chars().for_each(|c| { send_key(&mut inner, area, c); });
+        inner.process_event(area, key(KeyCode::Enter));
+        render_rows(&mut inner, area);
+        assert_eq!(inner.cursor_anchor(), Some((offset, 0)));
+        assert_eq!(inner.patterns.iter().count(), 1);
+        assert!(inner.last_search.is_some() && inner.search_preview.is_none());
+    }
+
+    #[test]
+    fn test_current_match() {
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("current_match", "foo a foo b\nfoo\n", &[], area);
+        "/foo".chars().for_each(|c| { send_key(&mut inner, area, c); });
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        inner.process_event(area, Some(Event::Key(enter)));
+        let reversed = |inner: &mut LogrokInner| {
+            let mut buf = Buffer::empty(area);
+            inner.render(area, &mut buf);
+            (0..area.width).filter(|&x| buf.cell((x, 0)).unwrap().modifier
+                .contains(Modifier::REVERSED)).collect::<Vec<_>>()
+        };
+        assert_eq!(inner.cursor_x, 6);
+        // the log area starts after the two marker columns
+        assert_eq!(reversed(&mut inner), vec![8, 9, 10]);
+        send_key(&mut inner, area, 'n');
+        assert_eq!(reversed(&mut inner), Vec::<u16>::new());
+        send_key(&mut inner, area, 'n');
+        assert_eq!(reversed(&mut inner), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_relative_numbers() {
+        let content = "a\nb\nc\nd\ne\n";
+        let args = ["--offsets", "relative", "--scroll-past-end"];
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("relative_numbers", content, &args, area);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[0].starts_with("  1 a"));
+        assert!(rows[2].starts_with("  2 c"));
+
+        // the cursor line has its line number, the others their distance to it
+        send_key(&mut inner, area, 'j');
+        let rows = send_key(&mut inner, area, 'j');
+        let numbers = rows[..4].iter().map(|r| &r[..5]).collect::<Vec<_>>();
+        assert_eq!(numbers, vec!["  2 a", "  1 b", "  3 c", "  1 d"]);
+
+        // scrolling keeps the cursor on its line
+        let ctrl_e = KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL);
+        inner.process_event(area, Some(Event::Key(ctrl_e)));
+        let rows = render_rows(&mut inner, area);
+        let numbers = rows[..3].iter().map(|r| &r[..5]).collect::<Vec<_>>();
+        assert_eq!(numbers, vec!["  1 b", "  3 c", "  1 d"]);
+
+        // @ cycles on to no numbers
+        let rows = send_key(&mut inner, area, '@');
+        assert!(rows[0].starts_with("  b"));
+    }
+
+    #[test]
+    fn test_line_number_width() {
+        // the unterminated last line is counted, too, so the numbers are two digits wide
+        let content = ('a'..='j').map(String::from).collect::<Vec<_>>().join("\n");
+        let mut inner = test_logrok("line_number_width", &content, &["--offsets", "line"]);
+        let area = Rect::new(0, 0, 42, 12);
+        // the gutter doesn't wait for the lines to be counted
+        for _ in 0..500 {
+            if inner.lines.line_count().1 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(inner.lines.line_count(), (10, true));
+        inner.process_event(area, None);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[0].starts_with("   1 a"));
+        assert!(rows[9].starts_with("  10 j"));
+        assert!(!inner.counting_lines);
+    }
+
+    #[test]
+    fn test_keys() {
+        let keys = test_file("keys.conf", "n = down\nj = none\n");
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("keys", "a\nb\nc\n", &["--keys", keys.to_str().unwrap()],
+            area);
+        send_key(&mut inner, area, 'j');
+        assert_eq!(inner.cursor_y, 0);
+        send_key(&mut inner, area, 'n');
+        assert_eq!(inner.cursor_y, 1);
+        // keys that aren't in the file keep their action
+        send_key(&mut inner, area, 'k');
+        assert_eq!(inner.cursor_y, 0);
+        assert!(inner.status_message.is_none());
+    }
+
+    #[test]
+    fn test_tab_expansion() {
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("tab_expansion", "a\tbc\nfoo\tx\tbar\n", &[], area);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[0].starts_with("  a       bc"));
+        assert!(rows[1].starts_with("  foo     x       bar"));
+
+        // a match over a tab covers the spaces it expands to
+        "/x\tb".chars().for_each(|c| { send_key(&mut inner, area, c); });
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        inner.process_event(area, Some(Event::Key(enter)));
+        let mut buf = Buffer::empty(area);
+        inner.render(area, &mut buf);
+        assert_eq!((inner.cursor_x, inner.cursor_y), (8, 1));
+        let reversed = (0..area.width).filter(|&x| buf.cell((x, 1)).unwrap().modifier
+            .contains(Modifier::REVERSED)).collect::<Vec<_>>();
+        assert_eq!(reversed, (10..19).collect::<Vec<_>>());
+
+        // marks extended over a tab match the tab, not the spaces shown
+        send_key(&mut inner, area, '\\');
+        send_key(&mut inner, area, 'm');
+        send_key(&mut inner, area, '.');
+        send_key(&mut inner, area, '<');
+        let marks = inner.patterns.iter().map(|p| p.pattern.clone()).collect::<Vec<_>>();
+        assert_eq!(marks, vec!["\tx\t"]);
+        assert_eq!(inner.yank_text(), Some(("\tx\t".to_string(), "mark")));
+
+        let mut inner = rendered_logrok("tab_width", "a\tbc\n", &["--tab-width", "4"], area);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[0].starts_with("  a   bc"));
+    }
+
+    #[test]
+    fn test_command() {
+        let content = (1..=30).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("command", &content, &[], area);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        let command = |inner: &mut LogrokInner, line: &str| {
+            send_key(inner, area, ':');
+            line.chars().for_each(|c| { send_key(inner, area, c); });
+            inner.process_event(area, Some(Event::Key(enter)));
+            render_rows(inner, area)
+        };
+
+        let rows = command(&mut inner, "20");
+        assert_eq!(inner.focus, Focus::Main);
+        assert!(rows[inner.cursor_y as usize].starts_with("  line 20"));
+        command(&mut inner, "set nowrap");
+        assert!(!inner.wrap);
+        command(&mut inner, "set indent=4");
+        assert_eq!(inner.indent_chars, 4);
+        let rows = command(&mut inner, "frobnicate");
+        assert!(rows[11].contains("unknown command: frobnicate"));
+        command(&mut inner, "set indent=x");
+        assert_eq!(inner.indent_chars, 4);
+        command(&mut inner, "q");
+        assert!(inner.exit);
+    }
+
+    #[test]
+    fn test_clear_all() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("clear_all", "foo bar\nbaz\n", &[], area);
+        send_key(&mut inner, area, 'm');
+        inner.lines.toggle_tag(8);
+        ":clear".chars().for_each(|c| { send_key(&mut inner, area, c); });
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        inner.process_event(area, Some(Event::Key(enter)));
+        render_rows(&mut inner, area);
+        assert_eq!(inner.patterns.iter().count(), 0);
+        assert!(inner.lines.tagged_lines().is_empty());
+
+        // one undo brings all of it back
+        send_key(&mut inner, area, 'u');
+        assert_eq!(inner.patterns.iter().count(), 1);
+        assert!(inner.lines.is_tagged(8));
+        let redo = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        inner.process_event(area, Some(Event::Key(redo)));
+        assert_eq!(inner.patterns.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_reload() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("reload", "one\ntwo\n", &[], area);
+        inner.lines.toggle_tag(0);
+        let path = inner.filename.clone();
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let rows = send_key(&mut inner, area, 'R');
+        assert!(rows[2].contains("three"));
+        assert!(rows[11].starts_with("read new lines"));
+        assert!(inner.lines.is_tagged(0));
+
+        // a truncated file is read from the top, its tags are gone
+        std::fs::write(&path, "new\n").unwrap();
+        let rows = send_key(&mut inner, area, 'R');
+        assert!(rows[0].contains("new"));
+        assert!(!rows[1].contains("two"));
+        assert!(!inner.lines.is_tagged(0));
+        assert!(rows[11].starts_with("the file was truncated or replaced"));
+    }
+
+    #[test]
+    fn test_message_log() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("message_log", "a\nb\n", &[], area);
+        send_key(&mut inner, area, '\'');
+        let rows = send_key(&mut inner, area, 'b');
+        assert!(rows[11].starts_with("bookmark b is not set"));
+
+        // it outlasts the next key for a while
+        let rows = send_key(&mut inner, area, 'j');
+        assert_eq!(inner.status_message, None);
+        assert!(rows[11].starts_with("bookmark b is not set"));
+        inner.old_message.as_mut().unwrap().1 -= MESSAGE_TIME;
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[11].starts_with("^H Help"));
+
+        let ctrl_g = KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL);
+        inner.process_event(area, Some(Event::Key(ctrl_g)));
+        assert_eq!(inner.focus, Focus::Overlay);
+        assert_eq!(inner.message_log, vec!["bookmark b is not set"]);
+    }
+
+    #[test]
+    fn test_info() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("info", "foo\nbar\nbaz\n", &[], area);
+        send_key(&mut inner, area, 'm');
+        send_key(&mut inner, area, 'I');
+        assert_eq!(inner.focus, Focus::Overlay);
+        let text = inner.overlay.as_ref().unwrap().text.iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(text[1], "size      12 bytes");
+        assert_eq!(text[2], "splits    1");
+        assert_eq!(text[3], "lines     3");
+        assert_eq!(text[4], "display   Normal");
+        assert_eq!(text[5], "patterns  0 tagging, 0 hiding, 1 marking");
+    }
+
+    #[test]
+    fn test_escape() {
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("escape", "a\nb\na\n", &[], area);
+        let key = |code| Some(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+        "/a".chars().for_each(|c| { send_key(&mut inner, area, c); });
+        inner.process_event(area, key(KeyCode::Enter));
+        render_rows(&mut inner, area);
+        let search = inner.last_search;
+
+        // an aborted input leaves the committed search alone
+        "/b".chars().for_each(|c| { send_key(&mut inner, area, c); });
+        inner.process_event(area, key(KeyCode::Esc));
+        assert_eq!(inner.focus, Focus::Main);
+        assert!(inner.current_search.is_empty());
+        assert_eq!(inner.last_search, search);
+        assert!(inner.patterns.get(search.unwrap()).enabled);
+
+        let ctrl_h = KeyEvent::new(KeyCode::Char('h'), KeyModifiers::CONTROL);
+        inner.process_event(area, Some(Event::Key(ctrl_h)));
+        assert_eq!(inner.focus, Focus::Help);
+        inner.process_event(area, key(KeyCode::Esc));
+        assert_eq!(inner.focus, Focus::Main);
+    }
+
+    #[test]
+    fn test_separator() {
+        let content = (0..20).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("separator", &content, &["--separator", "--ascii"], area);
+        let rows = render_rows(&mut inner, area);
+        assert_eq!(inner.area_height, 10);
+        assert!(rows[9].contains("line 9"));
+        assert_eq!(rows[10], "-".repeat(102));
+        assert_eq!(inner.input_area.y, 11);
+    }
+
+    #[test]
+    fn test_scrollbar() {
+        let content = (0..40).map(|i| format!("line {:02}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("scrollbar", &content, &["--scrollbar", "--ascii"], area);
+        let column = |rows: &[String]| {
+            rows[..11].iter().map(|r| r.chars().last().unwrap()).collect::<String>()
+        };
+        let rows = render_rows(&mut inner, area);
+        assert_eq!(column(&rows), "###||||||||");
+        let rows = send_key(&mut inner, area, 'G');
+        assert_eq!(column(&rows), "||||||||###");
+
+        // a window too narrow for it keeps all columns for the text
+        let narrow = Rect::new(0, 0, 10, 12);
+        inner.process_event(narrow, None);
+        let rows = render_rows(&mut inner, narrow);
+        assert!(rows[..11].iter().all(|r| !r.ends_with('|') && !r.ends_with('#')));
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("invalid_regex", "info\nfoo(bar\n", &[], area);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+
+        for c in "&foo(".chars() {
+            send_key(&mut inner, area, c);
+        }
+        inner.process_event(area, Some(Event::Key(enter)));
+        let rows = render_rows(&mut inner, area);
+        assert_eq!(inner.focus, Focus::Search);
+        assert!(rows[11].starts_with("&foo(  (error: unclosed group)"));
+        assert!(inner.last_search.is_none());
+
+        let backspace = KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE);
+        inner.process_event(area, Some(Event::Key(backspace)));
+        for c in "\\(".chars() {
+            send_key(&mut inner, area, c);
+        }
+        inner.process_event(area, Some(Event::Key(enter)));
+        assert_eq!(inner.focus, Focus::Main);
+        assert_eq!(inner.cursor_y, 1);
+    }
+
+    #[test]
+    fn test_backward_regex_search() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("backward_regex", "a1\nb\na2\nb\na3\nb\n", &[], area);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+
+        send_key(&mut inner, area, 'G');
+        let rows = "#a[12]".chars().map(|c| send_key(&mut inner, area, c)).last().unwrap();
+        assert!(rows[11].starts_with("#a[12]"));
+        inner.process_event(area, Some(Event::Key(enter)));
+        render_rows(&mut inner, area);
+        assert_eq!(inner.cursor_anchor(), Some((5, 0)));
+
+        // n keeps going backward, N forward
+        send_key(&mut inner, area, 'n');
+        assert_eq!(inner.cursor_anchor(), Some((0, 0)));
+        send_key(&mut inner, area, 'N');
+        assert_eq!(inner.cursor_anchor(), Some((5, 0)));
+    }
+
+    #[test]
+    fn test_pin_line() {
+        let content = (0..30).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("pin_line", &content, &["--ascii"], area);
+
+        send_key(&mut inner, area, 'j');
+        let rows = send_key(&mut inner, area, 'P');
+        assert_eq!(inner.pinned_line, Some(7));
+        assert_eq!(inner.area_height, 10);
+        assert!(rows[0].starts_with("P line 1"));
+        assert!(rows[1].contains("line 0"));
+
+        // the pin stays while the log scrolls underneath
+        let rows = send_key(&mut inner, area, 'G');
+        assert!(rows[0].starts_with("P line 1"));
+        assert!(rows[10].contains("line 29"));
+        assert_eq!(inner.cursor_y, 9);
+
+        let rows = send_key(&mut inner, area, 'P');
+        assert_eq!(inner.pinned_line, None);
+        assert_eq!(inner.area_height, 11);
+        assert!(rows[10].contains("line 29"));
+    }
+    #[test]
+    fn test_match_count() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("match_count", "a1\nb\na2\nb\na3\n", &[], area);
+        let rows = render_rows(&mut inner, area);
+        assert!(!rows[11].contains("matches"));
+
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        for c in "/a".chars() {
+            send_key(&mut inner, area, c);
+        }
+        inner.process_event(area, Some(Event::Key(enter)));
+        // the search moved to the first match, so all of the file has been searched
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[11].contains(" 3 matches"));
+        assert!(!inner.counting);
+
+        let rows = send_key(&mut inner, area, '\\');
+        assert!(!rows[11].contains("matches"));
+    }
+    #[test]
+    fn test_strip_prefix() {
+        let content = "12:00:01 start\n12:00:02 ".to_string() + &"x".repeat(150) +
+            "\n12:00:03\n12:00:04 code 04\n";
+        let area = Rect::new(0, 0, 102, 12);
+        let args = ["--strip", "9", "--indent", "0"];
+        let mut inner = rendered_logrok("strip_prefix", &content, &args, area);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[0].starts_with("  start "));
+        // 150 chars wrap into two rows once the prefix is gone
+        assert_eq!(rows[1], format!("  {}", "x".repeat(100)));
+        assert!(rows[2].starts_with(&format!("  {} ", "x".repeat(50))));
+        assert_eq!(inner.line_parts(&inner.plines[1], inner.area_width), 2);
+        // a line that is all prefix keeps its last char
+        assert!(rows[3].starts_with("  3 "));
+
+        // a match in the hidden prefix puts the cursor at the start of the line
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        for c in "/04".chars() {
+            send_key(&mut inner, area, c);
+        }
+        inner.process_event(area, Some(Event::Key(enter)));
+        render_rows(&mut inner, area);
+        assert_eq!((inner.cursor_x, inner.cursor_y), (0, 4));
+        assert_eq!(inner.resolve_cursor_position(), Some((Some(9), 3, 0)));
+        send_key(&mut inner, area, 'n');
+        assert_eq!((inner.cursor_x, inner.cursor_y), (5, 4));
+        send_key(&mut inner, area, 'N');
+        assert_eq!((inner.cursor_x, inner.cursor_y), (0, 4));
+        // going back from there doesn't find the hidden match again
+        send_key(&mut inner, area, 'N');
+        assert_eq!((inner.cursor_x, inner.cursor_y), (5, 4));
+    }
+    #[test]
+    fn test_count_prefix() {
+        let content = (1..=40).map(|i| format!("line {} a b c d\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("count_prefix", &content, &[], area);
+
+        send_key(&mut inner, area, '5');
+        send_key(&mut inner, area, 'j');
+        assert_eq!(inner.cursor_y, 5);
+        send_key(&mut inner, area, '3');
+        send_key(&mut inner, area, 'w');
+        assert_eq!(inner.cursor_x, 6);
+        // the count is used up
+        send_key(&mut inner, area, 'j');
+        assert_eq!(inner.cursor_y, 6);
+        // 0 without a count is still start of line
+        send_key(&mut inner, area, '0');
+        assert_eq!(inner.cursor_x, 0);
+
+        for c in "25G".chars() {
+            send_key(&mut inner, area, c);
+        }
+        let (line_id, _) = inner.cursor_anchor().unwrap();
+        assert!(inner.get_line(line_id).unwrap().chars.iter().map(|c| c.c).collect::<String>()
+            .starts_with("line 25 "));
+        for c in "41G".chars() {
+            send_key(&mut inner, area, c);
+        }
+        assert_eq!(inner.cursor_anchor().unwrap().0, line_id);
+        send_key(&mut inner, area, 'G');
+        assert!(inner.bottom_visible());
+    }
+
+    #[test]
+    fn test_prefix_keys() {
+        let content = (1..=30).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("prefix_keys", &content, &[], area);
+
+        // keys after a prefix don't do what they do alone
+        for c in "5j`q`Pgqgg'q]q[@".chars() {
+            send_key(&mut inner, area, c);
+            assert!(!inner.exit);
+        }
+        assert!(inner.bookmarks.contains_key(&'q'));
+        assert_eq!(inner.cursor_anchor(), Some((5 * 7, 0)));
+        assert_eq!(inner.pinned_line, None);
+        assert_eq!(inner.display_offset, OffsetDisplay::Off);
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let content = (1..=60).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("bookmarks", &content, &[], area);
+
+        for c in "20G3j`a".chars() {
+            send_key(&mut inner, area, c);
+        }
+        let (line_id, _) = inner.cursor_anchor().unwrap();
+        let row = inner.cursor_y;
+        send_key(&mut inner, area, 'G');
+        assert_ne!(inner.cursor_anchor().unwrap().0, line_id);
+        send_key(&mut inner, area, '\'');
+        send_key(&mut inner, area, 'a');
+        assert_eq!(inner.cursor_anchor().unwrap().0, line_id);
+        assert_eq!(inner.cursor_y, row);
+
+        // a hidden line is replaced by the nearest shown one
+        send_key(&mut inner, area, 'X');
+        send_key(&mut inner, area, 'g');
+        send_key(&mut inner, area, 'g');
+        send_key(&mut inner, area, '\'');
+        let rows = send_key(&mut inner, area, 'a');
+        assert!(rows[11].starts_with("line of bookmark a is not shown"));
+        assert_eq!(inner.cursor_y, row);
+        assert!(rows[row as usize].starts_with("  line 24 "));
+
+        send_key(&mut inner, area, '\'');
+        let rows = send_key(&mut inner, area, 'b');
+        assert!(rows[11].starts_with("bookmark b is not set"));
+    }
+
+    #[test]
+    fn test_diff_above() {
+        let content = "count 10 ok\ncount 11 ok\ncount 11\ncount 11 ok\n";
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("diff_above", content, &[], area);
+        let rows = send_key(&mut inner, area, 'D');
+        assert!(rows[11].starts_with("highlighting changes to the line above"));
+
+        let mut buf = Buffer::empty(area);
+        inner.render(area, &mut buf);
+        let changed = |x: u16, y: u16| {
+            buf.cell((x + MARKER_WIDTH, y)).unwrap().modifier.contains(Modifier::UNDERLINED)
+        };
+        assert!((0..11).all(|x| !changed(x, 0)));
+        assert_eq!((0..11).filter(|&x| changed(x, 1)).collect::<Vec<_>>(), vec![7]);
+        // a shorter line has nothing that changed, the longer one after it has
+        assert!((0..8).all(|x| !changed(x, 2)));
+        assert_eq!((0..11).filter(|&x| changed(x, 3)).collect::<Vec<_>>(), vec![8, 9, 10]);
+
+        send_key(&mut inner, area, 'D');
+        let mut buf = Buffer::empty(area);
+        inner.render(area, &mut buf);
+        assert!(!buf.cell((7 + MARKER_WIDTH, 1)).unwrap().modifier.contains(Modifier::UNDERLINED));
+    }
+
+    #[test]
+    fn test_highlight_word() {
+        let content = "foo bar foo\nfoobar foo.x\n";
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("highlight_word", content, &[], area);
+        let rows = send_key(&mut inner, area, 'O');
+        assert!(rows[11].starts_with("highlighting the word under the cursor"));
+
+        let highlighted = |inner: &mut LogrokInner, y: u16| {
+            let mut buf = Buffer::empty(area);
+            inner.render(area, &mut buf);
+            (0..12).filter(|&x| buf.cell((x + MARKER_WIDTH, y)).unwrap().bg == Color::DarkGray)
+                .collect::<Vec<_>>()
+        };
+        // whole words only
+        assert_eq!(highlighted(&mut inner, 0), vec![0, 1, 2, 8, 9, 10]);
+        assert_eq!(highlighted(&mut inner, 1), vec![7, 8, 9]);
+
+        // it follows the cursor, no pattern is left behind
+        send_key(&mut inner, area, 'w');
+        send_key(&mut inner, area, 'w');
+        assert_eq!(highlighted(&mut inner, 0), vec![4, 5, 6]);
+        assert_eq!(inner.patterns.iter().count(), 0);
+
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        inner.process_event(area, Some(Event::Key(esc)));
+        assert!(highlighted(&mut inner, 0).is_empty());
+    }
+
+    #[test]
+    fn test_yank_text() {
+        let content = "first line\nsecond foo-bar line\n";
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("yank_text", content, &["--no-wrap"], area);
+        assert_eq!(inner.yank_text(), Some(("first line".to_string(), "1 line")));
+
+        send_key(&mut inner, area, 'j');
+        inner.cursor_x = 9;
+        send_key(&mut inner, area, 'M');
+        inner.cursor_x = 11;
+        assert_eq!(inner.yank_text(), Some(("foo-bar".to_string(), "mark")));
+        // outside of the mark it's the line again
+        inner.cursor_x = 2;
+        assert_eq!(inner.yank_text(), Some(("second foo-bar line".to_string(), "1 line")));
+    }
+
+    #[test]
+    fn test_export_text() {
+        let content = (0..20).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("export_text", &content, &[], area);
+        for c in "3jT4jTf".chars() {
+            send_key(&mut inner, area, c);
+        }
+        assert_eq!(inner.display_mode, DisplayMode::Tagged);
+
+        let path = test_file("export_text.txt", "");
+        assert_eq!(inner.export_to(ExportFormat::Text, path.as_os_str(), true).unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "line 3\nline 7\n");
+        assert_eq!(inner.progress.get(), None);
+
+        // an existing file is only replaced with :w!
+        std::fs::write(&path, "keep\n").unwrap();
+        inner.run_command(&format!("w {}", path.to_string_lossy()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep\n");
+        assert!(inner.status_message.as_deref().unwrap().contains("exists, :w! "));
+        inner.run_command(&format!("w! {}", path.to_string_lossy()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "line 3\nline 7\n");
+        assert!(inner.status_message.as_deref().unwrap().starts_with("exported 2 lines to "));
+    }
+
+    #[test]
+    fn test_export_batch() {
+        let filename = test_file("export_batch", "a 1\nb 2\na 3\nc 4\n");
+        let path = test_file("export_batch.ansi", "");
+        let try_export = |args: &[&str]| {
+            let mut argv = vec!["logrok", "--no-state"];
+            argv.extend_from_slice(args);
+            argv.push(filename.to_str().unwrap());
+            let cli = Cli::parse_from(argv);
+            let lines = Lines::new(filename.as_os_str()).unwrap();
+            export_batch(&cli, lines, &Config::default(), path.as_os_str())
+        };
+        let export = |args: &[&str]| {
+            try_export(args).unwrap();
+            let out = std::fs::read(&path).unwrap();
+            String::from_utf8(ansi::visible(&out, true).into_owned()).unwrap()
+        };
+        assert_eq!(export(&["-t", "^a", "--tagged"]), "a 1\na 3\n");
+        assert_eq!(export(&["--hide", "^[bc]"]), "a 1\na 3\n");
+        assert_eq!(export(&["-t", "^a", "-x", "^b"]), "a 1\na 3\nc 4\n");
+        // a mark only colors, the escapes of it are there
+        let marked = export(&["-m", "3"]);
+        assert_eq!(marked, "a 1\nb 2\na 3\nc 4\n");
+        assert!(std::fs::read(&path).unwrap().contains(&0x1b));
+
+        let err = try_export(&["-t", "^d", "--tagged"]).unwrap_err();
+        assert_eq!(exit_code(&err), 4);
+        assert!(quiet_exit(&err));
+
+        let err = Cli::try_parse_from(["logrok", "-t", "(", "log"]).err().unwrap();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn test_goto_anchor() {
+        let content = (0..40).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("goto_anchor", &content, &[], area);
+        let rows = send_key(&mut inner, area, '=');
+        let anchor = LineAnchor::new(&inner.lines, 0, &inner.patterns);
+        assert!(rows[11].starts_with(&format!("anchor {} ", anchor)));
+
+        let content = "new first line\n".to_string() + &content;
+        let mut inner = test_logrok("goto_anchor_edited", &content, &[]);
+        inner.goto_anchor(&anchor);
+        inner.process_event(area, None);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[0].starts_with("  line 0 "));
+    }
+
+    #[test]
+    fn test_log_level_needs_output() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("log_level", "a\nb\n", &[], area);
+        let rows = send_key(&mut inner, area, '!');
+        assert!(rows[11].starts_with("no log file, start with --output"));
+        assert_eq!(inner.focus, Focus::Main);
+    }
+
+    #[test]
+    fn test_debug_splits() {
+        // a bit more than two splits
+        let content = (0..90000).map(|i| format!("line {:021}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("debug_splits", &content, &["--debug-splits"], area);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[11].starts_with("split 0/3 0.."));
+
+        send_key(&mut inner, area, ']');
+        let rows = send_key(&mut inner, area, 's');
+        let (start, _) = inner.lines.get_split(1).unwrap();
+        assert_eq!(inner.cursor_anchor(), Some((start, 0)));
+        assert!(rows[11].starts_with(&format!("split 1/3 {}..", start)));
+        assert!(rows[11].contains(" cached"));
+
+        send_key(&mut inner, area, '[');
+        send_key(&mut inner, area, 's');
+        assert_eq!(inner.cursor_anchor(), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_mark_extend_word() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("mark_extend_word", "GET request_id=abc123 done\n", &[],
+            area);
+        inner.cursor_x = 6;
+        send_key(&mut inner, area, 'm');
+        let mut alt = |c: char| {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT);
+            inner.process_event(area, Some(Event::Key(key)));
+            render_rows(&mut inner, area);
+            let p = inner.patterns.iter().next().unwrap();
+            assert_eq!(p.match_type, MatchType::Text);
+            p.pattern.clone()
+        };
+        assert_eq!(alt('.'), "request_id=");
+        assert_eq!(alt('.'), "request_id=abc123");
+        assert_eq!(alt(','), "request_id=");
+        assert_eq!(alt('<'), " request_id=");
+        assert_eq!(alt('<'), "GET request_id=");
+        // at the start of the line
+        assert_eq!(alt('<'), "GET request_id=");
+        assert_eq!(alt('>'), " request_id=");
+    }
+
+    #[test]
+    fn test_move_tagged() {
+        let content = (0..50).map(|i| format!("line {:02}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("move_tagged", &content, &[], area);
+        inner.lines.toggle_tag(5 * 8);
+        inner.lines.toggle_tag(30 * 8);
+        send_key(&mut inner, area, ']');
+        send_key(&mut inner, area, 't');
+        assert_eq!(inner.cursor_anchor(), Some((5 * 8, 0)));
+
+        // further down, out of the screen
+        send_key(&mut inner, area, ']');
+        let rows = send_key(&mut inner, area, 't');
+        assert_eq!(inner.cursor_anchor(), Some((30 * 8, 0)));
+        assert!(rows.iter().any(|row| row.contains("line 30")));
+        send_key(&mut inner, area, ']');
+        let rows = send_key(&mut inner, area, 't');
+        assert!(rows[11].starts_with("no more tagged lines"));
+
+        send_key(&mut inner, area, '[');
+        send_key(&mut inner, area, 't');
+        assert_eq!(inner.cursor_anchor(), Some((5 * 8, 0)));
+        assert_eq!(inner.display_mode, DisplayMode::Normal);
+    }
+
+    #[test]
+    fn test_display_positions() {
+        let content = (0..50).map(|i| format!("line {:02}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("display_positions", &content, &[], area);
+        inner.lines.toggle_tag(5 * 8);
+        inner.lines.toggle_tag(30 * 8);
+        for _ in 0..20 {
+            send_key(&mut inner, area, 'j');
+        }
+        send_key(&mut inner, area, 'f');
+        assert_eq!(inner.cursor_anchor(), Some((30 * 8, 0)));
+
+        // moving in one mode doesn't lose the position in the other
+        send_key(&mut inner, area, 'k');
+        assert_eq!(inner.cursor_anchor(), Some((5 * 8, 0)));
+        let rows = send_key(&mut inner, area, 'd');
+        assert_eq!(inner.display_mode, DisplayMode::Normal);
+        assert_eq!(inner.cursor_anchor(), Some((20 * 8, 0)));
+        assert_eq!(inner.cursor_y, 10);
+        assert!(rows[10].starts_with("  line 20"));
+        send_key(&mut inner, area, 'j');
+        send_key(&mut inner, area, 'f');
+        assert_eq!(inner.cursor_anchor(), Some((5 * 8, 0)));
+        send_key(&mut inner, area, 'd');
+        assert_eq!(inner.cursor_anchor(), Some((21 * 8, 0)));
+
+        // a line that is no longer shown isn't gone back to
+        inner.lines.toggle_tag(5 * 8);
+        send_key(&mut inner, area, 'f');
+        assert_eq!(inner.cursor_anchor(), Some((30 * 8, 0)));
+    }
+
+    #[test]
+    fn test_mouse_wheel() {
+        let content = (0..50).map(|i| format!("line {:02}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("mouse_wheel", &content, &["--mouse"], area);
+        send_key(&mut inner, area, 'g');
+        send_key(&mut inner, area, 'g');
+        for _ in 0..5 {
+            send_key(&mut inner, area, 'j');
+        }
+        let wheel = |kind| Some(Event::Mouse(MouseEvent {
+            kind,
+            column: 10,
+            row: 3,
+            modifiers: KeyModifiers::NONE,
+        }));
+
+        // the cursor stays on its line
+        inner.process_event(area, wheel(MouseEventKind::ScrollDown));
+        render_rows(&mut inner, area);
+        assert_eq!(inner.first_line, 3 * 8);
+        assert_eq!((inner.cursor_y, inner.cursor_anchor()), (2, Some((5 * 8, 0))));
+        inner.process_event(area, wheel(MouseEventKind::ScrollDown));
+        render_rows(&mut inner, area);
+        assert_eq!(inner.first_line, 6 * 8);
+        assert_eq!((inner.cursor_y, inner.cursor_anchor()), (0, Some((6 * 8, 0))));
+        inner.process_event(area, wheel(MouseEventKind::ScrollUp));
+        render_rows(&mut inner, area);
+        assert_eq!(inner.first_line, 3 * 8);
+        assert_eq!((inner.cursor_y, inner.cursor_anchor()), (3, Some((6 * 8, 0))));
+
+        // only the wheel is used
+        inner.process_event(area, wheel(MouseEventKind::Moved));
+        assert_eq!(inner.first_line, 3 * 8);
+        assert!(Logrok::relevant(&wheel(MouseEventKind::ScrollUp).unwrap()));
+        assert!(!Logrok::relevant(&wheel(MouseEventKind::Moved).unwrap()));
+    }
+
+    #[test]
+    fn test_column_lock() {
+        let content = format!("10 alpha 1\n11 {}\n12\n13 gamma 3\n", "b".repeat(42));
+        let area = Rect::new(0, 0, 42, 12);
+        // continuation rows start in the first column
+        let mut inner = rendered_logrok("column_lock", &content, &["--indent", "0"], area);
+        send_key(&mut inner, area, 'g');
+        send_key(&mut inner, area, 'g');
+        for c in "lll|".chars() {
+            send_key(&mut inner, area, c);
+        }
+        assert_eq!(inner.status_message.as_deref(), Some("column lock on, j/k keep the column"));
+
+        // over the continuation row of the long line and past the end of the short one
+        let ids = [0, 11, 57, 60];
+        for (row, id) in [(1, ids[1]), (3, ids[2]), (4, ids[3])] {
+            send_key(&mut inner, area, 'j');
+            assert_eq!((inner.cursor_x, inner.cursor_y), (3, row));
+            assert_eq!(inner.cursor_anchor(), Some((id, 0)));
+        }
+        send_key(&mut inner, area, '3');
+        send_key(&mut inner, area, 'k');
+        assert_eq!((inner.cursor_x, inner.cursor_y), (3, 0));
+
+        // fields
+        send_key(&mut inner, area, '3');
+        send_key(&mut inner, area, 'j');
+        assert_eq!(inner.cursor_y, 4);
+        send_key(&mut inner, area, '_');
+        assert!(inner.plines[3].chars[3].matches.is_some());
+        assert!(inner.plines[0].chars[3].matches.is_none());
+        send_key(&mut inner, area, 'k');
+        inner.status_message = None;
+        send_key(&mut inner, area, '_');
+        assert_eq!(inner.status_message.as_deref(), Some("no field here"));
+
+        // unlocked, j moves by rows again
+        send_key(&mut inner, area, '|');
+        send_key(&mut inner, area, 'k');
+        assert_eq!(inner.cursor_anchor(), Some((ids[1], 1)));
+    }
+
+    #[test]
+    fn test_mouse_click() {
+        let content = (0..5).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("mouse_click", &content, &["--mouse"], area);
+        let click = |column, row| Some(Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }));
+
+        // the text starts after the markers, below the last line is the last line
+        inner.process_event(area, click(2, 2));
+        render_rows(&mut inner, area);
+        assert_eq!((inner.cursor_x, inner.cursor_y), (0, 2));
+        inner.process_event(area, click(5, 10));
+        render_rows(&mut inner, area);
+        assert_eq!((inner.cursor_x, inner.cursor_y), (3, 4));
+
+        // the markers toggle the tag of the line, unless there is none
+        inner.process_event(area, click(0, 1));
+        render_rows(&mut inner, area);
+        assert!(inner.lines.is_tagged(7));
+        assert_eq!(inner.cursor_y, 1);
+        inner.process_event(area, click(1, 1));
+        render_rows(&mut inner, area);
+        assert!(!inner.lines.is_tagged(7));
+        inner.process_event(area, click(0, 9));
+        render_rows(&mut inner, area);
+        assert!(inner.lines.tagged_lines().is_empty());
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        let cli = Cli::try_parse_from(["logrok", "--chunk-size", "8K", "file"]).unwrap();
+        assert_eq!(cli.chunk_size, 8192);
+        assert!(Cli::try_parse_from(["logrok", "--chunk-size", "1000", "file"]).is_err());
+        assert!(Cli::try_parse_from(["logrok", "--chunk-size", "off", "file"]).is_err());
+        assert!(Cli::try_parse_from(["logrok", "--chunk-size", "2G", "file"]).is_err());
+
+        let content = (0..1000).map(|i| format!("line {:021}\n", i)).collect::<String>();
+        let filename = test_file("chunk_size", &content);
+        let mut out = Vec::new();
+        dump_splits(filename.as_os_str(), cli.chunk_size, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 4);
+    }
+
+    #[test]
+    fn test_dump_splits() {
+        let content = (0..90000).map(|i| format!("line {:021}\n", i)).collect::<String>();
+        let filename = test_file("dump_splits", &content);
+        let mut out = Vec::new();
+        dump_splits(filename.as_os_str(), search::SPLIT_CHUNK_SIZE, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows = out.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3);
+
+        // contiguous ranges of whole lines
+        let (mut next, mut lines) = (0, 0);
+        for (i, row) in rows.iter().enumerate() {
+            let words = row.split(' ').collect::<Vec<_>>();
+            assert_eq!(words[1], format!("{}/3", i));
+            let (start, end) = words[2].split_once("..").unwrap();
+            assert_eq!(start.parse::<u64>().unwrap(), next);
+            next = end.parse().unwrap();
+            let count = words[5].parse::<u64>().unwrap();
+            assert_eq!(count * 27, next - start.parse::<u64>().unwrap());
+            lines += count;
+        }
+        assert_eq!((next, lines), (90000 * 27, 90000));
+    }
+
+    #[test]
+    fn test_regex_search_backward() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("regex_backward", "info\nerr 1\nok\nerr 22\nok\n", &[],
+            area);
+        send_key(&mut inner, area, 'G');
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+
+        let rows = "#err \\d+".chars().map(|c| send_key(&mut inner, area, c)).last().unwrap();
+        assert!(rows[11].starts_with("#err \\d+"));
+        inner.process_event(area, Some(Event::Key(enter)));
+        render_rows(&mut inner, area);
+        assert_eq!(inner.cursor_anchor(), Some((14, 0)));
+
+        // n keeps going backward and wraps like a text search, N goes forward
+        send_key(&mut inner, area, 'n');
+        assert_eq!(inner.cursor_anchor(), Some((5, 0)));
+        inner.status_message = None;
+        send_key(&mut inner, area, 'n');
+        assert_eq!(inner.cursor_anchor(), Some((14, 0)));
+        assert_eq!(inner.status_message.as_deref(), Some("Search wrapped"));
+        send_key(&mut inner, area, 'N');
+        assert_eq!(inner.cursor_anchor(), Some((5, 0)));
+    }
+
+    #[test]
+    fn test_scroll_sideways() {
+        let content = format!("{}\nshort\n", "0123456789".repeat(10));
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("scroll_sideways", &content, &["--no-wrap"], area);
+        let mut alt = |c: char| {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT);
+            inner.process_event(area, Some(Event::Key(key)));
+            let rows = render_rows(&mut inner, area);
+            (inner.horizontal_offset, rows)
+        };
+        let (offset, rows) = alt('l');
+        assert_eq!(offset, 20);
+        assert!(rows[0].starts_with("  0123456789"));
+        // no further than the end of the long line
+        assert_eq!(alt('L').0, 60);
+        let (offset, rows) = alt('l');
+        assert_eq!(offset, 60);
+        assert!(rows[0].trim_end().ends_with("0123456789"));
+        assert_eq!(alt('h').0, 40);
+        assert_eq!(alt('H').0, 0);
+        assert_eq!(alt('H').0, 0);
+
+        send_key(&mut inner, area, 'v');
+        let key = KeyEvent::new(KeyCode::Char('l'), KeyModifiers::ALT);
+        inner.process_event(area, Some(Event::Key(key)));
+        let rows = render_rows(&mut inner, area);
+        assert_eq!(inner.horizontal_offset, 0);
+        assert!(rows[11].starts_with("wrapped lines"));
+    }
+
+    #[test]
+    fn test_desired_column() {
+        let content = "a long line\nab\n\nanother long line\n";
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("desired_column", content, &[], area);
+        inner.cursor_x = 7;
+
+        // the short lines take the cursor to their end, the long one gives the column back
+        let mut columns = Vec::new();
+        for _ in 0..3 {
+            send_key(&mut inner, area, 'j');
+            columns.push(inner.cursor_x);
+        }
+        assert_eq!(columns, vec![1, 0, 7]);
+        send_key(&mut inner, area, 'k');
+        send_key(&mut inner, area, 'k');
+        assert_eq!(inner.resolve_cursor_position(), Some((Some(1), 1, 0)));
+
+        // moving sideways makes it the column to keep
+        send_key(&mut inner, area, 'h');
+        send_key(&mut inner, area, 'j');
+        send_key(&mut inner, area, 'j');
+        assert_eq!(inner.cursor_x, 0);
+    }
+
+    #[test]
+    fn test_search_same_line() {
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("search_same_line", "foo a foo b\nbar\n", &[], area);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        for c in "/foo".chars() {
+            send_key(&mut inner, area, c);
+        }
+        inner.process_event(area, Some(Event::Key(enter)));
+        render_rows(&mut inner, area);
+
+        // with the cursor after both matches, n wraps around to the first of them
+        send_key(&mut inner, area, '$');
+        assert_eq!(inner.cursor_x, 10);
+        inner.status_message = None;
+        send_key(&mut inner, area, 'n');
+        assert_eq!(inner.cursor_anchor(), Some((0, 0)));
+        assert_eq!(inner.cursor_x, 0);
+        assert_eq!(inner.status_message.as_deref(), Some("Search wrapped"));
+        inner.status_message = None;
+        send_key(&mut inner, area, 'n');
+        assert_eq!(inner.cursor_x, 6);
+        assert_eq!(inner.status_message, None);
+        send_key(&mut inner, area, 'n');
+        assert_eq!(inner.cursor_x, 0);
+        assert_eq!(inner.status_message.as_deref(), Some("Search wrapped"));
+    }
+
+    #[test]
+    fn test_center_match() {
+        let content = (0..60)
+            .map(|i| format!("line {}{}\n", i, if i % 25 == 5 { " foo" } else { "" }))
+            .collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("center_match", &content, &[], area);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        let command = |inner: &mut LogrokInner, line: &str| {
+            line.chars().for_each(|c| { send_key(inner, area, c); });
+            inner.process_event(area, Some(Event::Key(enter)));
+            render_rows(inner, area)
+        };
+        command(&mut inner, ":set center");
+        assert!(inner.center_match);
+        command(&mut inner, "/foo");
+        let rows = send_key(&mut inner, area, 'n');
+        assert_eq!(inner.cursor_y, 5);
+        assert!(rows[5].starts_with("  line 30 foo"));
+
+        // at the end of the file, the screen stays filled
+        let rows = send_key(&mut inner, area, 'n');
+        assert!(rows[inner.cursor_y as usize].starts_with("  line 55 foo"));
+        assert!(rows[10].starts_with("  line 59"));
+
+        // without centering, the match line goes to the top
+        command(&mut inner, ":set nocenter");
+        let rows = send_key(&mut inner, area, 'N');
+        assert_eq!(inner.cursor_y, 0);
+        assert!(rows[0].starts_with("  line 30 foo"));
+    }
+
+    #[test]
+    fn test_bigword_delimiters() {
+        let args = ["--bigword-delimiters", ","];
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("bigword_delimiters", "a,bc d\n", &args, area);
+        // W stops on the delimiter after the WORD
+        send_key(&mut inner, area, 'W');
+        assert_eq!(inner.cursor_x, 1);
+        // the mark is a WORD between commas, too
+        send_key(&mut inner, area, 'l');
+        send_key(&mut inner, area, 'M');
+        let (_, matches, _, _) = inner.patterns.process_line("x,bc,y\n", None);
+        assert_eq!(matches.len(), 1);
+
+        // other logs in the same process keep the default
+        let mut inner = rendered_logrok("bigword_default", "a,bc d\n", &[], area);
+        send_key(&mut inner, area, 'W');
+        assert_eq!(inner.cursor_x, 4);
+    }
+
+    #[test]
+    fn test_ansi() {
+        let content = "plain\na \x1b[31mred\x1b[0m error\n";
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("ansi", content, &["--ansi"], area);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[1].starts_with("  a red error "));
+        assert_eq!(inner.plines[1].chars[2].ansi.fg, Some(Color::Red));
+        // the search looks through the escape sequences, too
+        "/red error".chars().for_each(|c| { send_key(&mut inner, area, c); });
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        inner.process_event(area, Some(Event::Key(enter)));
+        render_rows(&mut inner, area);
+        assert_eq!(inner.cursor_anchor(), Some((6, 0)));
+
+        // other logs in the same process show them as they are
+        let inner = rendered_logrok("ansi_off", content, &[], area);
+        assert_eq!(inner.plines[1].chars.len(), content.len() - 7);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let missing = std::env::temp_dir().join(format!("logrok-missing-{}", process::id()));
+        let err = check_log_file(missing.to_str().unwrap()).unwrap_err();
+        assert_eq!(exit_code(&err), 3);
+        // other missing files are plain errors
+        let err = Config::load(Some(missing.as_os_str())).unwrap_err();
+        assert_eq!(exit_code(&err), 1);
+        assert_eq!(exit_code(&Exit::Interrupted.into()), 130);
+        assert!(quiet_exit(&Exit::Interrupted.into()));
+        assert!(!quiet_exit(&err));
+    }
+
+    #[test]
+    fn test_word_end() {
+        let filename = test_file("word_end", "foo bar.baz  qux\n");
+        let cli = Cli::parse_from(["logrok", "--no-state", filename.to_str().unwrap()]);
+        let config = Config::parse("word_end = true\n").unwrap();
+        let mut inner = LogrokInner::new(Lines::new(filename.as_os_str()).unwrap(), &cli, &config);
+        let area = Rect::new(0, 0, 102, 12);
+        inner.process_event(area, None);
+        send_key(&mut inner, area, 'g');
+        send_key(&mut inner, area, 'g');
+
+        let mut columns = Vec::new();
+        for c in "eeeee".chars() {
+            send_key(&mut inner, area, c);
+            columns.push(inner.cursor_x);
+        }
+        assert_eq!(columns, vec![2, 6, 10, 15, 15]);
+
+        // with word_end, b goes back to the previous end and W over the punctuation
+        send_key(&mut inner, area, 'b');
+        assert_eq!(inner.cursor_x, 10);
+        send_key(&mut inner, area, '2');
+        send_key(&mut inner, area, 'b');
+        assert_eq!(inner.cursor_x, 2);
+        send_key(&mut inner, area, 'b');
+        assert_eq!(inner.cursor_x, 2);
+        send_key(&mut inner, area, 'W');
+        assert_eq!(inner.cursor_x, 10);
+
+        // ge/gE go back to the end of the previous word/WORD, E forward to the end of a WORD
+        let mut columns = Vec::new();
+        for keys in ["ge", "gE", "E", "E", "2ge"] {
+            for c in keys.chars() {
+                send_key(&mut inner, area, c);
+            }
+            columns.push(inner.cursor_x);
+        }
+        assert_eq!(columns, vec![6, 2, 10, 15, 6]);
+    }
+
+    #[test]
+    fn test_follow() {
+        let content = (0..5).map(|i| format!("line {}\n", i)).collect::<String>();
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("follow", &content, &[], area);
+
+        // ^W follows the file without -f
+        let ctrl_w = KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL);
+        inner.process_event(area, Some(Event::Key(ctrl_w)));
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[11].contains(" Normal Follow"));
+        let mut file = std::fs::OpenOptions::new().append(true).open(&inner.filename).unwrap();
+        let more = (5..25).map(|i| format!("line {}\n", i)).collect::<String>();
+        file.write_all(more.as_bytes()).unwrap();
+        inner.process_event(area, None);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[10].starts_with("  line 24"));
+
+        // away from the end it pauses
+        send_key(&mut inner, area, 'g');
+        let rows = send_key(&mut inner, area, 'g');
+        assert!(rows[11].contains(" Normal Paused"));
+
+        inner.process_event(area, Some(Event::Key(ctrl_w)));
+        let rows = render_rows(&mut inner, area);
+        assert!(!rows[11].contains("Follow") && !rows[11].contains("Paused"));
+    }
+
+    #[test]
+    fn test_alert() {
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("alert", "ERROR old\ninfo\n", &["-f", "--alert", "ERROR"],
+            area);
+        assert!(!inner.bell);
+        assert!(inner.patterns.is_tagging(inner.alert.unwrap()));
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&inner.filename).unwrap();
+        file.write_all(b"ERROR one\ninfo\nERROR two\n").unwrap();
+        inner.process_event(area, None);
+        render_rows(&mut inner, area);
+        assert!(inner.bell);
+        assert!(inner.alert_flash.is_some());
+        assert_eq!(inner.status_message.as_deref(), Some("alert: 2 new matching lines"));
+
+        // lines not matching are quiet
+        inner.bell = false;
+        inner.status_message = None;
+        file.write_all(b"info\n").unwrap();
+        inner.process_event(area, None);
+        render_rows(&mut inner, area);
+        assert!(!inner.bell);
+        assert_eq!(inner.status_message, None);
+    }
+
+    #[test]
+    fn test_collapse_hidden() {
+        let content = "a\nnoise 1\nnoise 2\nnoise 3\nb\nnoise 4\n";
+        let area = Rect::new(0, 0, 102, 12);
+        let mut inner = rendered_logrok("collapse_hidden", content, &[], area);
+        let style = inner.mark_style.get(MarkType::Hide);
+        inner.add_pattern("noise", MatchType::Text, Anchor::default(), false, style,
+            PatternMode::Hiding);
+        let rows = send_key(&mut inner, area, 'Z');
+        assert!(rows[0].contains('a'));
+        assert!(rows[1].contains("--- 3 lines hidden ---"));
+        assert!(rows[2].contains('b'));
+        assert!(rows[3].contains("--- 1 line hidden ---"));
+
+        // z opens the run under the cursor
+        send_key(&mut inner, area, 'j');
+        let rows = send_key(&mut inner, area, 'z');
+        assert_eq!(inner.cursor_anchor(), Some((2, 0)));
+        assert!(rows[1].contains("noise 1"));
+        assert!(rows[3].contains("noise 3"));
+        assert!(rows[4].contains('b'));
+        assert!(rows[5].contains("--- 1 line hidden ---"));
+        send_key(&mut inner, area, 'k');
+        send_key(&mut inner, area, 'z');
+        assert_eq!(inner.status_message.as_deref(), Some("no collapsed lines here"));
+
+        // without collapsing, the hidden lines vanish again
+        send_key(&mut inner, area, 'j');
+        send_key(&mut inner, area, 'Z');
+        assert_eq!(inner.cursor_anchor(), Some((26, 0)));
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[1].contains('b'));
+        assert!(rows[2].starts_with('~'));
+    }
+
+    #[test]
+    fn test_pattern_list() {
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("pattern_list", "a error\nb\nc error\n", &[], area);
+        // marked and then hidden, like from the keyboard
+        send_key(&mut inner, area, 'l');
+        send_key(&mut inner, area, 'l');
+        send_key(&mut inner, area, 'm');
+        let rows = send_key(&mut inner, area, 'x');
+        let (id, _) = inner.patterns.iter_ids()
+            .find(|(_, pattern)| pattern.mode == PatternMode::Hiding).unwrap();
+        assert!(rows[0].starts_with("  b "));
+
+        // switched off, the pattern stays in the list but hides nothing
+        let ctrl_p = KeyEvent::new(KeyCode::Char('p'), KeyModifiers::CONTROL);
+        inner.process_event(area, Some(Event::Key(ctrl_p)));
+        assert_eq!(inner.focus, Focus::PatternList);
+        send_key(&mut inner, area, ' ');
+        assert!(!inner.patterns.get(id).enabled);
+        let rows = send_key(&mut inner, area, 'q');
+        assert!(rows[0].starts_with("  a error"));
+        assert!(rows[2].starts_with("  c error"));
+
+        inner.process_event(area, Some(Event::Key(ctrl_p)));
+        send_key(&mut inner, area, ' ');
+        let rows = send_key(&mut inner, area, 'q');
+        assert!(inner.patterns.get(id).enabled);
+        assert!(rows[0].starts_with("  b "));
+
+        // hiding the others, then marking, recolored, then gone
+        inner.process_event(area, Some(Event::Key(ctrl_p)));
+        send_key(&mut inner, area, 'm');
+        assert!(inner.patterns.get(id).negative);
+        let rows = send_key(&mut inner, area, 'q');
+        assert!(rows[0].starts_with("  a error"));
+        assert!(rows[1].starts_with("  c error"));
+        inner.process_event(area, Some(Event::Key(ctrl_p)));
+        send_key(&mut inner, area, 'm');
+        assert_eq!(inner.patterns.get(id).mode, PatternMode::Marking);
+        assert!(!inner.patterns.get(id).negative);
+        let style = inner.patterns.get(id).style.style();
+        send_key(&mut inner, area, 'c');
+        assert_ne!(inner.patterns.get(id).style.style(), style);
+        send_key(&mut inner, area, 'm');
+        assert_eq!(inner.patterns.get(id).mode, PatternMode::Tagging);
+        let rows = send_key(&mut inner, area, 'd');
+        assert_eq!(inner.focus, Focus::Main);
+        assert_eq!(inner.patterns.iter().count(), 0);
+        assert!(rows[0].starts_with("  a error"));
+
+        // undo brings back the tagging pattern
+        send_key(&mut inner, area, 'u');
+        assert_eq!(inner.patterns.iter().next().unwrap().mode, PatternMode::Tagging);
+    }
+
+    #[test]
+    fn test_tag_wrapped() {
+        let content = "a\n".to_string() + &"x".repeat(60) + "\nb\n";
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("tag_wrapped", &content, &[], area);
+        send_key(&mut inner, area, 'j');
+        send_key(&mut inner, area, 'j');
+        assert_eq!(inner.line_indexes[inner.cursor_y as usize].line_part, 1);
+
+        let rows = send_key(&mut inner, area, 'T');
+        assert!(inner.lines.is_tagged(2));
+        assert!(!inner.lines.is_tagged(0) && !inner.lines.is_tagged(63));
+        assert!(rows[1].starts_with(inner.glyphs.tagged));
+        assert!(!rows[2].starts_with(inner.glyphs.tagged));
+        assert_eq!(inner.cursor_y, 2);
+    }
+
+    #[test]
+    fn test_mark_text() {
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("mark_text", "ts=1 id=42 ok\nid=421 x\n", &[], area);
+        inner.cursor_x = 7;
+        send_key(&mut inner, area, '*');
+        let p = inner.patterns.iter().next().unwrap();
+        assert_eq!((p.pattern.as_str(), p.match_type), ("id=42", MatchType::Text));
+
+        // a substring, so it matches inside of longer text, too
+        let chars = &inner.plines[0].chars;
+        assert!(chars[4].matches.is_none() && chars[5].matches.is_some());
+        assert!(chars[9].matches.is_some() && chars[10].matches.is_none());
+        assert!(inner.plines[1].chars[0].matches.is_some());
+
+        send_key(&mut inner, area, '*');
+        assert_eq!(inner.patterns.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_filler_rows() {
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("filler_rows", "a\nb\n", &[], area);
+        let rows = render_rows(&mut inner, area);
+        assert_eq!(rows[2], format!("~ ~{}", " ".repeat(39)));
+
+        // the cursor stops at the last line
+        send_key(&mut inner, area, 'j');
+        send_key(&mut inner, area, 'j');
+        send_key(&mut inner, area, 'j');
+        assert_eq!(inner.cursor_y, 1);
+        inner.process_event(area, Some(Event::Key(KeyEvent::new(KeyCode::Down,
+            KeyModifiers::NONE))));
+        assert_eq!(inner.cursor_y, 1);
+    }
+
+    #[test]
+    fn test_hide_others() {
+        let content = "req1 start\nreq2 start\nreq1 done\nreq2 done\n";
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("hide_others", content, &[], area);
+        let style = inner.mark_style.get(MarkType::Mark);
+        let id = inner.add_pattern("req1", MatchType::SmallWord, Anchor::default(), false, style,
+            PatternMode::Marking).unwrap();
+        inner.plines.clear();
+        inner.process_event(area, None);
+        render_rows(&mut inner, area);
+
+        let rows = send_key(&mut inner, area, '~');
+        assert_eq!(inner.patterns.get(id).mode, PatternMode::Hiding);
+        assert!(rows[0].starts_with("  req1 start"));
+        assert!(rows[1].starts_with("  req1 done"));
+        assert!(!rows[2].contains("req2"));
+
+        // all lines are there in all mode, the others marked as hidden by a match
+        send_key(&mut inner, area, 'd');
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[1].starts_with(&format!("{}req2 start", inner.glyphs.hidden_match)));
+        send_key(&mut inner, area, 'f');
+
+        // a second one shows its lines too, a plain hiding pattern still hides
+        let style = inner.mark_style.get(MarkType::Hide);
+        let other = inner.add_pattern("req2", MatchType::SmallWord, Anchor::default(), false,
+            style.clone(), PatternMode::Hiding).unwrap();
+        inner.patterns.with(other, |p| p.negative = true);
+        inner.add_pattern("done", MatchType::SmallWord, Anchor::default(), false, style,
+            PatternMode::Hiding).unwrap();
+        inner.update_patterns(PatternMode::Hiding);
+        inner.plines.clear();
+        inner.process_event(area, None);
+        let rows = render_rows(&mut inner, area);
+        assert!(rows[0].starts_with("  req1 start"));
+        assert!(rows[1].starts_with("  req2 start"));
+        assert!(!rows[2].contains("done"));
+
+        // and back to a mark
+        send_key(&mut inner, area, '~');
+        assert_eq!(inner.patterns.get(id).mode, PatternMode::Marking);
+    }
+
+    #[test]
+    fn test_change_context() {
+        let area = Rect::new(0, 0, 42, 12);
+        let mut inner = rendered_logrok("change_context", "a\nb\ntag\nc\nd\n", &[], area);
+        let style = inner.mark_style.get(MarkType::Tag);
+        inner.add_pattern("tag", MatchType::Text, Anchor::default(), false, style,
+            PatternMode::Tagging);
+        send_key(&mut inner, area, 'f');
+        send_key(&mut inner, area, 'g');
+        let rows = send_key(&mut inner, area, 'g');
+        assert_eq!(inner.display_mode, DisplayMode::Tagged);
+        assert!(rows[0].contains("tag"));
+        assert!(rows[1].starts_with('~'));
+
+        let rows = send_key(&mut inner, area, '}');
+        assert_eq!(inner.status_message.as_deref(), Some("context 1 lines"));
+        assert!(rows[0].contains("b "));
+        assert!(rows[2].contains("c "));
+        assert!(rows[3].starts_with('~'));
+
+        send_key(&mut inner, area, '3');
+        send_key(&mut inner, area, '0');
+        send_key(&mut inner, area, '0');
+        let rows = send_key(&mut inner, area, '}');
+        assert_eq!(inner.lines.context(), MAX_CONTEXT);
+        assert!(rows[4].contains("d "));
+
+        send_key(&mut inner, area, '2');
+        send_key(&mut inner, area, '0');
+        send_key(&mut inner, area, '0');
+        send_key(&mut inner, area, '{');
+        assert_eq!(inner.lines.context(), 0);
+    }
+}
@@ -1,17 +1,34 @@
 use regex::bytes::RegexSet;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use regex::Regex;
+use std::sync::Arc;
+use memchr::memmem::Finder;
+use ratatui::style::Style;
 
 use crate::MarkStyle;
+use crate::ansi;
 
 pub type PatternId = usize;
 
+// beyond these limits matches on a line are only styled, without per-char bookkeeping
+const MAX_MATCHES_PER_PATTERN: usize = 256;
+const MAX_MATCHES_PER_LINE: usize = 1024;
+
 #[derive(Debug, Clone)]
 pub struct StyledChar {
     pub c: char,
+    pub raw: Option<char>,  // the char of the line, a tab where c is a space, None in its padding
     pub matches: Option<Vec<(PatternId, usize)>>, // Option to avoid allocations, (id, pos in match)
     pub style: MarkStyle,
+    pub ansi: Style,    // from escape sequences in the log, under the mark style
+}
+
+impl StyledChar {
+    pub fn display_style(&self) -> Style {
+        self.ansi.patch(self.style.style())
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -22,33 +39,102 @@ pub enum MatchType {
     Regex,
 }
 
+// restricts a pattern to the start and/or end of the line
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct Anchor {
+    pub start: bool,
+    pub end: bool,
+}
+
+impl Anchor {
+    // strip a leading '^' and a trailing '$' from user input. A backslash keeps them literal
+    pub fn parse(input: &str) -> (String, Anchor) {
+        let mut anchor = Anchor::default();
+        let mut s = input;
+        if let Some(rest) = s.strip_prefix('^') {
+            anchor.start = true;
+            s = rest;
+        } else if s.starts_with("\\^") {
+            s = &s[1..];
+        }
+        let mut pattern = s.to_string();
+        if pattern.ends_with("\\$") {
+            pattern.remove(pattern.len() - 2);
+        } else if pattern.ends_with('$') {
+            anchor.end = true;
+            pattern.pop();
+        }
+
+        (pattern, anchor)
+    }
+}
+
+// WORDs are only separated by whitespace, unless the pattern set has more delimiters
+pub const BIGWORD_DELIMITERS: &str = " \t";
+const SMALLWORD_DELIMITERS: &str = " \t:.,\"';()[]{}<>=+-*/&|^~!@#$%?";
+
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
+// the text of the line the cells show, for building patterns that match it. Tabs are
+// tabs again, a range starting in the padding of a tab includes the tab
+pub fn source_text(chars: &[StyledChar], range: std::ops::Range<usize>) -> String {
+    let mut start = range.start;
+    while start > 0 && start < range.end && chars[start].raw.is_none() {
+        start -= 1;
+    }
+
+    chars[start..range.end].iter().filter_map(|sc| sc.raw).collect()
+}
+
+pub fn source_char(chars: &[StyledChar], pos: usize) -> char {
+    source_text(chars, pos..pos + 1).chars().next().unwrap_or(chars[pos].c)
+}
+
+// regex character class matching any of the given characters
+fn charclass(chars: &str) -> String {
+    let mut class = String::from("[");
+    for c in chars.chars() {
+        // escape everything that is special in a class, including set operators
+        if matches!(c, '\\' | '[' | ']' | '-' | '^' | '&' | '~') {
+            class.push('\\');
+        }
+        class.push(c);
+    }
+    class.push(']');
+    class
+}
+
 impl MatchType {
-    pub fn delimiter(&self) -> &'static str {
+    // bigword are the delimiters of WORDs, usually those of the pattern set
+    pub fn delimiter<'a>(&self, bigword: &'a str) -> &'a str {
         match self {
-            MatchType::BigWord => " \t",
-            MatchType::SmallWord => " \t:.,\"';()[]{}<>=+-*/&|^~!@#$%?",  // see also build_re
+            MatchType::BigWord => bigword,
+            MatchType::SmallWord => SMALLWORD_DELIMITERS,
             MatchType::Text => "",
             MatchType::Regex => "",
         }
     }
 
-    pub fn build_re(&self, pattern: &str) -> String {
-        match self {
-            MatchType::BigWord => {
-                let charclass = "[\t ]";
-                format!("(?:{}|^|\n)({})(?:$|\n|{})", charclass, regex::escape(pattern), charclass)
-            }
-            MatchType::SmallWord => {
-                let charclass = "[\t :.,\"';()\\[\\]{}<>=+\\-*/&|^~!@#$%?]";
-                format!("(?:{}|^|\n)({})(?:$|\n|{})", charclass, regex::escape(pattern), charclass)
-            }
-            MatchType::Text => {
-                format!("({})", regex::escape(pattern))
+    // the regex is used on single lines as well as on whole splits, so anchors have to
+    // match at line boundaries (multi-line mode)
+    pub fn build_re(&self, pattern: &str, anchor: Anchor, ignore_case: bool, bigword: &str)
+        -> String
+    {
+        let flags = if ignore_case { "(?i)" } else { "" };
+        let (start, end) = match self {
+            MatchType::BigWord | MatchType::SmallWord => {
+                let charclass = charclass(self.delimiter(bigword));
+                (format!("(?:{}|^|\n)", charclass), format!("(?:$|\n|{})", charclass))
             }
+            MatchType::Text | MatchType::Regex => (String::new(), String::new()),
+        };
+        let start = if anchor.start { "(?m:^)".to_string() } else { start };
+        let end = if anchor.end { "(?m:$)".to_string() } else { end };
+        match self {
             MatchType::Regex => {
-                // TODO: validate pattern
-                format!(r"({})", pattern)
+                format!(r"{}{}((?m:{})){}", flags, start, pattern, end)
             }
+            _ => format!("{}{}({}){}", flags, start, regex::escape(pattern), end),
         }
     }
 }
@@ -61,12 +147,65 @@ pub enum PatternMode {
     Search,
 }
 
+// matches lines that match any of re, or, if there are any, none of unless. Only meant
+// for single lines, unless is empty
+#[derive(Debug, Clone)]
+pub struct LineMatcher {
+    pub re: RegexSet,
+    pub unless: RegexSet,
+    // the same patterns as re, if they are all plain text. They are found without the regex
+    // engine then
+    literals: Option<Arc<Vec<Finder<'static>>>>,
+    ansi: bool, // escape sequences are removed before matching
+}
+
+impl LineMatcher {
+    pub fn new(re: RegexSet) -> Self {
+        LineMatcher { re, unless: RegexSet::new([""; 0]).unwrap(), literals: None, ansi: false }
+    }
+
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.ansi = ansi;
+        self
+    }
+
+    // what the patterns are matched against
+    pub fn visible<'a>(&self, bytes: &'a [u8]) -> Cow<'a, [u8]> {
+        ansi::visible(bytes, self.ansi)
+    }
+
+    // see PatternSet::literals
+    pub fn with_literals(mut self, literals: Option<Vec<String>>) -> Self {
+        self.literals = literals.map(|literals| Arc::new(literals.iter()
+            .map(|literal| Finder::new(literal).into_owned())
+            .collect()));
+        self
+    }
+
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        let matched = match self.literals {
+            Some(ref finders) => finders.iter().any(|finder| finder.find(line).is_some()),
+            None => self.re.is_match(line),
+        };
+        matched || (!self.unless.is_empty() && !self.unless.is_match(line))
+    }
+
+    // matches nothing at all
+    pub fn is_empty(&self) -> bool {
+        self.re.is_empty() && self.unless.is_empty()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Pattern {
     pub pattern: String,
     pub style: MarkStyle,
     pub mode: PatternMode,
     pub match_type: MatchType,
+    pub anchor: Anchor,
+    pub ignore_case: bool,
+    pub enabled: bool,  // a disabled pattern keeps its place and color, but matches nothing
+    pub negative: bool, // hiding only: hides the lines that don't match
     re: Regex,
 }
 
@@ -79,6 +218,12 @@ pub struct PatternSet {
     pub tagged_re: RegexSet,
     pub search_re: RegexSet,
     pub hidden_re: RegexSet,
+    // negative hiding patterns. A line is shown if it matches any of them, so several of
+    // them widen what is shown. Lines matching a plain hiding pattern are still hidden
+    pub shown_re: RegexSet,
+    tab_width: usize,   // tabs are expanded to the next multiple of it
+    bigword_delimiters: String,
+    ansi: bool,         // escape sequences are interpreted, patterns match the text without them
 }
 
 impl PatternSet {
@@ -88,32 +233,82 @@ impl PatternSet {
             tagged_re: RegexSet::new(&[""; 0]).unwrap(),
             search_re: RegexSet::new(&[""; 0]).unwrap(),
             hidden_re: RegexSet::new(&[""; 0]).unwrap(),
+            shown_re: RegexSet::new(&[""; 0]).unwrap(),
             seq: 1,
             sort_by_len: Vec::new(),
             default_style,
+            tab_width: DEFAULT_TAB_WIDTH,
+            bigword_delimiters: BIGWORD_DELIMITERS.to_string(),
+            ansi: false,
+        }
+    }
+
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.ansi = ansi;
+        self
+    }
+
+    pub fn ansi(&self) -> bool {
+        self.ansi
+    }
+
+    // what is displayed of a line, see ansi::visible
+    pub fn visible<'a>(&self, bytes: &'a [u8]) -> Cow<'a, [u8]> {
+        ansi::visible(bytes, self.ansi)
+    }
+
+    // characters that separate WORDs in addition to whitespace
+    pub fn with_bigword_delimiters(mut self, extra: &str) -> Self {
+        for c in extra.chars() {
+            if !self.bigword_delimiters.contains(c) {
+                self.bigword_delimiters.push(c);
+            }
         }
+        self
+    }
+
+    pub fn delimiter(&self, match_type: MatchType) -> &str {
+        match_type.delimiter(&self.bigword_delimiters)
+    }
+
+    pub fn build_re(&self, match_type: MatchType, pattern: &str, anchor: Anchor,
+        ignore_case: bool) -> String
+    {
+        match_type.build_re(pattern, anchor, ignore_case, &self.bigword_delimiters)
     }
 
     fn rebuild_re(&mut self) {
         self.seq += 1;
+        let bigword = &self.bigword_delimiters;
         let tagged_patterns = self.patterns
             .values()
-            .filter(|p| p.mode == PatternMode::Tagging)
-            .map(|p| p.match_type.build_re(&p.pattern));
+            .filter(|p| p.enabled && p.mode == PatternMode::Tagging)
+            .map(|p| p.match_type.build_re(&p.pattern, p.anchor, p.ignore_case, bigword));
         self.tagged_re = RegexSet::new(tagged_patterns).unwrap();
 
         let search_patterns = self.patterns
             .values()
-            .filter(|p| p.mode == PatternMode::Search)
-            .map(|p| p.match_type.build_re(&p.pattern));
+            .filter(|p| p.enabled && p.mode == PatternMode::Search)
+            .map(|p| p.match_type.build_re(&p.pattern, p.anchor, p.ignore_case, bigword));
         self.search_re = RegexSet::new(search_patterns).unwrap();
 
         let hidden_patterns = self.patterns.values()
-            .filter(|p| p.mode == PatternMode::Hiding)
-            .map(|p| p.match_type.build_re(&p.pattern));
+            .filter(|p| p.enabled && p.mode == PatternMode::Hiding && !p.negative)
+            .map(|p| p.match_type.build_re(&p.pattern, p.anchor, p.ignore_case, bigword));
         self.hidden_re = RegexSet::new(hidden_patterns).unwrap();
 
+        let shown_patterns = self.patterns.values()
+            .filter(|p| p.enabled && p.mode == PatternMode::Hiding && p.negative)
+            .map(|p| p.match_type.build_re(&p.pattern, p.anchor, p.ignore_case, bigword));
+        self.shown_re = RegexSet::new(shown_patterns).unwrap();
+
         let mut lengths = self.patterns.iter()
+            .filter(|(_, p)| p.enabled)
             .map(|(id, p)| (id, p.pattern.len()))
             .collect::<Vec<_>>();
         lengths.sort_by_key(|&(_, len)| len);
@@ -121,21 +316,26 @@ impl PatternSet {
 
     }
 
-    pub fn add(&mut self, pattern: &str, match_type: MatchType, style: MarkStyle,
-        mode: PatternMode) -> PatternId
+    // fails if the pattern isn't a valid regex
+    pub fn add(&mut self, pattern: &str, match_type: MatchType, anchor: Anchor, ignore_case: bool,
+        style: MarkStyle, mode: PatternMode) -> Result<PatternId, regex::Error>
     {
         let id = self.seq;
-        let re = Regex::new(&match_type.build_re(pattern)).unwrap();
+        let re = Regex::new(&self.build_re(match_type, pattern, anchor, ignore_case))?;
         let pat = Pattern {
             pattern: pattern.to_string(),
             style,
             mode,
             match_type,
+            anchor,
+            ignore_case,
+            enabled: true,
+            negative: false,
             re,
         };
         self.patterns.insert(id, pat);
         self.rebuild_re();
-        id
+        Ok(id)
     }
 
     pub fn remove(&mut self, id: PatternId) {
@@ -144,6 +344,14 @@ impl PatternSet {
         self.rebuild_re();
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &Pattern> {
+        self.patterns.values()
+    }
+
+    pub fn iter_ids(&self) -> impl Iterator<Item = (PatternId, &Pattern)> {
+        self.patterns.iter().map(|(&id, p)| (id, p))
+    }
+
     pub fn get(&self, id: PatternId) -> &Pattern {
         self.patterns.get(&id).unwrap()
     }
@@ -153,7 +361,13 @@ impl PatternSet {
     {
         let pattern = self.patterns.get_mut(&id).unwrap();
         f(pattern);
-        pattern.re = Regex::new(&pattern.match_type.build_re(&pattern.pattern)).unwrap();
+        pattern.re = Regex::new(&pattern.match_type.build_re(&pattern.pattern, pattern.anchor,
+            pattern.ignore_case, &self.bigword_delimiters)).unwrap();
+        self.rebuild_re();
+    }
+
+    pub fn set_enabled(&mut self, id: PatternId, enabled: bool) {
+        self.patterns.get_mut(&id).unwrap().enabled = enabled;
         self.rebuild_re();
     }
 
@@ -165,6 +379,30 @@ impl PatternSet {
         self.get(id).mode == PatternMode::Hiding
     }
 
+    // whether a line with these matching patterns is hidden
+    pub fn hides(&self, matches: &[PatternId]) -> bool {
+        let positive = matches.iter().any(|&id| self.is_hiding(id) && !self.get(id).negative);
+        let shown = matches.iter().any(|&id| self.is_hiding(id) && self.get(id).negative);
+
+        positive || (!self.shown_re.is_empty() && !shown)
+    }
+
+    // false if the pattern has been removed meanwhile
+    pub fn is_match(&self, id: PatternId, line: &str) -> bool {
+        self.patterns.get(&id).is_some_and(|p| p.enabled && p.re.is_match(line))
+    }
+
+    // whether both sets match the same lines in the mode
+    pub fn same_re(&self, other: &PatternSet, mode: PatternMode) -> bool {
+        match mode {
+            PatternMode::Tagging => self.tagged_re.patterns() == other.tagged_re.patterns(),
+            PatternMode::Search => self.search_re.patterns() == other.search_re.patterns(),
+            PatternMode::Hiding => self.hidden_re.patterns() == other.hidden_re.patterns() &&
+                self.shown_re.patterns() == other.shown_re.patterns(),
+            PatternMode::Marking => true,
+        }
+    }
+
     pub fn get_tagged_re(&self) -> RegexSet {
         self.tagged_re.clone()
     }
@@ -173,58 +411,120 @@ impl PatternSet {
         self.search_re.clone()
     }
 
-    pub fn get_hidden_re(&self) -> RegexSet {
-        self.hidden_re.clone()
+    pub fn get_hidden_re(&self) -> LineMatcher {
+        LineMatcher { re: self.hidden_re.clone(), unless: self.shown_re.clone(), literals: None,
+            ansi: self.ansi }
     }
 
+    // the patterns of the mode as they are, if none of them needs the regex engine: text
+    // that is neither anchored nor matched ignoring case
+    pub fn literals(&self, mode: PatternMode) -> Option<Vec<String>> {
+        let patterns = self.patterns.values()
+            .filter(|p| p.enabled && p.mode == mode)
+            .collect::<Vec<_>>();
+        if patterns.is_empty() {
+            return None;
+        }
+        patterns.into_iter()
+            .map(|p| (p.match_type == MatchType::Text && !p.ignore_case
+                && p.anchor == Anchor::default()).then(|| p.pattern.clone()))
+            .collect()
+    }
+
+    // returns the styled chars, the ids of all matching patterns, whether the line got cropped
+    // and whether a pattern matched too often to keep track of all matches
     pub fn process_line(&self, line: &str, crop_chars: Option<usize>)
-        -> (Vec<StyledChar>, Vec<PatternId>, bool)
+        -> (Vec<StyledChar>, Vec<PatternId>, bool, bool)
     {
         let mut pline = Vec::new();
         let mut matches = BTreeSet::new();
 
+        // patterns match what is displayed, without the escape sequences
+        let parsed;
+        let (line, ansi_styles) = if self.ansi {
+            parsed = ansi::parse(line);
+            (parsed.0.as_str(), Some(&parsed.1))
+        } else {
+            (line, None)
+        };
+        let tab_width = self.tab_width;
+        let crop = crop_chars.unwrap_or(usize::MAX);
+        // the pline index of each byte of the line, matches are found in bytes
+        let mut columns = Vec::with_capacity(line.len() + 1);
         let mut bytes = 0;
         let mut cropped = false;
-        for c in line.chars() {
-            pline.push(StyledChar {
-                c,
-                style: self.default_style.clone(),
-                matches: None,
-            });
+        for (char_ix, c) in line.chars().enumerate() {
+            columns.extend(std::iter::repeat_n(pline.len(), c.len_utf8()));
+            let (shown, width) = if c == '\t' {
+                (' ', tab_width - pline.len() % tab_width)
+            } else {
+                (c, 1)
+            };
+            for i in 0..width {
+                pline.push(StyledChar {
+                    c: shown,
+                    raw: (i == 0).then_some(c),
+                    style: self.default_style.clone(),
+                    ansi: ansi_styles.map_or(Style::default(), |s| s[char_ix]),
+                    matches: None,
+                });
+            }
             bytes += c.len_utf8();
-            if pline.len() >= crop_chars.unwrap_or(usize::MAX) {
+            if pline.len() >= crop {
+                pline.truncate(crop);
                 cropped = true;
                 break;
             }
         }
+        columns.push(pline.len());
         if pline.last().map(|c| c.c) == Some('\n') {
             pline.pop();
         }
         let mut match_num = 0;
+        let mut broad = false;
         for &id in &self.sort_by_len {
             let pattern = self.get(id);
             // only match what we have in pline, plus the pattern length so we can catch
-            // a pattern match over the end
-            let match_len = (bytes + pattern.pattern.len()).min(line.len());
+            // a pattern match over the end. Anchoring to the end needs the full line
+            let match_len = if pattern.anchor.end {
+                line.len()
+            } else {
+                (bytes + pattern.pattern.len()).min(line.len())
+            };
+            let mut pattern_matches = 0;
             for c in pattern.re.captures_iter(&line[..match_len]) {
                 let m = c.get(1).unwrap();
-                for i in m.start() .. m.end() {
+                let track = pattern_matches < MAX_MATCHES_PER_PATTERN &&
+                    match_num < MAX_MATCHES_PER_LINE;
+                broad |= !track;
+                // a match beyond the crop starts after the last column
+                let start = columns.get(m.start()).copied().unwrap_or(pline.len());
+                let end = columns.get(m.end()).copied().unwrap_or(pline.len());
+                for i in start .. end {
                     if i >= pline.len() {
                         break;
                     }
                     pline[i].style = pattern.style.clone();
+                    if !track {
+                        continue;
+                    }
                     if let Some(ref mut matches) = pline[i].matches {
                         matches.push((id, i));
                     } else {
                         pline[i].matches = Some(vec![(id, match_num)]);
                     }
-                    matches.insert(id);
                 }
+                matches.insert(id);
                 match_num += 1;
+                pattern_matches += 1;
+            }
+            // the line matches even if only the cropped part does, it just isn't styled
+            if match_len < line.len() && !matches.contains(&id) && pattern.re.is_match(line) {
+                matches.insert(id);
             }
         }
 
         let matches = matches.into_iter().collect();
-        (pline, matches, cropped)
+        (pline, matches, cropped, broad)
     }
 }
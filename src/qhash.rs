@@ -0,0 +1,49 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, Read};
+
+// only this much of the start of a file goes into the hash
+const QHASH_PREFIX: u64 = 1024 * 1024;
+
+// cheap fingerprint of a file: the length and a hash over the start of the file. A file
+// that only got appended to still matches its old fingerprint
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct QHash {
+    pub len: u64,
+    pub hash: u64,
+}
+
+// fnv-1a, stable across builds unlike the std hasher
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+fn hash_prefix(file: &mut File, len: u64) -> io::Result<u64> {
+    let mut buf = Vec::new();
+    file.take(len.min(QHASH_PREFIX)).read_to_end(&mut buf)?;
+
+    Ok(fnv1a(&buf))
+}
+
+pub fn qhash(filename: &OsStr) -> io::Result<QHash> {
+    let mut file = File::open(filename)?;
+    let len = file.metadata()?.len();
+
+    Ok(QHash { len, hash: hash_prefix(&mut file, len)? })
+}
+
+// does the file still start with what was fingerprinted
+pub fn check(qhash: &QHash, filename: &OsStr) -> io::Result<bool> {
+    let mut file = File::open(filename)?;
+    if file.metadata()?.len() < qhash.len {
+        return Ok(false);
+    }
+
+    Ok(hash_prefix(&mut file, qhash.len)? == qhash.hash)
+}
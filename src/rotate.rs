@@ -0,0 +1,115 @@
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use flate2::read::MultiGzDecoder;
+
+use crate::spool::Source;
+
+// rotation index of a sibling of the log, like 2 for app.log.2 or app.log.2.gz
+fn rotation_index(name: &str, base: &str) -> Option<u32> {
+    let suffix = name.strip_prefix(base)?.strip_prefix('.')?;
+    let index = suffix.strip_suffix(".gz").unwrap_or(suffix);
+    if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    index.parse().ok()
+}
+
+// the log and its rotated predecessors from the same directory, oldest first
+pub fn discover(filename: &OsStr) -> io::Result<Vec<PathBuf>> {
+    let path = Path::new(filename);
+    let Some(base) = path.file_name().and_then(|n| n.to_str()) else {
+        return Ok(vec![path.to_path_buf()]);
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut rotated = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(index) = name.to_str().and_then(|n| rotation_index(n, base)) else {
+            continue;
+        };
+        // higher indexes are older, mtime breaks ties like app.log.1 and app.log.1.gz
+        let mtime = entry.metadata()?.modified()?;
+        rotated.push((index, mtime, entry.path()));
+    }
+    rotated.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut files = rotated.into_iter().map(|(_, _, path)| path).collect::<Vec<_>>();
+    files.push(path.to_path_buf());
+
+    Ok(files)
+}
+
+// gzip files are recognized by their magic bytes, whatever they are called. Only regular
+// files are sniffed, reading from a pipe would eat the bytes
+pub fn is_gzip(path: &Path) -> io::Result<bool> {
+    if !path.metadata()?.is_file() {
+        return Ok(false);
+    }
+    let mut magic = [0; 2];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(magic == [0x1f, 0x8b]),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// read a file, decompressing it if it is gzipped
+pub fn open(path: &Path) -> io::Result<Source> {
+    let file = File::open(path)?;
+    if is_gzip(path)? {
+        Ok(Box::new(MultiGzDecoder::new(BufReader::new(file))))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempPath;
+
+    #[test]
+    fn test_discover() {
+        let dir = TempPath::dir("rotate");
+        for name in ["app.log", "app.log.1", "app.log.2.gz", "app.log.10", "app.log.old",
+            "app.log.1.bak", "other.log.1"]
+        {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let files = discover(dir.join("app.log").as_os_str()).unwrap();
+        let names = files.iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["app.log.10", "app.log.2.gz", "app.log.1", "app.log"]);
+    }
+
+    #[test]
+    fn test_gzip() {
+        let dir = TempPath::dir("gzip");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        io::Write::write_all(&mut encoder, b"compressed\n").unwrap();
+        // without the extension
+        fs::write(dir.join("app.log.1"), encoder.finish().unwrap()).unwrap();
+        fs::write(dir.join("app.log"), "plain\n").unwrap();
+        fs::write(dir.join("empty.gz"), "").unwrap();
+
+        assert!(is_gzip(&dir.join("app.log.1")).unwrap());
+        assert!(!is_gzip(&dir.join("app.log")).unwrap());
+        assert!(!is_gzip(&dir.join("empty.gz")).unwrap());
+        assert!(!is_gzip(&dir).unwrap());
+        for (name, content) in [("app.log.1", "compressed\n"), ("app.log", "plain\n")] {
+            let mut read = String::new();
+            open(&dir.join(name)).unwrap().read_to_string(&mut read).unwrap();
+            assert_eq!(read, content);
+        }
+    }
+}
@@ -11,12 +11,20 @@ use anyhow::Result;
 use regex::bytes::RegexSet;
 use bitvec::prelude::*;
 use clog::prelude::*;
+use memmap2::Mmap;
 
 use crate::log::LogKeys::SE;
 
 use crate::lines::LineId;
+use crate::pattern::LineMatcher;
 
-const SPLIT_CHUNK_SIZE: LineId = 1048576;
+pub const SPLIT_CHUNK_SIZE: LineId = 1048576;
+// smaller splits mostly add overhead, there are few lines in each
+pub const MIN_CHUNK_SIZE: LineId = 4096;
+// a split is read into memory as a whole
+pub const MAX_CHUNK_SIZE: LineId = 1 << 30;
+const READ_RETRIES: usize = 3;
+const READ_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(50);
 
 pub type SplitId = usize;
 
@@ -27,7 +35,9 @@ pub struct FileSearchReState {
     split_has_matches: BitVec<usize, Lsb0>,
     split_dirty: BitVec<usize, Lsb0>,
     re_seq: u64,
-    re: RegexSet,
+    re: LineMatcher,
+    all_lines: bool, // a split only matches if every line in it matches
+    match_counts: Option<Vec<u64>>, // matching lines per split, if they are counted
 }
 
 #[derive(Debug)]
@@ -35,11 +45,23 @@ pub struct FileSearchInner {
     filename: OsString,
     thread_handles: Vec<std::thread::JoinHandle<()>>,
     split_ids: Vec<LineId>, // ends of splits
+    chunk_size: LineId,     // splits are about this long, longer if a line is
     max_split_len: LineId,
     // shared state
     re_states: Vec<FileSearchReState>,
     split_in_progress: BitVec<usize, Lsb0>,
     current_split: usize, // index of the split that contains the current line
+    // number of lines per split, only counted on demand
+    line_counts: Vec<Option<u64>>,
+    lines_dirty: BitVec<usize, Lsb0>,
+    lines_requested: bool,
+    lines_seq: u64, // changes when splits change, to discard stale line counts
+    // prefix sums of line_counts, the number of lines before each split, as far as known
+    lines_before: Vec<u64>,
+    read_failed: BitVec<usize, Lsb0>, // splits that couldn't be read, retried on grow
+    read_error: Option<String>, // last read error, for the user
+    map: Option<Arc<Mmap>>, // the mapped file, splits in it aren't read
+    file_seq: u64, // changes when the file is read from scratch, results in flight are stale
 }
 
 #[derive(Debug, Clone)]
@@ -50,9 +72,9 @@ pub struct FileSearch {
 }
 
 impl FileSearch {
-    pub fn new(filename: &OsStr, num_res: usize) -> Result<Self> {
+    pub fn new(filename: &OsStr, num_res: usize, chunk_size: LineId) -> Result<Self> {
         // TODO: split in background, multi-threaded
-        let split_ids = split_file(&filename, SPLIT_CHUNK_SIZE)?;
+        let split_ids = split_file(&filename, chunk_size)?;
         let nsplits = split_ids.len();
         let mut start = 0;
         let mut max_split_len = 0;
@@ -67,7 +89,9 @@ impl FileSearch {
                 split_has_matches: bitvec![0; nsplits],
                 split_dirty: bitvec![0; nsplits],
                 re_seq: 0,
-                re: RegexSet::new(&[""; 0]).unwrap(), // never
+                re: LineMatcher::new(RegexSet::new([""; 0]).unwrap()), // never
+                all_lines: false,
+                match_counts: None,
             });
         }
         let this = FileSearch {
@@ -76,10 +100,20 @@ impl FileSearch {
                     filename: filename.into(),
                     thread_handles: Vec::new(),
                     split_ids,
+                    chunk_size,
                     max_split_len,
                     re_states,
                     split_in_progress: bitvec![0; nsplits],
                     current_split: 0,
+                    line_counts: vec![None; nsplits],
+                    lines_dirty: bitvec![0; nsplits],
+                    lines_requested: false,
+                    lines_seq: 0,
+                    lines_before: vec![0],
+                    read_failed: bitvec![0; nsplits],
+                    read_error: None,
+                    map: None,
+                    file_seq: 0,
                 }),
                 Condvar::new(),
                 Condvar::new()),
@@ -103,14 +137,129 @@ impl FileSearch {
         Ok(this)
     }
 
-    pub fn set_re(&mut self, ix: usize, re: &RegexSet) {
+    pub fn set_re(&mut self, ix: usize, re: &LineMatcher, all_lines: bool, count: bool) {
         let mut inner = self.inner.0.lock().unwrap();
         assert!(ix < inner.re_states.len());
-        lD3!(SE, "set_re: ix {} to {:?}", ix, re);
-        inner.re_states[ix].split_dirty = bitvec![1; inner.split_ids.len()];
-        inner.re_states[ix].re_seq += 1;
-        inner.re_states[ix].re = re.clone();
+        lD3!(SE, "set_re: ix {} to {:?} all_lines {} count {}", ix, re, all_lines, count);
+        let nsplits = inner.split_ids.len();
+        let state = &mut inner.re_states[ix];
+        state.re_seq += 1;
+        state.re = re.clone();
+        state.all_lines = all_lines;
+        state.match_counts = count.then(|| vec![0; nsplits]);
+        if re.is_empty() {
+            // no need to read the file to find nothing
+            state.split_dirty = bitvec![0; nsplits];
+            state.split_has_matches = bitvec![0; nsplits];
+            self.inner.2.notify_all();
+        } else {
+            state.split_dirty = bitvec![1; nsplits];
+            self.inner.1.notify_all();
+        }
+    }
+
+    // the file has grown. The new lines are split again together with the last split, so
+    // it doesn't end up in lots of tiny splits. That and all new splits are marked dirty.
+    // Returns the first changed split
+    pub fn grow(&self) -> Result<Option<SplitId>> {
+        let mut inner = self.inner.0.lock().unwrap();
+        let last = inner.split_ids.len() - 1;
+        // a line still being written is picked up once it is complete
+        let len = complete_len(&inner.filename, inner.split_ids[last])?;
+        if inner.read_failed.any() {
+            // give splits that failed to read another chance
+            lD3!(SE, "grow: retrying {} unreadable splits", inner.read_failed.count_ones());
+            let failed = std::mem::replace(&mut inner.read_failed, bitvec![0; last + 1]);
+            for re in inner.re_states.iter_mut() {
+                if !re.re.is_empty() {
+                    re.split_dirty |= &failed;
+                }
+            }
+            self.inner.1.notify_all();
+        }
+        if len <= inner.split_ids[last] {
+            return Ok(None);
+        }
+        let start = if last > 0 {
+            inner.split_ids[last - 1]
+        } else {
+            0
+        };
+        let new_ids = split_file_from(&inner.filename, start, len, inner.chunk_size)?;
+        inner.split_ids.truncate(last);
+        inner.split_ids.extend(new_ids);
+        let nsplits = inner.split_ids.len();
+        let mut start = start;
+        for i in last..nsplits {
+            let end = inner.split_ids[i];
+            inner.max_split_len = inner.max_split_len.max(end - start);
+            start = end;
+        }
+        lD3!(SE, "grow: {} bytes, nsplits {} -> {}", len, last + 1, nsplits);
+
+        // results of splits in flight might be stale, have them redone
+        for re in inner.re_states.iter_mut() {
+            re.split_has_matches.resize(nsplits, false);
+            re.split_dirty.resize(nsplits, false);
+            re.split_dirty[last..].fill(true);
+            if let Some(counts) = re.match_counts.as_mut() {
+                counts.resize(nsplits, 0);
+            }
+            re.re_seq += 1;
+        }
+        inner.split_in_progress.resize(nsplits, false);
+        inner.read_failed.resize(nsplits, false);
+        inner.line_counts.truncate(last);
+        inner.line_counts.resize(nsplits, None);
+        inner.lines_dirty.resize(nsplits, false);
+        let lines_requested = inner.lines_requested;
+        inner.lines_dirty[last..].fill(lines_requested);
+        inner.lines_seq += 1;
+        inner.lines_before.truncate(last + 1);
+        self.inner.1.notify_all();
+
+        Ok(Some(last))
+    }
+
+    // start over with the file as it is now, after it was replaced or truncated
+    pub fn reset(&self) -> Result<()> {
+        let (filename, chunk_size) = {
+            let inner = self.inner.0.lock().unwrap();
+            (inner.filename.clone(), inner.chunk_size)
+        };
+        let split_ids = split_file(&filename, chunk_size)?;
+        let nsplits = split_ids.len();
+        let mut inner = self.inner.0.lock().unwrap();
+        let mut start = 0;
+        inner.max_split_len = 0;
+        for &end in &split_ids {
+            inner.max_split_len = inner.max_split_len.max(end - start);
+            start = end;
+        }
+        inner.split_ids = split_ids;
+        lD3!(SE, "reset: nsplits {}", nsplits);
+
+        for re in inner.re_states.iter_mut() {
+            re.split_has_matches = bitvec![0; nsplits];
+            re.split_dirty = BitVec::repeat(!re.re.is_empty(), nsplits);
+            if let Some(counts) = re.match_counts.as_mut() {
+                *counts = vec![0; nsplits];
+            }
+            re.re_seq += 1;
+        }
+        inner.split_in_progress = bitvec![0; nsplits];
+        inner.read_failed = bitvec![0; nsplits];
+        inner.read_error = None;
+        inner.current_split = 0;
+        inner.line_counts = vec![None; nsplits];
+        inner.lines_dirty = BitVec::repeat(inner.lines_requested, nsplits);
+        inner.lines_seq += 1;
+        inner.lines_before = vec![0];
+        inner.map = None;
+        inner.file_seq += 1;
         self.inner.1.notify_all();
+
+        Ok(())
     }
 
     pub fn set_current_split(&mut self, split_id: SplitId) {
@@ -129,13 +278,130 @@ impl FileSearch {
         inner.re_states[ix].split_has_matches[split_id]
     }
 
-    fn search_next(inner: &FileSearchInner, ix: usize) -> Option<SplitId> {
+    // whether the split has matches, None while it still is to be searched. Doesn't block
+    pub fn split_searched(&self, ix: usize, split_id: SplitId) -> Option<bool> {
+        let inner = self.inner.0.lock().unwrap();
+        let state = &inner.re_states[ix];
+
+        (!state.split_dirty[split_id]).then(|| state.split_has_matches[split_id])
+    }
+
+    // number of matching lines in the splits searched so far, and whether that are all of
+    // them. None if matches are not counted for this search. Doesn't block
+    pub fn match_count(&self, ix: usize) -> Option<(u64, bool)> {
+        let inner = self.inner.0.lock().unwrap();
+        let state = &inner.re_states[ix];
+        let counts = state.match_counts.as_ref()?;
+        let count = counts.iter().zip(state.split_dirty.iter())
+            .filter(|(_, dirty)| !**dirty)
+            .map(|(count, _)| count)
+            .sum();
+
+        Some((count, state.split_dirty.not_any()))
+    }
+
+    // search the mapped file instead of reading splits
+    pub fn set_map(&self, map: Arc<Mmap>) {
+        let mut inner = self.inner.0.lock().unwrap();
+        inner.map = Some(map);
+    }
+
+    pub fn report_read_error(&self, error: String) {
+        let mut inner = self.inner.0.lock().unwrap();
+        inner.read_error = Some(error);
+    }
+
+    // the last read error since the previous call
+    pub fn take_read_error(&self) -> Option<String> {
+        let mut inner = self.inner.0.lock().unwrap();
+        inner.read_error.take()
+    }
+
+    // start counting lines of all splits in the background
+    fn request_line_counts(&self, inner: &mut FileSearchInner) {
+        if inner.lines_requested {
+            return;
+        }
+        inner.lines_requested = true;
+        for i in 0..inner.line_counts.len() {
+            if inner.line_counts[i].is_none() {
+                inner.lines_dirty.set(i, true);
+            }
+        }
+        self.inner.1.notify_all();
+    }
+
+    fn store_line_count(inner: &mut FileSearchInner, split_id: SplitId, count: u64) {
+        if inner.line_counts[split_id] != Some(count) {
+            // the sums after this split are off now
+            inner.lines_before.truncate(split_id + 1);
+        }
+        inner.line_counts[split_id] = Some(count);
+        inner.lines_dirty.set(split_id, false);
+    }
+
+    // the line count of a split is known anyway when it is loaded
+    pub fn set_line_count(&self, split_id: SplitId, count: u64) {
+        let mut inner = self.inner.0.lock().unwrap();
+        Self::store_line_count(&mut inner, split_id, count);
+        self.inner.2.notify_all();
+    }
+
+    // number of lines in the splits counted so far, and whether that are all of them. Starts
+    // counting the rest in the background. Doesn't block
+    pub fn line_count(&self) -> (u64, bool) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.line_counts.iter().any(|c| c.is_none()) {
+            self.request_line_counts(&mut inner);
+        }
+        let count: u64 = inner.line_counts.iter().flatten().sum();
+
+        (count, inner.line_counts.iter().all(|c| c.is_some()))
+    }
+
+    // number of lines in all splits before the given one. Blocks until they are counted
+    pub fn lines_before(&self, split_id: SplitId) -> u64 {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.line_counts[..split_id].iter().any(|c| c.is_none()) {
+            self.request_line_counts(&mut inner);
+        }
+        while inner.lines_dirty[..split_id].any() {
+            inner = self.inner.2.wait(inner).unwrap();
+        }
+
+        Self::sum_lines_before(&mut inner, split_id)
+    }
+
+    // like lines_before, but None instead of waiting for the splits to be counted
+    pub fn counted_lines_before(&self, split_id: SplitId) -> Option<u64> {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.line_counts[..split_id].iter().any(|c| c.is_none()) {
+            self.request_line_counts(&mut inner);
+        }
+        if inner.lines_dirty[..split_id].any() {
+            return None;
+        }
+
+        Some(Self::sum_lines_before(&mut inner, split_id))
+    }
+
+    fn sum_lines_before(inner: &mut FileSearchInner, split_id: SplitId) -> u64 {
+        while inner.lines_before.len() <= split_id {
+            let i = inner.lines_before.len();
+            let before = inner.lines_before[i - 1] + inner.line_counts[i - 1].unwrap();
+            inner.lines_before.push(before);
+        }
+
+        inner.lines_before[split_id]
+    }
+
+    fn search_next(inner: &FileSearchInner, dirty: &BitSlice<usize, Lsb0>) -> Option<SplitId> {
         let mut search_up = Some(inner.current_split);
         let mut search_down = Some(inner.current_split);
         let mut found = None;
         while search_up.is_some() || search_down.is_some() {
             if let Some(id) = search_up {
-                let next = inner.re_states[ix].split_dirty[id..].first_one();
+                let next = dirty[id..].first_one();
                 if let Some(next) = next {
                     if inner.split_in_progress[id + next] {
                         search_up = if id + next < inner.split_ids.len() - 1 {
@@ -152,7 +418,7 @@ impl FileSearch {
                 }
             }
             if let Some(id) = search_down {
-                let next = inner.re_states[ix].split_dirty[..id].last_one();
+                let next = dirty[..id].last_one();
                 if let Some(next) = next {
                     if inner.split_in_progress[next] {
                         search_down = if next > 0 {
@@ -175,29 +441,38 @@ impl FileSearch {
 
     fn search_thread(&self) {
         let mut inner = self.inner.0.lock().unwrap();
-        let mut file = File::open(&inner.filename).unwrap();
-        let mut buf = vec![0; inner.max_split_len as usize];
+        let filename = inner.filename.clone();
+        let mut file = File::open(&filename).ok();
+        let mut file_seq = inner.file_seq;
+        let mut buf = Vec::with_capacity(inner.max_split_len as usize);
 
         loop {
+            // searches take precedence over counting lines
             let mut found = None;
-            let mut ix = 0;
             for i in 0..inner.re_states.len() {
-                if let Some(f) = Self::search_next(&inner, i) {
-                    found = Some(f);
-                    ix = i;
+                if let Some(f) = Self::search_next(&inner, &inner.re_states[i].split_dirty) {
+                    found = Some((f, Some(i)));
                     break;
                 }
             }
-            let Some(split_id) = found else {
+            if found.is_none() {
+                found = Self::search_next(&inner, &inner.lines_dirty).map(|f| (f, None));
+            }
+            let Some((split_id, ix)) = found else {
                 lD10!(SE, "no more dirty splits");
                 inner = self.inner.1.wait(inner).unwrap();
                 continue;
             };
-            lD10!(SE, "found dirty split: {} ix {}", split_id, ix);
+            // a replaced file is opened again
+            if inner.file_seq != file_seq {
+                file = None;
+                file_seq = inner.file_seq;
+            }
+            lD10!(SE, "found dirty split: {} ix {:?}", split_id, ix);
 
             inner.split_in_progress.set(split_id, true);
-            let re = inner.re_states[ix].re.clone();
-            let seq = inner.re_states[ix].re_seq;
+            let search = ix.map(|ix| (ix, inner.re_states[ix].re.clone(), inner.re_states[ix].re_seq,
+                inner.re_states[ix].all_lines, inner.re_states[ix].match_counts.is_some()));
 
             let start = if split_id > 0 {
                 inner.split_ids[split_id - 1]
@@ -205,20 +480,107 @@ impl FileSearch {
                 0
             };
             let end = inner.split_ids[split_id];
+            let lines_seq = inner.lines_seq;
+            let map = inner.map.clone().filter(|map| map.len() as u64 >= end);
             drop(inner);
 
-            // search split for all patterns, is_match
-            file.seek(std::io::SeekFrom::Start(start)).unwrap();
-            file.read_exact(&mut buf[..(end - start) as usize]).unwrap();
-            let m = re.is_match(&buf[..(end - start) as usize]);
+            let res = match map {
+                Some(_) => Ok(()),
+                None => read_split(&mut file, &filename, start, (end - start) as usize, &mut buf,
+                    true),
+            };
+            if let Err(e) = res {
+                lD1!(SE, "failed to read split {}: {}", split_id, e);
+                inner = self.inner.0.lock().unwrap();
+                if inner.file_seq != file_seq {
+                    continue;
+                }
+                inner.split_in_progress.set(split_id, false);
+                // nothing found in it for now, so nobody waits for it forever
+                inner.read_failed.set(split_id, true);
+                inner.read_error = Some(e.to_string());
+                match search {
+                    Some((ix, _, seq, _, _)) if inner.re_states[ix].re_seq == seq => {
+                        inner.re_states[ix].split_dirty.set(split_id, false);
+                        inner.re_states[ix].split_has_matches.set(split_id, false);
+                    }
+                    None if inner.lines_seq == lines_seq => {
+                        // it's shown as a single line
+                        Self::store_line_count(&mut inner, split_id, 1);
+                    }
+                    _ => {}
+                }
+                self.inner.2.notify_all();
+                continue;
+            }
+            let buf = match map {
+                Some(ref map) => &map[start as usize..end as usize],
+                None => &buf[..],
+            };
+
+            let Some((ix, re, seq, all_lines, count)) = search else {
+                // an unterminated last line counts, too, like when the split is read
+                let count = memchr::memchr_iter(b'\n', buf).count() as u64 +
+                    (!buf.is_empty() && !buf.ends_with(b"\n")) as u64;
+                inner = self.inner.0.lock().unwrap();
+                if inner.file_seq != file_seq {
+                    continue;
+                }
+                inner.split_in_progress.set(split_id, false);
+                // discard result if splits have changed
+                if inner.lines_seq == lines_seq {
+                    Self::store_line_count(&mut inner, split_id, count);
+                    lD10!(SE, "counted {} lines in split_id: {}", count, split_id);
+                    self.inner.2.notify_all();
+                }
+                continue;
+            };
+
+            // a pattern that changed while the split was read isn't searched for anymore
+            if self.abandon(split_id, ix, seq, file_seq) {
+                inner = self.inner.0.lock().unwrap();
+                continue;
+            }
+
+            // escape sequences don't span lines, so they can be removed from the whole split
+            let visible = re.visible(buf);
+            let buf = &visible[..];
 
-            // update split state with matches
+            // search split for all patterns, is_match. Plain text is found with memmem, the
+            // regex is only needed for the positions when lines are displayed
+            let m = if all_lines {
+                // lines without their newline, like they are matched when displayed
+                buf.strip_suffix(b"\n").unwrap_or(buf).split(|&b| b == b'\n')
+                    .all(|line| re.is_match(line))
+            } else {
+                re.is_match(buf)
+            };
+            if count && m && self.abandon(split_id, ix, seq, file_seq) {
+                inner = self.inner.0.lock().unwrap();
+                continue;
+            }
+            // lines without their newline again, so a line isn't counted twice
+            let matching_lines = if count && m {
+                buf.strip_suffix(b"\n").unwrap_or(buf).split(|&b| b == b'\n')
+                    .filter(|line| re.is_match(line))
+                    .count() as u64
+            } else {
+                0
+            };
+
+            // update split state with matches, unless the splits are gone with a reset
             inner = self.inner.0.lock().unwrap();
+            if inner.file_seq != file_seq {
+                continue;
+            }
             inner.split_in_progress.set(split_id, false);
             // discard result if pattern has changed
             if inner.re_states[ix].re_seq == seq {
                 inner.re_states[ix].split_dirty.set(split_id, false);
                 inner.re_states[ix].split_has_matches.set(split_id, m);
+                if let Some(counts) = inner.re_states[ix].match_counts.as_mut() {
+                    counts[split_id] = matching_lines;
+                }
                 if m {
                     lD5!(SE, "match in split split_id: {} ix {}", split_id, ix);
                 } else {
@@ -229,6 +591,24 @@ impl FileSearch {
         }
     }
 
+    // give up searching the split if the pattern has changed since, so the new one is
+    // searched for sooner. The split is searched again with it
+    fn abandon(&self, split_id: SplitId, ix: usize, seq: u64, file_seq: u64) -> bool {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.file_seq != file_seq {
+            // the splits are gone anyway
+            return true;
+        }
+        if inner.re_states[ix].re_seq == seq {
+            return false;
+        }
+        lD5!(SE, "pattern changed, abandoning split {} ix {}", split_id, ix);
+        inner.split_in_progress.set(split_id, false);
+        self.inner.1.notify_all();
+
+        true
+    }
+
     pub fn num_splits(&self) -> usize {
         let inner = self.inner.0.lock().unwrap();
 
@@ -281,6 +661,7 @@ impl FileSearch {
             dirty = dirty.max(re.split_dirty.count_ones());
             total = total.max(re.split_dirty.len());
         }
+        dirty = dirty.max(inner.lines_dirty.count_ones());
         if total == 0 {
             return 1.0;
         }
@@ -288,30 +669,142 @@ impl FileSearch {
     }
 }
 
+// replaces the content of buf with len bytes at start. read_to_end reads into the spare
+// capacity, so unlike read_exact into a slice, the buffer doesn't have to be zeroed first
+fn read_at(file: &mut Option<File>, name: &OsStr, start: u64, len: usize, buf: &mut Vec<u8>)
+    -> std::io::Result<()>
+{
+    let file = match file {
+        Some(file) => file,
+        None => file.insert(File::open(name)?),
+    };
+    file.seek(std::io::SeekFrom::Start(start))?;
+    buf.clear();
+    buf.reserve(len);
+    file.take(len as u64).read_to_end(buf)?;
+    if buf.len() < len {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof,
+            format!("file ends {} bytes early", len - buf.len())));
+    }
+
+    Ok(())
+}
+
+// read a split, retrying as errors can be transient, like on network file systems. The
+// file is opened again for each retry. Only the background thread waits between retries,
+// the UI thread tries once more right away and shows a placeholder if that fails, too
+pub fn read_split(file: &mut Option<File>, name: &OsStr, start: u64, len: usize,
+    buf: &mut Vec<u8>, background: bool) -> std::io::Result<()>
+{
+    let retries = if background { READ_RETRIES } else { 1 };
+    let mut tries = 0;
+    loop {
+        match read_at(file, name, start, len, buf) {
+            Ok(()) => return Ok(()),
+            Err(e) if tries == retries => return Err(e),
+            Err(e) => lD2!(SE, "read at {} failed: {}, retrying", start, e),
+        }
+        tries += 1;
+        if background {
+            std::thread::sleep(READ_RETRY_DELAY);
+        }
+        *file = None;
+    }
+}
+
 fn split_file(name: &OsStr, chunk_size: u64) -> std::io::Result<Vec<LineId>> {
+    let len = std::fs::metadata(name)?.len();
+    split_file_from(name, 0, len, chunk_size)
+}
+
+// end of the last complete line in the file, but not before from
+fn complete_len(name: &OsStr, from: u64) -> std::io::Result<u64> {
+    let mut file = File::open(name)?;
+    let mut end = file.metadata()?.len();
+    let mut buf = vec![0; 65536];
+    while end > from {
+        let start = end.saturating_sub(buf.len() as u64).max(from);
+        let chunk = &mut buf[..(end - start) as usize];
+        file.seek(std::io::SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(pos) = memchr::memrchr(b'\n', chunk) {
+            return Ok(start + pos as u64 + 1);
+        }
+        end = start;
+    }
+
+    Ok(from)
+}
+
+// split ends of the part of the file from..to
+fn split_file_from(name: &OsStr, from: u64, to: u64, chunk_size: u64)
+    -> std::io::Result<Vec<LineId>>
+{
     let mut splits = Vec::new();
     let mut file = std::fs::File::open(name)?;
     let mut buf = vec![0; 1];
-    let mut start = chunk_size;
+    let mut start = from.saturating_add(chunk_size);
     'a: loop {
         file.seek(std::io::SeekFrom::Start(start))?;
         let mut reader = BufReader::new(file);
         loop {
             let bytes_read = reader.read(&mut buf)?;
-            if bytes_read == 0 {
-                file = reader.into_inner();
+            if bytes_read == 0 || start >= to {
                 break 'a;
             }
             start += 1;
             if buf[0] == b'\n' {
                 splits.push(start);
-                start += chunk_size;
+                start = start.saturating_add(chunk_size);
                 break;
             }
         }
         file = reader.into_inner();
     }
-    splits.push(file.metadata().unwrap().len());
+    // a newline right at the end already ended the last split
+    if splits.last() != Some(&to) {
+        splits.push(to);
+    }
 
     Ok(splits)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempPath;
+
+    #[test]
+    fn test_abandon() {
+        let path = TempPath::file("abandon", "first\nsecond\n");
+        let mut fs = FileSearch::new(path.as_os_str(), 1, SPLIT_CHUNK_SIZE).unwrap();
+        let (seq, file_seq) = {
+            let inner = fs.inner.0.lock().unwrap();
+            (inner.re_states[0].re_seq, inner.file_seq)
+        };
+        assert!(!fs.abandon(0, 0, seq, file_seq));
+
+        // a search with the old pattern stops before its result is stored
+        fs.set_re(0, &LineMatcher::new(RegexSet::new(["second"]).unwrap()), false, false);
+        assert!(fs.abandon(0, 0, seq, file_seq));
+        assert!(!fs.abandon(0, 0, seq + 1, file_seq));
+        // as does one in a file that is read from scratch since
+        assert!(fs.abandon(0, 0, seq + 1, file_seq + 1));
+        assert!(fs.split_has_matches(0, 0));
+    }
+
+    #[test]
+    fn test_counted_lines_before() {
+        let path = TempPath::new("counted");
+        let content = (0..3000).map(|i| format!("line {:04}\n", i)).collect::<String>();
+        std::fs::write(&path, content).unwrap();
+        let fs = FileSearch::new(path.as_os_str(), 1, 4096).unwrap();
+        let last = fs.num_splits() - 1;
+        assert_eq!(fs.counted_lines_before(0), Some(0));
+        // without waiting there is no count yet or the right one
+        let counted = fs.counted_lines_before(last);
+        let before = fs.lines_before(last);
+        assert!(counted.is_none() || counted == Some(before));
+        assert_eq!(fs.counted_lines_before(last), Some(before));
+    }
+}
@@ -0,0 +1,162 @@
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub type Source = Box<dyn Read + Send>;
+
+const MAX_TEMP_TRIES: u32 = 100;
+
+// logrok needs a file it can seek in, so input from a pipe, from compressed files or from
+// several files is copied to a temporary file first. The file is removed again when the
+// spool is dropped
+#[derive(Debug)]
+pub struct Spool {
+    path: PathBuf,
+    bytes: Arc<AtomicU64>,
+    done: Arc<AtomicBool>,
+    error: Arc<Mutex<Option<io::Error>>>,
+    starts: Arc<Mutex<Vec<u64>>>,
+}
+
+// copy all sources into the file, each starting on a new line
+fn copy(sources: Vec<Source>, file: &mut File, bytes: &AtomicU64, starts: &Mutex<Vec<u64>>)
+    -> io::Result<()>
+{
+    let mut buf = vec![0; 65536];
+    let mut last = b'\n';
+    for mut source in sources {
+        if last != b'\n' {
+            file.write_all(b"\n")?;
+            bytes.fetch_add(1, Ordering::Relaxed);
+            last = b'\n';
+        }
+        starts.lock().unwrap().push(bytes.load(Ordering::Relaxed));
+        loop {
+            let n = match source.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            file.write_all(&buf[..n])?;
+            last = buf[n - 1];
+            bytes.fetch_add(n as u64, Ordering::Relaxed);
+        }
+    }
+
+    Ok(())
+}
+
+// a new file only we can read. The name is predictable, so never open what someone else
+// put there, not even through a symlink, but try the next name
+fn create_temp(name: &str) -> io::Result<(PathBuf, File)> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut i = 0;
+    loop {
+        let path = std::env::temp_dir()
+            .join(format!("logrok-{}-{}-{}", name, std::process::id(), i));
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && i < MAX_TEMP_TRIES => i += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl Spool {
+    // start copying the sources in the background
+    pub fn new(name: &str, sources: Vec<Source>) -> io::Result<Self> {
+        let (path, mut file) = create_temp(name)?;
+        let spool = Spool {
+            path,
+            bytes: Arc::new(AtomicU64::new(0)),
+            done: Arc::new(AtomicBool::new(false)),
+            error: Arc::new(Mutex::new(None)),
+            starts: Arc::new(Mutex::new(Vec::new())),
+        };
+        let (bytes, done, error, starts) = (spool.bytes.clone(), spool.done.clone(),
+            spool.error.clone(), spool.starts.clone());
+        std::thread::spawn(move || {
+            if let Err(e) = copy(sources, &mut file, &bytes, &starts) {
+                *error.lock().unwrap() = Some(e);
+            }
+            done.store(true, Ordering::Release);
+        });
+
+        Ok(spool)
+    }
+
+    pub fn stdin() -> io::Result<Self> {
+        Self::new("stdin", vec![Box::new(io::stdin())])
+    }
+
+    pub fn path(&self) -> &OsStr {
+        self.path.as_os_str()
+    }
+
+    // number of bytes copied so far
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    // offsets in the file where each of the sources starts
+    pub fn starts(&self) -> Vec<u64> {
+        self.starts.lock().unwrap().clone()
+    }
+
+    // Some once all sources hit EOF or reading failed
+    pub fn result(&self) -> Option<io::Result<()>> {
+        if !self.done.load(Ordering::Acquire) {
+            return None;
+        }
+        match self.error.lock().unwrap().take() {
+            Some(e) => Some(Err(e)),
+            None => Some(Ok(())),
+        }
+    }
+
+    // block until all sources are read
+    pub fn wait(&self) -> io::Result<()> {
+        loop {
+            if let Some(result) = self.result() {
+                return result;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spool_exclusive() {
+        // someone else's file where the spool would go is left alone
+        let taken = std::env::temp_dir()
+            .join(format!("logrok-rotated-{}-0", std::process::id()));
+        fs::write(&taken, "not ours\n").unwrap();
+        let sources: Vec<Source> = vec![Box::new(io::Cursor::new(b"a\nb".to_vec())),
+            Box::new(io::Cursor::new(b"c\n".to_vec()))];
+        let spool = Spool::new("rotated", sources).unwrap();
+        spool.wait().unwrap();
+        assert_ne!(spool.path(), taken.as_os_str());
+        assert_eq!(fs::read_to_string(spool.path()).unwrap(), "a\nb\nc\n");
+        assert_eq!(spool.starts(), vec![0, 4]);
+        drop(spool);
+        assert_eq!(fs::read_to_string(&taken).unwrap(), "not ours\n");
+        fs::remove_file(&taken).unwrap();
+    }
+}
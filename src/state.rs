@@ -0,0 +1,238 @@
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+use crate::lines::LineId;
+use crate::pattern::*;
+use crate::qhash::{self, QHash};
+
+// tags, hides and marks of a file, saved on quit and restored on the next start. The state
+// file is a simple line based format:
+//
+//   logrok-state 2
+//   qhash <len> <hash>
+//   tag <line id>
+//   hide <line id>
+//   pattern <mode> <match type> <anchor start> <anchor end> <ignore case> <color index> <pattern>
+//
+// version 1 files lack the ignore case field. A hiding pattern that hides the lines not
+// matching it has the mode HidingOthers
+const STATE_MAGIC: &str = "logrok-state 2";
+const STATE_MAGIC_V1: &str = "logrok-state 1";
+
+#[derive(Debug, Clone)]
+pub struct SavedPattern {
+    pub pattern: String,
+    pub match_type: MatchType,
+    pub anchor: Anchor,
+    pub ignore_case: bool,
+    pub mode: PatternMode,
+    pub negative: bool,
+    pub color: isize,
+}
+
+#[derive(Debug, Default)]
+pub struct State {
+    pub tagged_lines: BTreeSet<LineId>,
+    pub hidden_lines: BTreeSet<LineId>,
+    pub patterns: Vec<SavedPattern>,
+}
+
+// one state file per log file path, in the cache directory
+fn state_path(filename: &OsStr) -> Option<PathBuf> {
+    let cache = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    let path = fs::canonicalize(filename).ok()?;
+    let key = qhash::fnv1a(path.to_string_lossy().as_bytes());
+
+    Some(cache.join("logrok").join(format!("{:016x}.state", key)))
+}
+
+// the mode and if the pattern is negative
+fn parse_mode(s: &str) -> Option<(PatternMode, bool)> {
+    match s {
+        "Tagging" => Some((PatternMode::Tagging, false)),
+        "Hiding" => Some((PatternMode::Hiding, false)),
+        "HidingOthers" => Some((PatternMode::Hiding, true)),
+        "Marking" => Some((PatternMode::Marking, false)),
+        _ => None,
+    }
+}
+
+fn parse_match_type(s: &str) -> Option<MatchType> {
+    match s {
+        "BigWord" => Some(MatchType::BigWord),
+        "SmallWord" => Some(MatchType::SmallWord),
+        "Text" => Some(MatchType::Text),
+        "Regex" => Some(MatchType::Regex),
+        _ => None,
+    }
+}
+
+fn parse_pattern(rest: &str, v1: bool) -> Option<SavedPattern> {
+    let mut fields = rest.splitn(if v1 { 6 } else { 7 }, ' ');
+    let (mode, negative) = parse_mode(fields.next()?)?;
+    let match_type = parse_match_type(fields.next()?)?;
+    let anchor = Anchor {
+        start: fields.next()?.parse().ok()?,
+        end: fields.next()?.parse().ok()?,
+    };
+    let ignore_case = if v1 { false } else { fields.next()?.parse().ok()? };
+    let color = fields.next()?.parse().ok()?;
+    let pattern = fields.next()?.to_string();
+    // don't let a broken state file crash us later on
+    Regex::new(&match_type.build_re(&pattern, anchor, ignore_case, BIGWORD_DELIMITERS)).ok()?;
+
+    Some(SavedPattern { pattern, match_type, anchor, ignore_case, mode, negative, color })
+}
+
+fn parse(content: &str, filename: &OsStr) -> Result<Option<State>> {
+    let mut lines = content.lines();
+    let v1 = match lines.next() {
+        Some(STATE_MAGIC) => false,
+        Some(STATE_MAGIC_V1) => true,
+        _ => return Err(anyhow!("unknown state file format")),
+    };
+    let qhash = lines.next()
+        .and_then(|l| l.strip_prefix("qhash "))
+        .and_then(|l| l.split_once(' '))
+        .and_then(|(len, hash)| Some(QHash { len: len.parse().ok()?, hash: hash.parse().ok()? }))
+        .ok_or_else(|| anyhow!("state file without qhash"))?;
+    if !qhash::check(&qhash, filename)? {
+        // a different file by now
+        return Ok(None);
+    }
+
+    let mut state = State::default();
+    for line in lines {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match kind {
+            "tag" => { state.tagged_lines.insert(rest.parse()?); }
+            "hide" => { state.hidden_lines.insert(rest.parse()?); }
+            "pattern" => {
+                let pattern = parse_pattern(rest, v1)
+                    .ok_or_else(|| anyhow!("invalid pattern in state file: {}", rest))?;
+                state.patterns.push(pattern);
+            }
+            _ => return Err(anyhow!("invalid line in state file: {}", line)),
+        }
+    }
+
+    Ok(Some(state))
+}
+
+// the saved state of the file, if there is one and the file didn't change in between
+pub fn load(filename: &OsStr) -> Result<Option<State>> {
+    let Some(path) = state_path(filename) else {
+        return Ok(None);
+    };
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    parse(&content, filename)
+}
+
+fn write(path: &Path, state: &State, qhash: QHash) -> Result<()> {
+    let mut out = Vec::new();
+    writeln!(out, "{}", STATE_MAGIC)?;
+    writeln!(out, "qhash {} {}", qhash.len, qhash.hash)?;
+    for line_id in &state.tagged_lines {
+        writeln!(out, "tag {}", line_id)?;
+    }
+    for line_id in &state.hidden_lines {
+        writeln!(out, "hide {}", line_id)?;
+    }
+    for p in &state.patterns {
+        let mode = if p.negative { "HidingOthers".to_string() } else { format!("{:?}", p.mode) };
+        writeln!(out, "pattern {} {:?} {} {} {} {} {}", mode, p.match_type, p.anchor.start,
+            p.anchor.end, p.ignore_case, p.color, p.pattern)?;
+    }
+    // write atomically, another instance might read it just now
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, out)?;
+    fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+pub fn save(filename: &OsStr, state: &State) -> Result<()> {
+    let Some(path) = state_path(filename) else {
+        return Ok(());
+    };
+    if state.tagged_lines.is_empty() && state.hidden_lines.is_empty() &&
+        state.patterns.is_empty()
+    {
+        // nothing worth keeping
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    fs::create_dir_all(path.parent().unwrap())?;
+
+    write(&path, state, qhash::qhash(filename)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempPath;
+
+    #[test]
+    fn test_state_roundtrip() {
+        let log = TempPath::file("state-log", "first\nsecond\n");
+        let path = TempPath::new("state");
+
+        let mut state = State::default();
+        state.tagged_lines.insert(6);
+        state.hidden_lines.insert(0);
+        state.patterns.push(SavedPattern {
+            pattern: " a b ".to_string(),
+            match_type: MatchType::Text,
+            anchor: Anchor { start: true, end: false },
+            ignore_case: true,
+            mode: PatternMode::Tagging,
+            negative: false,
+            color: 3,
+        });
+        state.patterns.push(SavedPattern {
+            pattern: "req1".to_string(),
+            match_type: MatchType::SmallWord,
+            anchor: Anchor::default(),
+            ignore_case: false,
+            mode: PatternMode::Hiding,
+            negative: true,
+            color: 0,
+        });
+        write(&path, &state, qhash::qhash(log.as_os_str()).unwrap()).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+
+        // appending to the file keeps the state
+        fs::write(&log, "first\nsecond\nthird\n").unwrap();
+        let restored = parse(&content, log.as_os_str()).unwrap().unwrap();
+        assert_eq!(restored.tagged_lines, state.tagged_lines);
+        assert_eq!(restored.hidden_lines, state.hidden_lines);
+        assert_eq!(restored.patterns.len(), 2);
+        let p = &restored.patterns[0];
+        assert_eq!(p.pattern, " a b ");
+        assert_eq!(p.anchor, Anchor { start: true, end: false });
+        assert!(p.ignore_case);
+        assert_eq!((p.mode, p.match_type, p.color), (PatternMode::Tagging, MatchType::Text, 3));
+        assert!(!p.negative);
+        let p = &restored.patterns[1];
+        assert_eq!((p.mode, p.negative), (PatternMode::Hiding, true));
+
+        // changing it doesn't
+        fs::write(&log, "frist\nsecond\nthird\n").unwrap();
+        assert!(parse(&content, log.as_os_str()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,61 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+// a file or directory in the temp dir for a test, removed again when it goes out of scope,
+// even if the test failed
+pub struct TempPath {
+    path: PathBuf,
+}
+
+impl TempPath {
+    // tests run in parallel, so the name has to be unique over all of them
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("logrok-{}-{}", name, std::process::id()));
+        TempPath { path }
+    }
+
+    pub fn file(name: &str, content: impl AsRef<[u8]>) -> Self {
+        let temp = Self::new(name);
+        fs::write(&temp.path, content).unwrap();
+        temp
+    }
+
+    pub fn dir(name: &str) -> Self {
+        let temp = Self::new(name);
+        fs::create_dir_all(&temp.path).unwrap();
+        temp
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<OsStr> for TempPath {
+    fn as_ref(&self) -> &OsStr {
+        self.path.as_os_str()
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        // the test might have removed it already, or never got to create it
+        if self.path.is_dir() {
+            let _ = fs::remove_dir_all(&self.path);
+        } else {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::str::FromStr;
+use anyhow::{anyhow, Context, Result};
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::{MarkStyleSet, MarkType};
+
+// a theme file has one palette per line, `#` at the start of a line is a comment:
+//   mark = gray/blue, green/red bold, #ff8700/-
+//   search = bold
+// A style is FG[/BG] and modifiers, `-` leaves the color of the terminal. Colors are names,
+// #rrggbb or indexes. Palettes that aren't given keep their defaults
+pub fn parse(content: &str, sets: &mut [MarkStyleSet]) -> Result<()> {
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(anyhow!("line {}: expected palette = styles", i + 1));
+        };
+        let variant = match key.trim() {
+            "mark" => MarkType::Mark,
+            "tag" => MarkType::Tag,
+            "hide" => MarkType::Hide,
+            "search" => MarkType::Search,
+            key => return Err(anyhow!("line {}: unknown palette {}", i + 1, key)),
+        };
+        let styles = value.split(',')
+            .map(parse_style)
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("line {}: {}", i + 1, key.trim()))?;
+        sets[variant as usize] = MarkStyleSet { styles };
+    }
+
+    Ok(())
+}
+
+fn parse_color(s: &str) -> Result<Option<Color>> {
+    if s == "-" {
+        return Ok(None);
+    }
+
+    Color::from_str(s).map(Some).map_err(|_| anyhow!("unknown color {}", s))
+}
+
+fn parse_style(s: &str) -> Result<Style> {
+    let mut style = Style::default();
+    if s.trim().is_empty() {
+        return Err(anyhow!("empty style"));
+    }
+    for (i, word) in s.split_whitespace().enumerate() {
+        let modifier = match word {
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" => Modifier::UNDERLINED,
+            "reversed" => Modifier::REVERSED,
+            _ if i == 0 => {
+                let (fg, bg) = word.split_once('/').unwrap_or((word, "-"));
+                if let Some(fg) = parse_color(fg)? {
+                    style = style.fg(fg);
+                }
+                if let Some(bg) = parse_color(bg)? {
+                    style = style.bg(bg);
+                }
+                continue;
+            }
+            _ => return Err(anyhow!("unknown modifier {}", word)),
+        };
+        style = style.add_modifier(modifier);
+    }
+
+    Ok(style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        let mut sets = crate::MarkStyle::default_sets();
+        let search = sets[MarkType::Search as usize].styles.clone();
+        parse("# for dark terminals\nmark = gray/blue, #ff8700/- bold\n\ntag = -/red\n",
+            &mut sets).unwrap();
+        assert_eq!(sets[MarkType::Mark as usize].styles, vec![
+            Style::default().fg(Color::Gray).bg(Color::Blue),
+            Style::default().fg(Color::Rgb(0xff, 0x87, 0x00)).add_modifier(Modifier::BOLD),
+        ]);
+        assert_eq!(sets[MarkType::Tag as usize].styles, vec![Style::default().bg(Color::Red)]);
+        assert_eq!(sets[MarkType::Search as usize].styles, search);
+
+        assert!(parse("marks = red\n", &mut sets).is_err());
+        assert!(parse("tag = red,\n", &mut sets).is_err());
+        assert!(parse("tag = redish\n", &mut sets).is_err());
+        assert!(parse("tag = red blinking\n", &mut sets).is_err());
+    }
+}